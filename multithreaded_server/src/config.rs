@@ -0,0 +1,448 @@
+// Server configuration: defaults, an optional TOML file, environment variables and CLI flags.
+// Precedence (highest first): CLI flag > environment variable > config file > default.
+use clap::Parser;
+use serde::Deserialize;
+use std::{
+    error::Error,
+    fmt, fs, io,
+    net::{SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+    time::Duration,
+};
+
+// Defaults used when a setting is given nowhere else
+const DEFAULT_BIND: &str = "127.0.0.1:7878";
+const DEFAULT_WORKERS: usize = 4;
+const DEFAULT_DOCUMENT_ROOT: &str = ".";
+const DEFAULT_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 20;
+const DEFAULT_MAX_HEADER_SIZE: usize = 8 * 1024;
+const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// A small multithreaded HTTP server.
+///
+/// Every flag can also be set through the environment variable shown next to it,
+/// or through the TOML file passed with `--config`.
+#[derive(Parser, Debug, Default)]
+#[command(name = "multithreaded_server", version)]
+pub struct Args {
+    /// Path to a TOML configuration file
+    #[arg(short, long, env = "SERVER_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Address to listen on, e.g. 127.0.0.1:7878
+    #[arg(short, long, env = "SERVER_BIND")]
+    pub bind: Option<String>,
+
+    /// Number of worker threads in the pool
+    #[arg(short, long, env = "SERVER_WORKERS")]
+    pub workers: Option<usize>,
+
+    /// Directory the HTML files are served from
+    #[arg(short, long, env = "SERVER_DOCUMENT_ROOT")]
+    pub document_root: Option<PathBuf>,
+
+    /// Per-connection read timeout in seconds
+    #[arg(long, env = "SERVER_READ_TIMEOUT_SECS")]
+    pub read_timeout_secs: Option<u64>,
+
+    /// Per-connection write timeout in seconds
+    #[arg(long, env = "SERVER_WRITE_TIMEOUT_SECS")]
+    pub write_timeout_secs: Option<u64>,
+
+    /// Total time allowed to receive a whole request, in seconds
+    #[arg(long, env = "SERVER_REQUEST_TIMEOUT_SECS")]
+    pub request_timeout_secs: Option<u64>,
+
+    /// Largest request line plus headers accepted, in bytes
+    #[arg(long, env = "SERVER_MAX_HEADER_SIZE")]
+    pub max_header_size: Option<usize>,
+
+    /// Largest request body accepted, in bytes
+    #[arg(long, env = "SERVER_MAX_BODY_SIZE")]
+    pub max_body_size: Option<usize>,
+
+    /// Address for an additional HTTPS listener (requires the `tls` feature)
+    #[arg(long, env = "SERVER_TLS_BIND")]
+    pub tls_bind: Option<String>,
+
+    /// PEM file with the HTTPS certificate chain
+    #[arg(long, env = "SERVER_TLS_CERT")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM file with the HTTPS private key
+    #[arg(long, env = "SERVER_TLS_KEY")]
+    pub tls_key: Option<PathBuf>,
+}
+
+// Shape of the TOML file; every key is optional and unknown keys are rejected
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+    bind: Option<String>,
+    workers: Option<usize>,
+    document_root: Option<PathBuf>,
+    read_timeout_secs: Option<u64>,
+    write_timeout_secs: Option<u64>,
+    request_timeout_secs: Option<u64>,
+    max_header_size: Option<usize>,
+    max_body_size: Option<usize>,
+    tls_bind: Option<String>,
+    tls_cert: Option<PathBuf>,
+    tls_key: Option<PathBuf>,
+}
+
+// Fully resolved and validated configuration
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub bind: SocketAddr,
+    pub workers: usize,
+    pub document_root: PathBuf,
+    pub read_timeout: Duration,
+    pub write_timeout: Duration,
+    // Deadline for reading the request line, headers and body
+    pub request_timeout: Duration,
+    pub max_header_size: usize,
+    pub max_body_size: usize,
+    // Present when the HTTPS listener is enabled
+    pub tls: Option<TlsConfig>,
+}
+
+// Where to listen for HTTPS and which certificate to present
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub bind: SocketAddr,
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+// Everything that can go wrong while loading the configuration
+#[derive(Debug)]
+pub enum ConfigError {
+    // The config file could not be read
+    Read {
+        path: PathBuf,
+        source: io::Error,
+    },
+    // The config file is not valid TOML or has unexpected keys
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    // A setting has a value the server cannot run with
+    Invalid {
+        field: &'static str,
+        message: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, source } => {
+                write!(f, "cannot read config file {}: {source}", path.display())
+            }
+            ConfigError::Parse { path, source } => {
+                write!(f, "cannot parse config file {}: {source}", path.display())
+            }
+            ConfigError::Invalid { field, message } => write!(f, "invalid `{field}`: {message}"),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Read { source, .. } => Some(source),
+            ConfigError::Parse { source, .. } => Some(source),
+            ConfigError::Invalid { .. } => None,
+        }
+    }
+}
+
+impl Config {
+    // Build the configuration from parsed arguments (which already include env overrides)
+    pub fn load(args: Args) -> Result<Config, ConfigError> {
+        // Read the config file only if one was given
+        let file = match &args.config {
+            Some(path) => {
+                let contents = fs::read_to_string(path).map_err(|source| ConfigError::Read {
+                    path: path.clone(),
+                    source,
+                })?;
+                toml::from_str(&contents).map_err(|source| ConfigError::Parse {
+                    path: path.clone(),
+                    source,
+                })?
+            }
+            None => FileConfig::default(),
+        };
+
+        Config::merge(args, file)
+    }
+
+    // Layer the arguments over the file over the defaults, then validate the result
+    fn merge(args: Args, file: FileConfig) -> Result<Config, ConfigError> {
+        let bind = args
+            .bind
+            .or(file.bind)
+            .unwrap_or_else(|| DEFAULT_BIND.to_string());
+        let workers = args.workers.or(file.workers).unwrap_or(DEFAULT_WORKERS);
+        let document_root = args
+            .document_root
+            .or(file.document_root)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_DOCUMENT_ROOT));
+        let read_timeout_secs = args
+            .read_timeout_secs
+            .or(file.read_timeout_secs)
+            .unwrap_or(DEFAULT_TIMEOUT_SECS);
+        let write_timeout_secs = args
+            .write_timeout_secs
+            .or(file.write_timeout_secs)
+            .unwrap_or(DEFAULT_TIMEOUT_SECS);
+        let request_timeout_secs = args
+            .request_timeout_secs
+            .or(file.request_timeout_secs)
+            .unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECS);
+        let max_header_size = args
+            .max_header_size
+            .or(file.max_header_size)
+            .unwrap_or(DEFAULT_MAX_HEADER_SIZE);
+        let max_body_size = args
+            .max_body_size
+            .or(file.max_body_size)
+            .unwrap_or(DEFAULT_MAX_BODY_SIZE);
+
+        Ok(Config {
+            bind: validate_bind(&bind)?,
+            workers: validate_workers(workers)?,
+            document_root: validate_document_root(document_root)?,
+            read_timeout: validate_timeout("read_timeout_secs", read_timeout_secs)?,
+            write_timeout: validate_timeout("write_timeout_secs", write_timeout_secs)?,
+            request_timeout: validate_timeout("request_timeout_secs", request_timeout_secs)?,
+            max_header_size: validate_max_header_size(max_header_size)?,
+            max_body_size,
+            tls: validate_tls(
+                args.tls_bind.or(file.tls_bind),
+                args.tls_cert.or(file.tls_cert),
+                args.tls_key.or(file.tls_key),
+            )?,
+        })
+    }
+}
+
+// The bind address may be a hostname, so resolve it and take the first address
+fn validate_bind(bind: &str) -> Result<SocketAddr, ConfigError> {
+    resolve("bind", bind)
+}
+
+// Resolve a host:port string for the given setting
+fn resolve(field: &'static str, bind: &str) -> Result<SocketAddr, ConfigError> {
+    let invalid = |message: String| ConfigError::Invalid { field, message };
+
+    bind.to_socket_addrs()
+        .map_err(|e| invalid(format!("`{bind}` is not a valid address: {e}")))?
+        .next()
+        .ok_or_else(|| invalid(format!("`{bind}` did not resolve to any address")))
+}
+
+// ThreadPool::new panics on zero, so catch it here with a readable message
+fn validate_workers(workers: usize) -> Result<usize, ConfigError> {
+    if workers == 0 {
+        return Err(ConfigError::Invalid {
+            field: "workers",
+            message: "must be at least 1".to_string(),
+        });
+    }
+    Ok(workers)
+}
+
+// Even the shortest request needs a request line, so a zero limit would refuse everything
+fn validate_max_header_size(size: usize) -> Result<usize, ConfigError> {
+    if size == 0 {
+        return Err(ConfigError::Invalid {
+            field: "max_header_size",
+            message: "must be at least 1 byte".to_string(),
+        });
+    }
+    Ok(size)
+}
+
+// HTTPS is enabled by `tls_bind` and then needs both a certificate and a key
+fn validate_tls(
+    bind: Option<String>,
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
+) -> Result<Option<TlsConfig>, ConfigError> {
+    let Some(bind) = bind else {
+        // A certificate without a listener is almost certainly a mistake
+        if cert.is_some() || key.is_some() {
+            return Err(ConfigError::Invalid {
+                field: "tls_bind",
+                message: "must be set when `tls_cert` or `tls_key` is given".to_string(),
+            });
+        }
+        return Ok(None);
+    };
+
+    if !cfg!(feature = "tls") {
+        return Err(ConfigError::Invalid {
+            field: "tls_bind",
+            message: "this build has no TLS support; rebuild with `--features tls`".to_string(),
+        });
+    }
+
+    let missing = |field| ConfigError::Invalid {
+        field,
+        message: "is required when `tls_bind` is set".to_string(),
+    };
+
+    Ok(Some(TlsConfig {
+        bind: resolve("tls_bind", &bind)?,
+        cert: cert.ok_or_else(|| missing("tls_cert"))?,
+        key: key.ok_or_else(|| missing("tls_key"))?,
+    }))
+}
+
+// The document root must be an existing directory
+fn validate_document_root(root: PathBuf) -> Result<PathBuf, ConfigError> {
+    if !root.is_dir() {
+        return Err(ConfigError::Invalid {
+            field: "document_root",
+            message: format!("`{}` is not a directory", root.display()),
+        });
+    }
+    Ok(root)
+}
+
+// A zero timeout is rejected by TcpStream::set_read_timeout, so refuse it up front
+fn validate_timeout(field: &'static str, secs: u64) -> Result<Duration, ConfigError> {
+    if secs == 0 {
+        return Err(ConfigError::Invalid {
+            field,
+            message: "must be at least 1 second".to_string(),
+        });
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    // Parse a TOML snippet the same way a config file would be
+    fn file(contents: &str) -> FileConfig {
+        toml::from_str(contents).unwrap()
+    }
+
+    // Parse command-line flags alone, so SERVER_* variables set where the tests run are ignored
+    fn args(argv: &[&str]) -> Args {
+        let command = Args::command().mut_args(|arg| arg.env(None));
+        Args::from_arg_matches(&command.try_get_matches_from(argv).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn defaults_when_nothing_is_set() {
+        let config = Config::merge(Args::default(), FileConfig::default()).unwrap();
+
+        assert_eq!(config.bind, "127.0.0.1:7878".parse().unwrap());
+        assert_eq!(config.workers, 4);
+        assert_eq!(config.document_root, PathBuf::from("."));
+        assert_eq!(config.read_timeout, Duration::from_secs(10));
+        assert_eq!(config.write_timeout, Duration::from_secs(10));
+        assert_eq!(config.request_timeout, Duration::from_secs(20));
+        assert_eq!(config.max_header_size, 8 * 1024);
+        assert_eq!(config.max_body_size, 1024 * 1024);
+        assert!(config.tls.is_none());
+    }
+
+    #[test]
+    fn file_values_are_used() {
+        let file = file(
+            r#"
+            bind = "127.0.0.1:9000"
+            workers = 8
+            read_timeout_secs = 5
+            max_body_size = 10
+            "#,
+        );
+        let config = Config::merge(Args::default(), file).unwrap();
+
+        assert_eq!(config.bind, "127.0.0.1:9000".parse().unwrap());
+        assert_eq!(config.workers, 8);
+        assert_eq!(config.read_timeout, Duration::from_secs(5));
+        assert_eq!(config.max_body_size, 10);
+    }
+
+    #[test]
+    fn arguments_override_file() {
+        let file = file("workers = 8\nbind = \"127.0.0.1:9000\"");
+        let args = args(&["server", "--workers", "2"]);
+        let config = Config::merge(args, file).unwrap();
+
+        assert_eq!(config.workers, 2);
+        assert_eq!(config.bind, "127.0.0.1:9000".parse().unwrap());
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(toml::from_str::<FileConfig>("wokers = 8").is_err());
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let cases = [
+            ("workers = 0", "workers"),
+            ("bind = \"not an address\"", "bind"),
+            ("document_root = \"/does/not/exist\"", "document_root"),
+            ("write_timeout_secs = 0", "write_timeout_secs"),
+            ("request_timeout_secs = 0", "request_timeout_secs"),
+            ("max_header_size = 0", "max_header_size"),
+            ("tls_cert = \"cert.pem\"", "tls_bind"),
+        ];
+
+        for (contents, expected) in cases {
+            match Config::merge(Args::default(), file(contents)) {
+                Err(ConfigError::Invalid { field, .. }) => assert_eq!(field, expected),
+                other => panic!("expected invalid `{expected}`, got {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "tls")]
+    fn tls_needs_certificate_and_key() {
+        let config = file("tls_bind = \"127.0.0.1:7879\"\ntls_key = \"key.pem\"");
+        match Config::merge(Args::default(), config) {
+            Err(ConfigError::Invalid { field, .. }) => assert_eq!(field, "tls_cert"),
+            other => panic!("expected invalid `tls_cert`, got {other:?}"),
+        }
+
+        let config =
+            file("tls_bind = \"127.0.0.1:7879\"\ntls_cert = \"cert.pem\"\ntls_key = \"key.pem\"");
+        let tls = Config::merge(Args::default(), config).unwrap().tls.unwrap();
+        assert_eq!(tls.bind, "127.0.0.1:7879".parse().unwrap());
+        assert_eq!(tls.cert, PathBuf::from("cert.pem"));
+    }
+
+    #[test]
+    #[cfg(not(feature = "tls"))]
+    fn tls_without_feature_is_rejected() {
+        let config = file("tls_bind = \"127.0.0.1:7879\"");
+        let err = Config::merge(Args::default(), config).unwrap_err();
+        assert!(err.to_string().contains("--features tls"));
+    }
+
+    #[test]
+    fn missing_config_file_is_reported() {
+        let args = Args {
+            config: Some(PathBuf::from("/does/not/exist.toml")),
+            ..Args::default()
+        };
+
+        let err = Config::load(args).unwrap_err();
+        assert!(matches!(err, ConfigError::Read { .. }));
+        assert!(err.to_string().contains("/does/not/exist.toml"));
+    }
+}
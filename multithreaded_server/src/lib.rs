@@ -1,20 +1,46 @@
 // Arc + Mutex for safe sharing of reciever
 // mpsc for job queue
 use std::{
-    sync::{mpsc, Arc, Mutex},
+    sync::{Arc, Mutex, mpsc},
     thread,
 };
 
+// Command-line, environment and file based server configuration
+pub mod config;
+// Reading requests under timeouts and size limits, and serving responses
+pub mod server;
+// Optional HTTPS listener
+#[cfg(feature = "tls")]
+pub mod tls;
+
 // Public ThreadPool struct to manage a set of worker threads
 pub struct ThreadPool {
-    workers: Vec<Worker>,
+    // Mutex so the pool can be resized through a shared reference
+    workers: Mutex<Workers>,
     // Optional so we can take() and drop on shutdown
-    sender: Option<mpsc::Sender<Job>>,
+    sender: Option<mpsc::Sender<Message>>,
+    // Shared with every worker, including ones spawned by resize()
+    receiver: Arc<Mutex<mpsc::Receiver<Message>>>,
+}
+
+// Worker bookkeeping guarded by the pool's mutex
+struct Workers {
+    threads: Vec<Worker>,
+    // Number of workers the pool is sized for once pending retirements are processed
+    size: usize,
+    // Id handed to the next spawned worker
+    next_id: usize,
 }
 
 // Job is a trait object to allow sending any `FnOnce` task
 type Job = Box<dyn FnOnce() + Send + 'static>;
 
+// Messages travel through the same queue so retiring a worker never overtakes queued jobs
+enum Message {
+    NewJob(Job),
+    Terminate,
+}
+
 impl ThreadPool {
     // Create a new ThreadPool with the given size
     pub fn new(size: usize) -> ThreadPool {
@@ -28,17 +54,63 @@ impl ThreadPool {
         let receiver = Arc::new(Mutex::new(receiver));
 
         // Preallocate the vector
-        let mut workers = Vec::with_capacity(size);
+        let mut threads = Vec::with_capacity(size);
 
         // Spawn worker threads
         for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
+            threads.push(Worker::new(id, Arc::clone(&receiver)));
         }
 
         ThreadPool {
-            workers,
+            workers: Mutex::new(Workers {
+                threads,
+                size,
+                next_id: size,
+            }),
             sender: Some(sender),
+            receiver,
+        }
+    }
+
+    // Number of workers the pool is currently sized for
+    pub fn size(&self) -> usize {
+        self.workers.lock().unwrap().size
+    }
+
+    // Grow or shrink the pool to `size` workers without dropping queued jobs
+    pub fn resize(&self, size: usize) {
+        // Panic if size is zero, as in new()
+        assert!(size > 0);
+
+        let mut workers = self.workers.lock().unwrap();
+
+        // Join workers that already exited after an earlier shrink
+        workers.threads.retain_mut(|worker| {
+            let finished = worker.thread.as_ref().is_some_and(|t| t.is_finished());
+            if finished {
+                worker.join();
+            }
+            !finished
+        });
+
+        if size > workers.size {
+            // Grow: spawn the extra workers straight away
+            for _ in workers.size..size {
+                let id = workers.next_id;
+                workers.next_id += 1;
+                workers
+                    .threads
+                    .push(Worker::new(id, Arc::clone(&self.receiver)));
+            }
+        } else {
+            // Shrink: queue one Terminate per surplus worker behind the pending jobs,
+            // so every job already submitted still runs before a worker retires
+            for _ in size..workers.size {
+                self.send(Message::Terminate);
+            }
         }
+
+        workers.size = size;
     }
 
     // Execute a task by sending it to the job queue
@@ -50,7 +122,12 @@ impl ThreadPool {
         let job = Box::new(f);
 
         // Send job to the workers
-        self.sender.as_ref().unwrap().send(job).unwrap();
+        self.send(Message::NewJob(job));
+    }
+
+    // Push a message onto the shared queue
+    fn send(&self, message: Message) {
+        self.sender.as_ref().unwrap().send(message).unwrap();
     }
 }
 
@@ -61,14 +138,12 @@ impl Drop for ThreadPool {
         drop(self.sender.take());
 
         // Join all worker threads
-        for worker in &mut self.workers {
+        for worker in &mut self.workers.get_mut().unwrap().threads {
             // Print message of which worker is shutting down
             println!("Shutting down worker {}", worker.id);
 
             // If the worker has an active thread, join it to ensure cleanup
-            if let Some(thread) = worker.thread.take() {
-                thread.join().unwrap();
-            }
+            worker.join();
         }
     }
 }
@@ -82,7 +157,7 @@ struct Worker {
 
 impl Worker {
     // Create and start a new worker thread
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Worker {
+    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Message>>>) -> Worker {
         // Spawn a new thread that will continuously receive and execute jobs
         let thread = thread::spawn(move || {
             // Continuously listen for incoming jobs
@@ -93,11 +168,16 @@ impl Worker {
                 // Handle the received message
                 match message {
                     // Execute the job
-                    Ok(job) => {
+                    Ok(Message::NewJob(job)) => {
                         println!("Worker {id} got a job; executing.");
 
                         job();
                     }
+                    // Retire this worker after the pool was shrunk
+                    Ok(Message::Terminate) => {
+                        println!("Worker {id} retired; shutting down.");
+                        break;
+                    }
                     // Exit if disconnected
                     Err(_) => {
                         // Exit loop if channel is closed (e.g., ThreadPool is dropped)
@@ -113,4 +193,98 @@ impl Worker {
             thread: Some(thread),
         }
     }
+
+    // Wait for the thread to finish if it has not been joined yet
+    fn join(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::{
+            Barrier,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    // Count the worker threads that have not exited yet
+    fn live_workers(pool: &ThreadPool) -> usize {
+        let workers = pool.workers.lock().unwrap();
+        workers
+            .threads
+            .iter()
+            .filter(|w| w.thread.as_ref().is_some_and(|t| !t.is_finished()))
+            .count()
+    }
+
+    #[test]
+    fn grow_runs_more_jobs_at_once() {
+        let pool = ThreadPool::new(1);
+        pool.resize(4);
+        assert_eq!(pool.size(), 4);
+
+        // Four jobs can only meet at the barrier if four workers run them concurrently
+        let barrier = Arc::new(Barrier::new(5));
+        for _ in 0..4 {
+            let barrier = Arc::clone(&barrier);
+            pool.execute(move || {
+                barrier.wait();
+            });
+        }
+        barrier.wait();
+    }
+
+    #[test]
+    fn shrink_keeps_queued_jobs() {
+        let pool = ThreadPool::new(4);
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        // Queue more jobs than workers, then shrink while they are pending
+        for _ in 0..20 {
+            let counter = Arc::clone(&counter);
+            pool.execute(move || {
+                thread::sleep(Duration::from_millis(5));
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        pool.resize(1);
+        assert_eq!(pool.size(), 1);
+
+        // Jobs submitted after the shrink still run on the remaining worker
+        for _ in 0..5 {
+            let counter = Arc::clone(&counter);
+            pool.execute(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        drop(pool);
+        assert_eq!(counter.load(Ordering::SeqCst), 25);
+    }
+
+    #[test]
+    fn shrink_retires_surplus_workers() {
+        let pool = ThreadPool::new(4);
+        pool.resize(2);
+
+        // Retirement is asynchronous, so wait for the workers to pick up Terminate
+        for _ in 0..100 {
+            if live_workers(&pool) == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(live_workers(&pool), 2);
+
+        // Finished workers are reaped on the next resize
+        pool.resize(3);
+        assert_eq!(pool.workers.lock().unwrap().threads.len(), 3);
+        assert_eq!(live_workers(&pool), 3);
+    }
 }
@@ -1,61 +1,97 @@
-use hello::ThreadPool; // Import the custom thread pool implementation from the `hello` crate/module
+use clap::Parser; // Parse command-line flags (and their environment variable fallbacks)
+#[cfg(feature = "tls")]
+use hello::config::TlsConfig; // Where the HTTPS listener binds and its certificate files
+use hello::{
+    ThreadPool, // Import the custom thread pool implementation from the `hello` crate/module
+    config::{Args, Config}, // Server configuration loaded at startup
+    server::handle_connection, // Per-connection request handling
+};
 use std::{
-    fs,
-    io::{prelude::*, BufReader}, // Used for reading lines from the stream
-    net::{TcpListener, TcpStream}, // For network connections
-    thread,
-    time::Duration,
+    net::TcpListener, // For network connections
+    process,
+    sync::Arc,
 };
 
 fn main() {
-    // Bind the TCP listener to address 127.0.0.1:7878
-    let listener = TcpListener::bind("127.0.0.1:7878").unwrap();
+    // Load the configuration from CLI flags, environment variables and the optional TOML file
+    let config = Config::load(Args::parse()).unwrap_or_else(|err| {
+        eprintln!("Configuration error: {err}");
+        process::exit(1);
+    });
+
+    // Share the configuration with every connection handler
+    let config = Arc::new(config);
+
+    // Bind the TCP listener to the configured address
+    let listener = TcpListener::bind(config.bind).unwrap_or_else(|err| {
+        eprintln!("Failed to bind {}: {err}", config.bind);
+        process::exit(1);
+    });
+
+    // Create a thread pool with the configured number of worker threads, shared by all listeners
+    let pool = Arc::new(ThreadPool::new(config.workers));
 
-    // Create a thread pool with 4 worker threads
-    let pool = ThreadPool::new(4);
+    // Serve HTTPS on its own listener when it is configured
+    #[cfg(feature = "tls")]
+    if let Some(tls) = &config.tls {
+        spawn_tls_listener(tls, Arc::clone(&pool), Arc::clone(&config));
+    }
 
     // Accept and handle only 2 incoming connections before shutting down
     for stream in listener.incoming().take(2) {
-        let stream = stream.unwrap();
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("Failed to accept connection: {err}");
+                continue;
+            }
+        };
+        let config = Arc::clone(&config);
 
         // Submit the connection handling task to the thread pool
-        pool.execute(|| {
-            handle_connection(stream);
+        pool.execute(move || {
+            if let Err(err) = handle_connection(stream, &config) {
+                eprintln!("Connection error: {err}");
+            }
         });
     }
 
     println!("Shutting down.");
 }
 
-// Handle the client connection by reading the request and sending a response
-fn handle_connection(mut stream: TcpStream) {
-    // Wrap the stream in a buffered reader to enable convenient line-by-line reading
-    let buf_reader = BufReader::new(&stream);
-    // Read the first request line
-    let request_line = buf_reader.lines().next().unwrap().unwrap();
-
-    // Match the request line and determine the appropriate response
-    let (status_line, filename) = match &request_line[..] {
-        // Serve the homepage for root path
-        "GET / HTTP/1.1" => ("HTTP/1.1 200 OK", "hello.html"),
-
-        // Simulate a delayed response to test server concurrency handling
-        "GET /sleep HTTP/1.1" => {
-            thread::sleep(Duration::from_secs(5));
-            ("HTTP/1.1 200 OK", "hello.html")
-        }
-
-        // Respond with 404 for all other (unrecognized) paths
-        _ => ("HTTP/1.1 404 NOT FOUND", "404.html"),
-    };
+// Load the certificate, bind the HTTPS address and accept TLS connections on a background thread
+#[cfg(feature = "tls")]
+fn spawn_tls_listener(tls: &TlsConfig, pool: Arc<ThreadPool>, config: Arc<Config>) {
+    let tls_config = hello::tls::load_server_config(&tls.cert, &tls.key).unwrap_or_else(|err| {
+        eprintln!("TLS configuration error: {err}");
+        process::exit(1);
+    });
 
-    // Read the content of the corresponding HTML file
-    let contents = fs::read_to_string(filename).unwrap();
-    let length = contents.len();
+    let listener = TcpListener::bind(tls.bind).unwrap_or_else(|err| {
+        eprintln!("Failed to bind {}: {err}", tls.bind);
+        process::exit(1);
+    });
 
-    // Format the full HTTP response
-    let response = format!("{status_line}\r\nContent-Length: {length}\r\n\r\n{contents}");
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    eprintln!("Failed to accept TLS connection: {err}");
+                    continue;
+                }
+            };
+            let tls_config = Arc::clone(&tls_config);
+            let config = Arc::clone(&config);
 
-    // Send the response to the client
-    stream.write_all(response.as_bytes()).unwrap();
+            // The handshake happens inside the worker, under the request deadline
+            pool.execute(move || {
+                let result = hello::tls::accept(stream, tls_config)
+                    .and_then(|stream| handle_connection(stream, &config));
+                if let Err(err) = result {
+                    eprintln!("TLS connection error: {err}");
+                }
+            });
+        }
+    });
 }
@@ -0,0 +1,201 @@
+// Connection handling: read a request within time and size limits, then serve a file
+use crate::config::Config;
+use std::{
+    fs,
+    io::{self, BufReader, prelude::*},
+    net::TcpStream,
+    thread,
+    time::{Duration, Instant},
+};
+
+// Reasons a request could not be read in full
+enum RequestError {
+    // The client stalled past the read timeout or the total request deadline
+    TimedOut,
+    // The request line plus headers exceeded `max_header_size`
+    HeaderTooLarge,
+    // The declared body exceeded `max_body_size`
+    BodyTooLarge,
+    // The head could not be understood, such as a malformed Content-Length
+    BadRequest,
+    // Any other IO failure; the connection is just closed
+    Io(io::Error),
+}
+
+impl From<io::Error> for RequestError {
+    fn from(err: io::Error) -> Self {
+        // Socket read timeouts surface as WouldBlock on Unix and TimedOut on Windows
+        match err.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => RequestError::TimedOut,
+            _ => RequestError::Io(err),
+        }
+    }
+}
+
+// A stream the server can speak HTTP over: plain TCP, or TLS layered on top of it
+pub trait Connection: Read + Write {
+    // The underlying socket, used to apply timeouts
+    fn socket(&self) -> &TcpStream;
+
+    // Finish the connection cleanly once the response is sent
+    fn close(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Connection for TcpStream {
+    fn socket(&self) -> &TcpStream {
+        self
+    }
+}
+
+// Reader that shrinks each read timeout so the whole request must arrive before `deadline`.
+// Without this a client dripping one byte per read timeout could hold a worker forever.
+struct DeadlineReader<'a, C> {
+    stream: &'a mut C,
+    read_timeout: Duration,
+    deadline: Instant,
+}
+
+impl<C: Connection> Read for DeadlineReader<'_, C> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Give up once the deadline has passed
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+
+        // Wait no longer than the read timeout or the time left, whichever is shorter
+        self.stream
+            .socket()
+            .set_read_timeout(Some(remaining.min(self.read_timeout)))?;
+        self.stream.read(buf)
+    }
+}
+
+// Handle the client connection by reading the request and sending a response
+pub fn handle_connection<C: Connection>(mut stream: C, config: &Config) -> io::Result<()> {
+    serve_request(&mut stream, config)?;
+    stream.close()
+}
+
+// Read a single request and answer it
+fn serve_request<C: Connection>(stream: &mut C, config: &Config) -> io::Result<()> {
+    // A client that stops reading must not hold a worker forever either
+    stream
+        .socket()
+        .set_write_timeout(Some(config.write_timeout))?;
+
+    // Wrap the stream in a buffered reader bounded by the request deadline
+    let mut buf_reader = BufReader::new(DeadlineReader {
+        stream,
+        read_timeout: config.read_timeout,
+        deadline: Instant::now() + config.request_timeout,
+    });
+
+    // Read the request, answering with an error status if a limit was hit
+    let request_line = match read_request(&mut buf_reader, config) {
+        Ok(request_line) => request_line,
+        Err(RequestError::TimedOut) => {
+            return send_error(stream, "HTTP/1.1 408 REQUEST TIMEOUT");
+        }
+        Err(RequestError::HeaderTooLarge) => {
+            return send_error(stream, "HTTP/1.1 431 REQUEST HEADER FIELDS TOO LARGE");
+        }
+        Err(RequestError::BodyTooLarge) => {
+            return send_error(stream, "HTTP/1.1 413 PAYLOAD TOO LARGE");
+        }
+        Err(RequestError::BadRequest) => {
+            return send_error(stream, "HTTP/1.1 400 BAD REQUEST");
+        }
+        Err(RequestError::Io(err)) => return Err(err),
+    };
+
+    // Match the request line and determine the appropriate response
+    let (status_line, filename) = match &request_line[..] {
+        // Serve the homepage for root path
+        "GET / HTTP/1.1" => ("HTTP/1.1 200 OK", "hello.html"),
+
+        // Simulate a delayed response to test server concurrency handling
+        "GET /sleep HTTP/1.1" => {
+            thread::sleep(Duration::from_secs(5));
+            ("HTTP/1.1 200 OK", "hello.html")
+        }
+
+        // Respond with 404 for all other (unrecognized) paths
+        _ => ("HTTP/1.1 404 NOT FOUND", "404.html"),
+    };
+
+    // Read the content of the corresponding HTML file from the document root
+    let contents = fs::read_to_string(config.document_root.join(filename))?;
+    let length = contents.len();
+
+    // Format the full HTTP response
+    let response = format!("{status_line}\r\nContent-Length: {length}\r\n\r\n{contents}");
+
+    // Send the response to the client
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
+}
+
+// Read the request line, headers and body, returning the request line
+fn read_request(reader: &mut impl BufRead, config: &Config) -> Result<String, RequestError> {
+    // The request line and all headers share one size budget
+    let mut budget = config.max_header_size as u64;
+
+    // Read the first request line
+    let request_line = read_head_line(reader, &mut budget)?;
+
+    // Read the headers up to the blank line, remembering the declared body length
+    let mut content_length = 0;
+    loop {
+        let line = read_head_line(reader, &mut budget)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("content-length")
+        {
+            // Guessing a length would leave the body to be read as the next request
+            content_length = value
+                .trim()
+                .parse()
+                .map_err(|_| RequestError::BadRequest)?;
+        }
+    }
+
+    // Refuse bodies larger than the configured limit without reading them
+    if content_length > config.max_body_size {
+        return Err(RequestError::BodyTooLarge);
+    }
+
+    // Consume the body so the client is not reset before it reads the response
+    io::copy(&mut reader.take(content_length as u64), &mut io::sink())?;
+
+    Ok(request_line)
+}
+
+// Read one line of the request head without letting it run past the remaining budget
+fn read_head_line(reader: &mut impl BufRead, budget: &mut u64) -> Result<String, RequestError> {
+    let mut line = String::new();
+    let read = reader.take(*budget).read_line(&mut line)? as u64;
+
+    // A line without its newline either hit the budget or the client hung up
+    if !line.ends_with('\n') {
+        return Err(if read == *budget {
+            RequestError::HeaderTooLarge
+        } else {
+            RequestError::Io(io::ErrorKind::UnexpectedEof.into())
+        });
+    }
+
+    *budget -= read;
+    Ok(line.trim_end().to_string())
+}
+
+// Send a bodyless error response and close the connection
+fn send_error(stream: &mut impl Write, status_line: &str) -> io::Result<()> {
+    let response = format!("{status_line}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
+}
@@ -0,0 +1,75 @@
+// HTTPS support built on rustls, compiled in with the `tls` cargo feature
+use crate::server::Connection;
+use rustls::{
+    ServerConfig, ServerConnection, StreamOwned,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+};
+use std::{io, net::TcpStream, path::Path, sync::Arc};
+
+// A TLS session layered over an accepted TCP socket
+pub type TlsStream = StreamOwned<ServerConnection, TcpStream>;
+
+impl Connection for TlsStream {
+    fn socket(&self) -> &TcpStream {
+        &self.sock
+    }
+
+    // Send close_notify so the client can tell the response was not truncated
+    fn close(&mut self) -> io::Result<()> {
+        self.conn.send_close_notify();
+        while self.conn.wants_write() {
+            self.conn.write_tls(&mut self.sock)?;
+        }
+        Ok(())
+    }
+}
+
+// Build a rustls server config from a PEM certificate chain and a PEM private key
+pub fn load_server_config(cert_path: &Path, key_path: &Path) -> io::Result<Arc<ServerConfig>> {
+    // Read every certificate in the chain file
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| {
+            invalid(format!(
+                "cannot read certificates from {}: {e}",
+                cert_path.display()
+            ))
+        })?;
+    if certs.is_empty() {
+        return Err(invalid(format!(
+            "no certificates found in {}",
+            cert_path.display()
+        )));
+    }
+
+    // Read the first private key in the key file
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|e| {
+        invalid(format!(
+            "cannot read private key from {}: {e}",
+            key_path.display()
+        ))
+    })?;
+
+    // Use the ring provider so the build needs no extra system libraries
+    let config =
+        ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|e| invalid(format!("unusable certificate or key: {e}")))?;
+
+    Ok(Arc::new(config))
+}
+
+// Wrap an accepted socket in a server-side TLS session.
+// The handshake runs lazily on the first read, inside the request deadline.
+pub fn accept(stream: TcpStream, config: Arc<ServerConfig>) -> io::Result<TlsStream> {
+    let conn = ServerConnection::new(config).map_err(io::Error::other)?;
+    Ok(StreamOwned::new(conn, stream))
+}
+
+// Configuration problems are reported as invalid data
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
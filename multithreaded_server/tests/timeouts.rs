@@ -0,0 +1,147 @@
+use hello::{ThreadPool, config::Config, server::handle_connection};
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+// Short limits so the tests finish quickly
+fn test_config() -> Config {
+    Config {
+        bind: "127.0.0.1:0".parse().unwrap(),
+        workers: 2,
+        document_root: PathBuf::from(env!("CARGO_MANIFEST_DIR")),
+        read_timeout: Duration::from_millis(500),
+        write_timeout: Duration::from_secs(1),
+        request_timeout: Duration::from_secs(2),
+        max_header_size: 1024,
+        max_body_size: 16,
+        tls: None,
+    }
+}
+
+// Run the accept loop on a random port, the same way `main` does
+fn start_server(config: Config) -> SocketAddr {
+    let listener = TcpListener::bind(config.bind).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = Arc::new(config);
+
+    thread::spawn(move || {
+        let pool = ThreadPool::new(config.workers);
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            let config = Arc::clone(&config);
+            pool.execute(move || {
+                let _ = handle_connection(stream, &config);
+            });
+        }
+    });
+
+    addr
+}
+
+// Send a raw request and return everything the server answers with
+fn send(addr: SocketAddr, request: &[u8]) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(request).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn idle_connections_do_not_starve_real_requests() {
+    let addr = start_server(test_config());
+
+    // Open more idle connections than there are workers and never send anything
+    let idle: Vec<TcpStream> = (0..4).map(|_| TcpStream::connect(addr).unwrap()).collect();
+    thread::sleep(Duration::from_millis(100));
+
+    // A real request still gets served once the idle ones time out
+    let start = Instant::now();
+    let response = send(addr, b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("Hi from Rust"));
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    // The idle connections were told why they were dropped
+    for mut stream in idle {
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 408 REQUEST TIMEOUT"));
+    }
+}
+
+#[test]
+fn slow_drip_is_cut_off_at_request_deadline() {
+    let addr = start_server(test_config());
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let start = Instant::now();
+
+    // Send one byte at a time, each well within the read timeout
+    for byte in b"GET / HTTP/1.1\r\nX-Slow: ".iter().cycle() {
+        if stream.write_all(&[*byte]).is_err() || start.elapsed() > Duration::from_secs(5) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+
+        // Stop once the server has answered
+        stream.set_nonblocking(true).unwrap();
+        let answered = matches!(stream.peek(&mut [0]), Ok(n) if n > 0);
+        stream.set_nonblocking(false).unwrap();
+        if answered {
+            break;
+        }
+    }
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 408 REQUEST TIMEOUT"));
+    assert!(start.elapsed() < Duration::from_secs(4));
+}
+
+#[test]
+fn oversized_headers_are_rejected() {
+    let addr = start_server(test_config());
+    let request = format!("GET / HTTP/1.1\r\nX-Big: {}\r\n\r\n", "a".repeat(2048));
+
+    let response = send(addr, request.as_bytes());
+
+    assert!(response.starts_with("HTTP/1.1 431 REQUEST HEADER FIELDS TOO LARGE"));
+}
+
+#[test]
+fn oversized_body_is_rejected() {
+    let addr = start_server(test_config());
+
+    let response = send(addr, b"POST / HTTP/1.1\r\nContent-Length: 100\r\n\r\n");
+
+    assert!(response.starts_with("HTTP/1.1 413 PAYLOAD TOO LARGE"));
+}
+
+#[test]
+fn body_within_limit_is_accepted() {
+    let addr = start_server(test_config());
+
+    let response = send(addr, b"GET / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello");
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+}
+
+#[test]
+fn malformed_content_length_is_rejected() {
+    let addr = start_server(test_config());
+
+    for length in ["abc", "-1", "5, 5", ""] {
+        let request = format!("POST / HTTP/1.1\r\nContent-Length: {length}\r\n\r\nhello");
+        let response = send(addr, request.as_bytes());
+
+        assert!(response.starts_with("HTTP/1.1 400 BAD REQUEST"), "{length:?}");
+    }
+}
@@ -0,0 +1,154 @@
+#![cfg(feature = "tls")]
+
+use hello::{ThreadPool, config::Config, server::handle_connection, tls};
+use rustls::{
+    ClientConfig, ClientConnection, RootCertStore, StreamOwned, pki_types::CertificateDer,
+};
+use std::{
+    fs,
+    io::{ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+// A freshly generated self-signed certificate written out as PEM files
+struct Certificate {
+    cert: PathBuf,
+    key: PathBuf,
+    der: CertificateDer<'static>,
+}
+
+fn generate_certificate(name: &str) -> Certificate {
+    let dir = std::env::temp_dir().join(format!("hello-tls-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let cert = dir.join("cert.pem");
+    let key = dir.join("key.pem");
+    fs::write(&cert, certified.cert.pem()).unwrap();
+    fs::write(&key, certified.key_pair.serialize_pem()).unwrap();
+
+    Certificate {
+        cert,
+        key,
+        der: certified.cert.der().clone(),
+    }
+}
+
+fn test_config() -> Config {
+    Config {
+        bind: "127.0.0.1:0".parse().unwrap(),
+        workers: 2,
+        document_root: PathBuf::from(env!("CARGO_MANIFEST_DIR")),
+        read_timeout: Duration::from_secs(1),
+        write_timeout: Duration::from_secs(1),
+        request_timeout: Duration::from_secs(2),
+        max_header_size: 1024,
+        max_body_size: 16,
+        tls: None,
+    }
+}
+
+// Run a TLS accept loop on a random port, the same way `main` does
+fn start_tls_server(certificate: &Certificate) -> SocketAddr {
+    let tls_config = tls::load_server_config(&certificate.cert, &certificate.key).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = Arc::new(test_config());
+
+    thread::spawn(move || {
+        let pool = ThreadPool::new(config.workers);
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            let tls_config = Arc::clone(&tls_config);
+            let config = Arc::clone(&config);
+            pool.execute(move || {
+                let _ = tls::accept(stream, tls_config)
+                    .and_then(|stream| handle_connection(stream, &config));
+            });
+        }
+    });
+
+    addr
+}
+
+// Connect with a client that trusts only the generated certificate
+fn connect(
+    certificate: &Certificate,
+    addr: SocketAddr,
+) -> StreamOwned<ClientConnection, TcpStream> {
+    let mut roots = RootCertStore::empty();
+    roots.add(certificate.der.clone()).unwrap();
+
+    let config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+    let conn = ClientConnection::new(Arc::new(config), "localhost".try_into().unwrap()).unwrap();
+
+    StreamOwned::new(conn, TcpStream::connect(addr).unwrap())
+}
+
+#[test]
+fn serves_over_tls() {
+    let certificate = generate_certificate("serve");
+    let addr = start_tls_server(&certificate);
+
+    let mut stream = connect(&certificate, addr);
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("Hi from Rust"));
+}
+
+#[test]
+fn stalled_handshake_times_out() {
+    let certificate = generate_certificate("stalled");
+    let addr = start_tls_server(&certificate);
+
+    // Connect but never start the handshake; the server must hang up by itself
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let start = Instant::now();
+    let mut response = Vec::new();
+    let result = stream.read_to_end(&mut response);
+    let elapsed = start.elapsed();
+
+    // Closed or reset by the server, not given up on by our own read timeout
+    match result {
+        Ok(_) => {}
+        Err(e) => assert_eq!(e.kind(), ErrorKind::ConnectionReset, "{e}"),
+    }
+    let request_timeout = test_config().request_timeout;
+    assert!(
+        elapsed < request_timeout + Duration::from_secs(1),
+        "{elapsed:?}"
+    );
+    assert!(!String::from_utf8_lossy(&response).contains("Hi from Rust"));
+}
+
+#[test]
+fn unreadable_certificate_is_reported() {
+    let certificate = generate_certificate("unreadable");
+
+    let err = tls::load_server_config(
+        &certificate.key.with_file_name("missing.pem"),
+        &certificate.key,
+    )
+    .unwrap_err();
+
+    assert!(err.to_string().contains("missing.pem"));
+}
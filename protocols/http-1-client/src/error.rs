@@ -0,0 +1,52 @@
+//! Everything that can go wrong while making a request.
+
+use std::{fmt, io};
+
+/// Why a request did not produce a response.
+#[derive(Debug)]
+pub enum Error {
+    /// Connecting, sending or receiving failed, including the server hanging up early.
+    Io(io::Error),
+    /// Connecting or a read took longer than allowed, or the total time ran out.
+    TimedOut,
+    /// A URL could not be parsed or uses something other than `http://`.
+    InvalidUrl(String),
+    /// The server's response is not valid HTTP/1.x.
+    Malformed(&'static str),
+    /// A redirect's `Location` could not be followed.
+    InvalidRedirect(String),
+    /// More redirects in a row than the client is allowed to follow.
+    TooManyRedirects,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::TimedOut => write!(f, "timed out"),
+            Error::InvalidUrl(reason) => write!(f, "invalid URL {reason}"),
+            Error::Malformed(what) => write!(f, "malformed response: {what}"),
+            Error::InvalidRedirect(location) => write!(f, "cannot follow redirect to {location:?}"),
+            Error::TooManyRedirects => write!(f, "too many redirects"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        // Socket timeouts surface as WouldBlock on Unix and TimedOut on Windows
+        match e.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Error::TimedOut,
+            _ => Error::Io(e),
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! A small blocking HTTP/1.1 client.
+//!
+//! Build a [`Request`], hand it to [`Client::send`] and get back a [`Response`] with the
+//! whole body read, whether it was sized by `Content-Length`, chunked, or ran to the close.
+//! Connections are kept alive and reused for later requests to the same host and port.
+//! Redirects are followed, time limits enforced and idempotent requests optionally retried.
+
+use std::{
+    borrow::Cow,
+    io::{self, BufRead},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+pub mod error;
+mod pool;
+mod redirect;
+pub mod request;
+pub mod response;
+mod socket;
+pub mod url;
+
+pub use error::Error;
+pub use request::Request;
+pub use response::Response;
+pub use socket::Direction;
+pub use url::Url;
+
+use pool::{Checkout, Pool};
+use socket::{Limits, Tap};
+
+/// Default cap on connections open to one host at a time.
+pub const DEFAULT_MAX_CONNECTIONS_PER_HOST: usize = 6;
+
+/// Default time an unused connection is kept before it is closed rather than reused.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default number of redirects followed in a row before giving up.
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// Default wait before the first retry; each further retry waits twice as long.
+pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Longest wait between two retries, however many came before.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Sends requests over a pool of keep-alive connections.
+///
+/// A client can be shared between threads; requests to the same host then wait for a
+/// connection once [`max_connections_per_host`](Client::max_connections_per_host) are busy.
+#[derive(Debug)]
+pub struct Client {
+    pool: Pool,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    max_redirects: usize,
+    retries: usize,
+    retry_backoff: Duration,
+    tap: Option<Tap>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self {
+            pool: Pool::new(DEFAULT_MAX_CONNECTIONS_PER_HOST, DEFAULT_IDLE_TIMEOUT),
+            connect_timeout: None,
+            read_timeout: None,
+            timeout: None,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            retries: 0,
+            retry_backoff: DEFAULT_RETRY_BACKOFF,
+            tap: None,
+        }
+    }
+}
+
+/// How an exchange on one connection failed.
+enum Failure {
+    /// Nothing came back, so the server may never have seen the request.
+    Unanswered(Error),
+    /// The response was missing or broken part way through.
+    Failed(Error),
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Caps the connections open to any one host at `max`; further requests wait for one.
+    pub fn max_connections_per_host(mut self, max: usize) -> Self {
+        self.pool.max_per_host = max;
+        self
+    }
+
+    /// Closes connections that have been idle longer than `timeout` instead of reusing them.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool.idle_timeout = timeout;
+        self
+    }
+
+    /// Gives up on connecting to a server after `timeout`.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Gives up when the server sends nothing for `timeout` while a response is expected.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Gives up when a whole call to [`send`](Client::send) takes longer than `timeout`,
+    /// counting every redirect, retry and wait for a connection.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Follows at most `max` redirects in a row; 0 returns redirect responses as they are.
+    pub fn max_redirects(mut self, max: usize) -> Self {
+        self.max_redirects = max;
+        self
+    }
+
+    /// Retries idempotent requests up to `retries` times after a connection failure, a
+    /// timeout or a 502, 503 or 504 response.
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Waits `backoff` before the first retry, doubling the wait for each one after.
+    pub fn retry_backoff(mut self, backoff: Duration) -> Self {
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Shows `tap` every byte sent or received, in the order they pass, for debugging.
+    pub fn wire_tap(mut self, tap: impl Fn(Direction, &[u8]) + Send + Sync + 'static) -> Self {
+        self.tap = Some(Tap(Arc::new(tap)));
+        self
+    }
+
+    /// Sends `request` and reads the complete response, following redirects.
+    pub fn send(&self, request: &Request) -> Result<Response, Error> {
+        let limits = Limits {
+            connect: self.connect_timeout,
+            read: self.read_timeout,
+            deadline: self.timeout.map(|timeout| Instant::now() + timeout),
+        };
+
+        let mut request = Cow::Borrowed(request);
+        let mut response = self.send_with_retries(&request, limits)?;
+        let mut hops = 0;
+
+        if self.max_redirects == 0 {
+            return Ok(response);
+        }
+        while let Some(next) = redirect::follow(&request, &response)? {
+            if hops == self.max_redirects {
+                return Err(Error::TooManyRedirects);
+            }
+            hops += 1;
+            response = self.send_with_retries(&next, limits)?;
+            request = Cow::Owned(next);
+        }
+
+        Ok(response)
+    }
+
+    /// Sends a GET request for `url`, an absolute `http://` URL.
+    pub fn get(&self, url: &str) -> Result<Response, Error> {
+        self.send(&Request::get(Url::parse(url)?))
+    }
+
+    /// Sends `request` once, then again with growing pauses while it is idempotent, the
+    /// failure looks transient and retries remain.
+    fn send_with_retries(&self, request: &Request, limits: Limits) -> Result<Response, Error> {
+        let mut backoff = self.retry_backoff;
+        let mut attempt = 0;
+
+        loop {
+            let result = self.send_once(request, limits);
+            let transient = match &result {
+                Ok(response) => matches!(response.status, 502..=504),
+                Err(error) => matches!(error, Error::Io(_) | Error::TimedOut),
+            };
+            if !transient || attempt == self.retries || !request.is_idempotent() {
+                return result;
+            }
+
+            // A pause that runs past the deadline would only end in a timeout anyway
+            if limits
+                .deadline
+                .is_some_and(|deadline| Instant::now() + backoff >= deadline)
+            {
+                return result;
+            }
+            thread::sleep(backoff);
+            backoff = (backoff * 2).min(MAX_RETRY_BACKOFF);
+            attempt += 1;
+        }
+    }
+
+    /// Sends `request` on a pooled connection, returning the connection if it can be reused.
+    ///
+    /// If a reused connection turns out to have been closed by the server before it answered,
+    /// an idempotent request is sent once more on a new connection.
+    fn send_once(&self, request: &Request, limits: Limits) -> Result<Response, Error> {
+        let mut checkout =
+            self.pool
+                .checkout(request.url.host(), request.url.port(), false, limits)?;
+
+        checkout.conn().get_mut().tap = self.tap.clone();
+        let response = match exchange(&mut checkout, request) {
+            Ok(response) => response,
+            Err(Failure::Unanswered(_)) if checkout.reused && request.is_idempotent() => {
+                drop(checkout);
+                checkout =
+                    self.pool
+                        .checkout(request.url.host(), request.url.port(), true, limits)?;
+                checkout.conn().get_mut().tap = self.tap.clone();
+                exchange(&mut checkout, request).map_err(Failure::into_error)?
+            }
+            Err(failure) => return Err(failure.into_error()),
+        };
+
+        if request.keeps_alive() && response.keeps_alive(request.method()) {
+            checkout.reuse();
+        }
+        Ok(response)
+    }
+}
+
+/// Writes `request` on the checked-out connection and reads the response.
+fn exchange(checkout: &mut Checkout<'_>, request: &Request) -> Result<Response, Failure> {
+    let conn = checkout.conn();
+    request
+        .write_to(conn.get_mut())
+        .map_err(Failure::before_response)?;
+
+    // An end of stream or reset before the first byte means the server dropped the connection
+    match conn.fill_buf() {
+        Ok([]) => Err(Failure::Unanswered(Error::Io(
+            io::ErrorKind::UnexpectedEof.into(),
+        ))),
+        Ok(_) => response::read_response(conn, request.method()).map_err(Failure::Failed),
+        Err(e) => Err(Failure::before_response(e)),
+    }
+}
+
+impl Failure {
+    /// Classifies an error hit before any of the response arrived.
+    ///
+    /// Only a closed or reset connection shows the server never took the request; a
+    /// timeout may just be a slow server.
+    fn before_response(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof => Failure::Unanswered(e.into()),
+            _ => Failure::Failed(e.into()),
+        }
+    }
+
+    fn into_error(self) -> Error {
+        match self {
+            Failure::Unanswered(e) | Failure::Failed(e) => e,
+        }
+    }
+}
@@ -1,74 +1,204 @@
+// A small curl-like command line client built on the library.
+use clap::Parser;
 use std::{
-    io::{BufRead, BufReader, Read, Write},
-    net::TcpStream,
+    fs::{self, File},
+    io::{self, Write},
+    path::PathBuf,
+    process::ExitCode,
+    sync::Mutex,
+    time::Duration,
 };
 
-fn main() {
-    let host = "example.com";
-    let port = 80;
-    let path = "/";
-
-    let mut stream = TcpStream::connect((host, port)).expect("Failed to connect");
-
-    // Send request
-    let request = format!(
-        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
-        path, host
-    );
-    stream.write_all(request.as_bytes()).unwrap();
-
-    // Read response
-    let mut reader = BufReader::new(stream);
-    let mut status_line = String::new();
-    reader.read_line(&mut status_line).unwrap();
-    println!("Status: {}", status_line.trim_end());
-
-    let mut headers = Vec::new();
-    let mut content_length = None;
-    let mut is_chunked = false;
-
-    loop {
-        let mut line = String::new();
-        reader.read_line(&mut line).unwrap();
-        if line == "\r\n" {
-            break;
+use http_1_client::{Client, Direction, Request, Response, Url};
+
+/// Send one HTTP/1.1 request and print the response body.
+#[derive(Parser, Debug)]
+#[command(
+    name = "http-1-client",
+    version,
+    after_help = "Exit status: 0 for a 1xx or 2xx response, 3, 4 or 5 for a response in that \
+                  class, 1 when no response was received and 2 for invalid arguments."
+)]
+struct Args {
+    /// URL to request, e.g. http://localhost:8080/index.html
+    url: String,
+
+    /// Request method; POST when data is given, otherwise GET
+    #[arg(short = 'X', long = "request", value_name = "METHOD")]
+    method: Option<String>,
+
+    /// Extra request header, e.g. "Accept: text/plain"; may be repeated
+    #[arg(short = 'H', long = "header", value_name = "NAME: VALUE", value_parser = parse_header)]
+    headers: Vec<(String, String)>,
+
+    /// Request body, or @FILE to read it from a file; repeated values are joined with '&'
+    #[arg(short = 'd', long = "data", value_name = "DATA")]
+    data: Vec<String>,
+
+    /// Write the output to FILE instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Include the status line and headers in the output
+    #[arg(short, long)]
+    include: bool,
+
+    /// Print the request and response heads as they go over the wire to stderr
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Follow redirects
+    #[arg(short = 'L', long = "location")]
+    follow: bool,
+
+    /// Give up on the whole transfer after SECONDS
+    #[arg(short = 'm', long = "max-time", value_name = "SECONDS")]
+    max_time: Option<f64>,
+
+    /// Give up on connecting after SECONDS
+    #[arg(long, value_name = "SECONDS")]
+    connect_timeout: Option<f64>,
+}
+
+fn parse_header(header: &str) -> Result<(String, String), String> {
+    match header.split_once(':') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err("expected NAME: VALUE".to_string()),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let response = match send(&args) {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!("Request to {} failed: {e}", args.url);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Err(e) = write_output(&args, &response) {
+        eprintln!("Failed to write the output: {e}");
+        return ExitCode::FAILURE;
+    }
+
+    // 2 is taken by argument errors, so a class maps to its own digit
+    match response.status / 100 {
+        1 | 2 => ExitCode::SUCCESS,
+        class => ExitCode::from(class as u8),
+    }
+}
+
+fn send(args: &Args) -> Result<Response, Box<dyn std::error::Error>> {
+    let url = Url::parse(&args.url)?;
+
+    let mut body = Vec::new();
+    for (i, data) in args.data.iter().enumerate() {
+        if i > 0 {
+            body.push(b'&');
         }
-        let lower = line.to_lowercase();
-        if lower.starts_with("content-length:") {
-            content_length = lower
-                .splitn(2, ':')
-                .nth(1)
-                .and_then(|v| v.trim().parse::<usize>().ok());
-        } else if lower.starts_with("transfer-encoding:") && lower.contains("chunked") {
-            is_chunked = true;
+        match data.strip_prefix('@') {
+            Some(path) => body.extend(fs::read(path)?),
+            None => body.extend_from_slice(data.as_bytes()),
         }
-        headers.push(line.trim().to_string());
     }
 
-    println!("Headers:\n{}", headers.join("\n"));
-    println!("Body:");
-
-    if is_chunked {
-        // Read chunked body
-        loop {
-            let mut size_line = String::new();
-            reader.read_line(&mut size_line).unwrap();
-            let size = usize::from_str_radix(size_line.trim(), 16).unwrap_or(0);
-            if size == 0 {
-                break;
+    let method = match &args.method {
+        Some(method) => method,
+        None if args.data.is_empty() => "GET",
+        None => "POST",
+    };
+    let mut request = Request::new(method, url);
+    for (name, value) in &args.headers {
+        request = request.header(name, value);
+    }
+    if !args.data.is_empty() && request.header_value("content-type").is_none() {
+        request = request.header("Content-Type", "application/x-www-form-urlencoded");
+    }
+    let request = request.body(body);
+
+    let mut client = Client::new();
+    if !args.follow {
+        client = client.max_redirects(0);
+    }
+    if let Some(seconds) = args.max_time {
+        client = client.timeout(Duration::try_from_secs_f64(seconds)?);
+    }
+    if let Some(seconds) = args.connect_timeout {
+        client = client.connect_timeout(Duration::try_from_secs_f64(seconds)?);
+    }
+    if args.verbose {
+        let dump = Mutex::new(Dump::default());
+        client = client.wire_tap(move |direction, bytes| {
+            dump.lock().unwrap().show(direction, bytes);
+        });
+    }
+
+    Ok(client.send(&request)?)
+}
+
+fn write_output(args: &Args, response: &Response) -> io::Result<()> {
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout().lock()),
+    };
+
+    if args.include {
+        write!(
+            out,
+            "{} {} {}\r\n",
+            response.version, response.status, response.reason
+        )?;
+        for (name, value) in &response.headers {
+            write!(out, "{name}: {value}\r\n")?;
+        }
+        out.write_all(b"\r\n")?;
+    }
+    out.write_all(&response.body)?;
+    out.flush()
+}
+
+// Prints heads line by line behind "> " or "< ", skipping bodies until the direction changes
+#[derive(Default)]
+struct Dump {
+    direction: Option<Direction>,
+    line_start: bool,
+    in_head: bool,
+}
+
+impl Dump {
+    fn show(&mut self, direction: Direction, bytes: &[u8]) {
+        if self.direction != Some(direction) {
+            self.direction = Some(direction);
+            self.line_start = true;
+            self.in_head = true;
+        }
+        let prefix = match direction {
+            Direction::Sent => '>',
+            Direction::Received => '<',
+        };
+
+        let mut stderr = io::stderr().lock();
+        for line in bytes.split_inclusive(|&b| b == b'\n') {
+            if !self.in_head {
+                return;
+            }
+            let text = String::from_utf8_lossy(line);
+            let text = text.trim_end_matches(['\r', '\n']);
+            if self.line_start {
+                if text.is_empty() {
+                    self.in_head = false;
+                }
+                let _ = write!(stderr, "{prefix} ");
+            }
+            let _ = write!(stderr, "{text}");
+            self.line_start = line.ends_with(b"\n");
+            if self.line_start {
+                let _ = writeln!(stderr);
             }
-            let mut chunk = vec![0; size];
-            reader.read_exact(&mut chunk).unwrap();
-            println!("{}", String::from_utf8_lossy(&chunk));
-            let _ = reader.read_line(&mut String::new()); // Read trailing CRLF
         }
-    } else if let Some(len) = content_length {
-        let mut body = vec![0; len];
-        reader.read_exact(&mut body).unwrap();
-        println!("{}", String::from_utf8_lossy(&body));
-    } else {
-        let mut body = String::new();
-        reader.read_to_string(&mut body).unwrap();
-        println!("{}", body);
     }
 }
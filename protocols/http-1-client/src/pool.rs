@@ -0,0 +1,261 @@
+//! Keep-alive connections shared between requests to the same host and port.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufReader},
+    sync::{Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::socket::{Limits, Socket};
+
+/// A connection with whatever it has buffered, ready for the next exchange.
+pub(crate) type Connection = BufReader<Socket>;
+
+type Key = (String, u16);
+
+/// Idle connections per host, and a cap on how many may be open to each at once.
+#[derive(Debug)]
+pub(crate) struct Pool {
+    hosts: Mutex<HashMap<Key, Host>>,
+    /// Signalled whenever a connection is returned or closed, freeing a slot.
+    released: Condvar,
+    pub(crate) max_per_host: usize,
+    pub(crate) idle_timeout: Duration,
+}
+
+#[derive(Debug, Default)]
+struct Host {
+    /// Most recently used last.
+    idle: Vec<(Connection, Instant)>,
+    /// Connections counted against the limit: idle ones plus those checked out.
+    open: usize,
+}
+
+impl Pool {
+    pub(crate) fn new(max_per_host: usize, idle_timeout: Duration) -> Self {
+        Self {
+            hosts: Mutex::new(HashMap::new()),
+            released: Condvar::new(),
+            max_per_host,
+            idle_timeout,
+        }
+    }
+
+    /// Takes a live idle connection to `host:port`, or opens a new one once the host has a free slot.
+    ///
+    /// With `fresh` set, idle connections are closed rather than reused. Waiting for a slot
+    /// and connecting both count against `limits`, which then apply to the connection.
+    pub(crate) fn checkout(
+        &self,
+        host: &str,
+        port: u16,
+        fresh: bool,
+        limits: Limits,
+    ) -> io::Result<Checkout<'_>> {
+        let key = (host.to_string(), port);
+        let mut hosts = self.lock();
+
+        loop {
+            let entry = hosts.entry(key.clone()).or_default();
+
+            // Prefer the most recently used connection; it is the least likely to be stale
+            while let Some((mut conn, since)) = entry.idle.pop() {
+                if !fresh && since.elapsed() < self.idle_timeout && is_alive(&conn) {
+                    conn.get_mut().limits = limits;
+                    return Ok(Checkout::new(self, key, conn, true));
+                }
+                entry.open -= 1;
+                self.released.notify_one();
+            }
+
+            if entry.open < self.max_per_host.max(1) {
+                entry.open += 1;
+                break;
+            }
+            hosts = match limits.within(None)? {
+                Some(timeout) => {
+                    self.released
+                        .wait_timeout(hosts, timeout)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .0
+                }
+                None => self
+                    .released
+                    .wait(hosts)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()),
+            };
+        }
+        drop(hosts);
+
+        // Connect without holding the lock; the slot is already ours
+        match Socket::connect(host, port, limits) {
+            Ok(socket) => Ok(Checkout::new(self, key, BufReader::new(socket), false)),
+            Err(e) => {
+                self.release(&key);
+                Err(e)
+            }
+        }
+    }
+
+    /// Number of connections to `host:port` currently idle in the pool.
+    #[cfg(test)]
+    fn idle(&self, host: &str, port: u16) -> usize {
+        self.lock()
+            .get(&(host.to_string(), port))
+            .map_or(0, |entry| entry.idle.len())
+    }
+
+    /// Frees the slot of a connection that was closed instead of returned.
+    fn release(&self, key: &Key) {
+        if let Some(entry) = self.lock().get_mut(key) {
+            entry.open -= 1;
+        }
+        self.released.notify_one();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Key, Host>> {
+        // The map stays consistent even if a holder panicked
+        self.hosts
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A connection taken from the pool; it is closed on drop unless handed back with [`Checkout::reuse`].
+pub(crate) struct Checkout<'a> {
+    pool: &'a Pool,
+    key: Key,
+    conn: Option<Connection>,
+    /// Whether the connection already carried an earlier exchange.
+    pub(crate) reused: bool,
+}
+
+impl<'a> Checkout<'a> {
+    fn new(pool: &'a Pool, key: Key, conn: Connection, reused: bool) -> Self {
+        Self {
+            pool,
+            key,
+            conn: Some(conn),
+            reused,
+        }
+    }
+
+    pub(crate) fn conn(&mut self) -> &mut Connection {
+        self.conn.as_mut().expect("connection taken before drop")
+    }
+
+    /// Returns the connection to the pool for the next request to the same host.
+    pub(crate) fn reuse(mut self) {
+        let conn = self.conn.take().expect("connection taken before drop");
+
+        // Bytes beyond the response mean the framing went wrong somewhere
+        if !conn.buffer().is_empty() {
+            self.pool.release(&self.key);
+            return;
+        }
+        if let Some(entry) = self.pool.lock().get_mut(&self.key) {
+            entry.idle.push((conn, Instant::now()));
+        }
+        self.pool.released.notify_one();
+    }
+}
+
+impl Drop for Checkout<'_> {
+    fn drop(&mut self) {
+        if self.conn.take().is_some() {
+            self.pool.release(&self.key);
+        }
+    }
+}
+
+/// Whether an idle connection can still carry a request.
+///
+/// A server that closed it has left an end of stream to read; anything else readable is
+/// unsolicited data. Either way the connection is unusable.
+fn is_alive(conn: &Connection) -> bool {
+    let stream = &conn.get_ref().stream;
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+    let alive = matches!(
+        stream.peek(&mut [0]),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock
+    );
+    stream.set_nonblocking(false).is_ok() && alive
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{net::TcpListener, sync::Arc, thread};
+
+    #[test]
+    fn returned_connections_are_reused() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let pool = Pool::new(2, Duration::from_secs(30));
+
+        let first = pool
+            .checkout("127.0.0.1", port, false, Limits::default())
+            .unwrap();
+        assert!(!first.reused);
+        first.reuse();
+        let _server_side = listener.accept().unwrap();
+        assert_eq!(pool.idle("127.0.0.1", port), 1);
+
+        let second = pool
+            .checkout("127.0.0.1", port, false, Limits::default())
+            .unwrap();
+        assert!(second.reused);
+        assert_eq!(pool.idle("127.0.0.1", port), 0);
+    }
+
+    #[test]
+    fn closed_connections_are_not_reused() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let pool = Pool::new(2, Duration::from_secs(30));
+
+        pool.checkout("127.0.0.1", port, false, Limits::default())
+            .unwrap()
+            .reuse();
+        // The server accepts and hangs up straight away
+        drop(listener.accept().unwrap());
+        thread::sleep(Duration::from_millis(50));
+
+        let checkout = pool
+            .checkout("127.0.0.1", port, false, Limits::default())
+            .unwrap();
+        assert!(!checkout.reused);
+    }
+
+    #[test]
+    fn limit_blocks_until_a_slot_frees() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let pool = Arc::new(Pool::new(1, Duration::from_secs(30)));
+
+        let held = pool
+            .checkout("127.0.0.1", port, false, Limits::default())
+            .unwrap();
+        let waiter = {
+            let pool = Arc::clone(&pool);
+            thread::spawn(move || {
+                let start = Instant::now();
+                let checkout = pool
+                    .checkout("127.0.0.1", port, false, Limits::default())
+                    .unwrap();
+                (start.elapsed(), checkout.reused)
+            })
+        };
+
+        thread::sleep(Duration::from_millis(200));
+        drop(held);
+        let (waited, reused) = waiter.join().unwrap();
+
+        assert!(waited >= Duration::from_millis(150));
+        assert!(!reused);
+    }
+}
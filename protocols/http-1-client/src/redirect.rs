@@ -0,0 +1,150 @@
+//! Turning a redirect response into the request that follows it.
+
+use crate::{Error, Request, Response};
+
+/// The request to send next if `response` redirects `request`, or `None` if it does not.
+///
+/// 307 and 308 repeat the request as it was. 301 and 302 turn a POST into a body-less GET,
+/// as browsers do, and 303 turns anything but HEAD into one.
+pub(crate) fn follow(request: &Request, response: &Response) -> Result<Option<Request>, Error> {
+    let rewrite_to_get = match response.status {
+        301 | 302 => request.method == "POST",
+        303 => request.method != "HEAD",
+        307 | 308 => false,
+        _ => return Ok(None),
+    };
+    // A redirect without somewhere to go is just a response
+    let Some(location) = response.header("location") else {
+        return Ok(None);
+    };
+
+    let url = request
+        .url
+        .join(location)
+        .map_err(|_| Error::InvalidRedirect(location.to_string()))?;
+    let mut next = request.clone();
+    if rewrite_to_get {
+        next.method = "GET".to_string();
+        next.body.clear();
+        next.headers.retain(|(name, _)| !is_body_header(name));
+    }
+    // Credentials and a hand-set Host belong to the original server only
+    if (url.host(), url.port()) != (request.url.host(), request.url.port()) {
+        next.headers.retain(|(name, _)| {
+            !name.eq_ignore_ascii_case("authorization") && !name.eq_ignore_ascii_case("host")
+        });
+    }
+    next.url = url;
+
+    Ok(Some(next))
+}
+
+fn is_body_header(name: &str) -> bool {
+    [
+        "content-length",
+        "content-type",
+        "content-encoding",
+        "transfer-encoding",
+    ]
+    .iter()
+    .any(|header| name.eq_ignore_ascii_case(header))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Url;
+
+    fn redirect(status: u16, location: &str) -> Response {
+        Response {
+            version: "HTTP/1.1".to_string(),
+            status,
+            reason: String::new(),
+            headers: vec![("Location".to_string(), location.to_string())],
+            body: Vec::new(),
+            trailers: Vec::new(),
+        }
+    }
+
+    fn post() -> Request {
+        Request::new(
+            "POST",
+            Url::parse("http://a.test:8080/dir/page?x=1").unwrap(),
+        )
+        .header("Content-Type", "text/plain")
+        .header("Authorization", "Bearer secret")
+        .body("data")
+    }
+
+    #[test]
+    fn location_is_resolved_against_the_request_url() {
+        let next = follow(&post(), &redirect(307, "../other?y=2"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(next.url.to_string(), "http://a.test:8080/other?y=2");
+
+        let next = follow(&post(), &redirect(307, "http://b.test/x"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(next.url.to_string(), "http://b.test/x");
+    }
+
+    #[test]
+    fn unfollowable_locations() {
+        for location in ["https://b.test/", "ftp://b.test/", "http://:80/"] {
+            assert!(matches!(
+                follow(&post(), &redirect(302, location)),
+                Err(Error::InvalidRedirect(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn see_other_and_post_redirects_become_get() {
+        for status in [301, 302, 303] {
+            let next = follow(&post(), &redirect(status, "/next"))
+                .unwrap()
+                .unwrap();
+            assert_eq!(next.method, "GET", "{status}");
+            assert!(next.body.is_empty());
+            assert_eq!(next.header_value("content-type"), None);
+        }
+    }
+
+    #[test]
+    fn temporary_and_permanent_keep_method_and_body() {
+        for status in [307, 308] {
+            let next = follow(&post(), &redirect(status, "/next"))
+                .unwrap()
+                .unwrap();
+            assert_eq!(next.method, "POST");
+            assert_eq!(next.body, b"data");
+            assert_eq!(next.header_value("content-type"), Some("text/plain"));
+        }
+    }
+
+    #[test]
+    fn put_keeps_method_on_found() {
+        let put = Request::new("PUT", Url::parse("http://a.test/").unwrap()).body("x");
+        let next = follow(&put, &redirect(302, "/next")).unwrap().unwrap();
+        assert_eq!(next.method, "PUT");
+        assert_eq!(next.body, b"x");
+    }
+
+    #[test]
+    fn credentials_stay_with_their_host() {
+        let same = follow(&post(), &redirect(307, "/next")).unwrap().unwrap();
+        assert_eq!(same.header_value("authorization"), Some("Bearer secret"));
+
+        let other = follow(&post(), &redirect(307, "http://b.test/"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(other.header_value("authorization"), None);
+    }
+
+    #[test]
+    fn non_redirects_are_not_followed() {
+        assert!(follow(&post(), &redirect(200, "/next")).unwrap().is_none());
+        assert!(follow(&post(), &redirect(304, "/next")).unwrap().is_none());
+    }
+}
@@ -0,0 +1,180 @@
+//! Requests built up with chained calls and their wire encoding.
+
+use std::io::{self, BufWriter, Write};
+
+use crate::{Url, url::form_encode};
+
+/// An HTTP/1.1 request to send with [`Client::send`](crate::Client::send).
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub(crate) method: String,
+    pub(crate) url: Url,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl Request {
+    /// A request with no headers or body for `url`.
+    pub fn new(method: &str, url: Url) -> Self {
+        Self {
+            method: method.to_ascii_uppercase(),
+            url,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// A GET request for `url`.
+    pub fn get(url: Url) -> Self {
+        Self::new("GET", url)
+    }
+
+    /// Adds a header. `Host` and `Content-Length` are filled in unless set here.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Uses `bytes` as the body.
+    pub fn body(mut self, bytes: impl Into<Vec<u8>>) -> Self {
+        self.body = bytes.into();
+        self
+    }
+
+    /// Sends `pairs` as an `application/x-www-form-urlencoded` body.
+    pub fn form<N: AsRef<str>, V: AsRef<str>>(
+        self,
+        pairs: impl IntoIterator<Item = (N, V)>,
+    ) -> Self {
+        self.header("Content-Type", "application/x-www-form-urlencoded")
+            .body(form_encode(pairs))
+    }
+
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// The value of the first header called `name`, ignoring case.
+    pub fn header_value(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Whether repeating the request has the same effect as sending it once.
+    pub fn is_idempotent(&self) -> bool {
+        matches!(
+            self.method.as_str(),
+            "GET" | "HEAD" | "PUT" | "DELETE" | "OPTIONS" | "TRACE"
+        )
+    }
+
+    /// Whether the connection may carry further requests, i.e. no `Connection: close` was set.
+    pub fn keeps_alive(&self) -> bool {
+        !self
+            .header_value("connection")
+            .is_some_and(|value| has_token(value, "close"))
+    }
+
+    /// Writes the request line, headers and body to `out`.
+    pub fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        // One buffer, so a small request leaves in a single segment
+        let mut out = BufWriter::new(out);
+
+        write!(out, "{} {} HTTP/1.1\r\n", self.method, self.url.target())?;
+        if self.header_value("host").is_none() {
+            write!(out, "Host: {}\r\n", self.url.authority())?;
+        }
+        for (name, value) in &self.headers {
+            write!(out, "{name}: {value}\r\n")?;
+        }
+        let framed = self.header_value("content-length").is_some()
+            || self.header_value("transfer-encoding").is_some();
+        // Methods that usually carry a body get an explicit zero length when empty
+        if !framed
+            && (!self.body.is_empty() || matches!(self.method.as_str(), "POST" | "PUT" | "PATCH"))
+        {
+            write!(out, "Content-Length: {}\r\n", self.body.len())?;
+        }
+        out.write_all(b"\r\n")?;
+        out.write_all(&self.body)?;
+        out.flush()
+    }
+}
+
+/// Whether the comma-separated header `value` contains `token`, ignoring case.
+pub(crate) fn has_token(value: &str, token: &str) -> bool {
+    value
+        .split(',')
+        .any(|item| item.trim().eq_ignore_ascii_case(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn render(request: &Request) -> String {
+        let mut out = Vec::new();
+        request.write_to(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn get_has_host_and_no_body() {
+        assert_eq!(
+            render(&Request::get(url("http://example.com/a?b=c#frag"))),
+            "GET /a?b=c HTTP/1.1\r\nHost: example.com\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn non_default_port_is_in_host() {
+        let rendered = render(&Request::get(url("http://localhost:8080/")));
+        assert!(rendered.contains("\r\nHost: localhost:8080\r\n"));
+    }
+
+    #[test]
+    fn body_gets_content_length() {
+        let request = Request::new("post", url("http://localhost/echo"))
+            .header("Content-Type", "text/plain")
+            .body("hello");
+
+        assert_eq!(
+            render(&request),
+            "POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Type: text/plain\r\n\
+             Content-Length: 5\r\n\r\nhello"
+        );
+    }
+
+    #[test]
+    fn explicit_headers_win() {
+        let request = Request::new("PUT", url("http://localhost/"))
+            .header("Host", "virtual.test")
+            .header("Connection", "close");
+        let rendered = render(&request);
+
+        assert!(rendered.contains("\r\nHost: virtual.test\r\n"));
+        assert!(rendered.contains("\r\nContent-Length: 0\r\n"));
+        assert!(!rendered.contains("localhost"));
+        assert!(rendered.contains("\r\nConnection: close\r\n"));
+    }
+
+    #[test]
+    fn form_body_is_encoded() {
+        let request = Request::new("POST", url("http://localhost/login"))
+            .form([("user", "ann lee"), ("pass", "p&ss=1")]);
+        let rendered = render(&request);
+
+        assert!(rendered.contains("\r\nContent-Type: application/x-www-form-urlencoded\r\n"));
+        assert!(rendered.ends_with("\r\nContent-Length: 28\r\n\r\nuser=ann+lee&pass=p%26ss%3D1"));
+    }
+}
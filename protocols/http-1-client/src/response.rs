@@ -0,0 +1,341 @@
+//! Responses and reading them off the wire.
+
+use std::{
+    borrow::Cow,
+    io::{BufRead, Read},
+};
+
+use crate::{Error, request::has_token};
+
+/// Most bytes the status line and headers (or the trailers) may take together.
+pub const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+/// A complete HTTP/1.x response, body included.
+#[derive(Debug, Clone)]
+pub struct Response {
+    /// `HTTP/1.1` or `HTTP/1.0`.
+    pub version: String,
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Fields sent after a chunked body.
+    pub trailers: Vec<(String, String)>,
+}
+
+impl Response {
+    /// The value of the first header called `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find(&self.headers, name)
+    }
+
+    /// The value of the first trailer called `name`, ignoring case.
+    pub fn trailer(&self, name: &str) -> Option<&str> {
+        find(&self.trailers, name)
+    }
+
+    /// The body as text, with invalid UTF-8 replaced.
+    pub fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.body)
+    }
+
+    /// Whether the connection this arrived on can carry another request after it.
+    ///
+    /// Not when the server said `Connection: close`, when an HTTP/1.0 server did not offer
+    /// keep-alive, or when the body could only end by the server closing the connection.
+    pub fn keeps_alive(&self, method: &str) -> bool {
+        let connection = self.header("connection").unwrap_or("");
+        if has_token(connection, "close") {
+            return false;
+        }
+        if self.version == "HTTP/1.0" && !has_token(connection, "keep-alive") {
+            return false;
+        }
+        !has_body(method, self.status)
+            || is_chunked(self)
+            || self.header("content-length").is_some()
+    }
+
+    /// Whether the status is 2xx.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+fn find<'a>(fields: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    fields
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Reads the response to a request made with `method`, skipping interim 1xx responses.
+pub fn read_response(reader: &mut impl BufRead, method: &str) -> Result<Response, Error> {
+    loop {
+        let mut budget = MAX_HEAD_SIZE;
+        let status_line = read_line(reader, &mut budget)?;
+        let (version, status, reason) = parse_status_line(&status_line)?;
+        let headers = read_fields(reader, &mut budget)?;
+
+        // 100 Continue and friends precede the real response; 101 ends HTTP altogether
+        if (100..200).contains(&status) && status != 101 {
+            continue;
+        }
+
+        let mut response = Response {
+            version,
+            status,
+            reason,
+            headers,
+            body: Vec::new(),
+            trailers: Vec::new(),
+        };
+        if has_body(method, status) {
+            read_body(reader, &mut response)?;
+        }
+        return Ok(response);
+    }
+}
+
+/// HEAD responses and 1xx, 204 and 304 responses never carry a body, whatever they declare.
+fn has_body(method: &str, status: u16) -> bool {
+    method != "HEAD" && !(100..200).contains(&status) && status != 204 && status != 304
+}
+
+/// Splits `HTTP/1.1 200 OK` into its version, status code and reason phrase.
+fn parse_status_line(line: &str) -> Result<(String, u16, String), Error> {
+    let mut parts = line.splitn(3, ' ');
+    let version = parts.next().unwrap_or("");
+    if !matches!(version, "HTTP/1.1" | "HTTP/1.0") {
+        return Err(Error::Malformed("HTTP version"));
+    }
+    let status = parts
+        .next()
+        .filter(|code| code.len() == 3 && code.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|code| code.parse().ok())
+        .ok_or(Error::Malformed("status code"))?;
+    let reason = parts.next().unwrap_or("");
+
+    Ok((version.to_string(), status, reason.to_string()))
+}
+
+/// Reads `name: value` lines up to the blank line that ends a header or trailer section.
+fn read_fields(
+    reader: &mut impl BufRead,
+    budget: &mut usize,
+) -> Result<Vec<(String, String)>, Error> {
+    let mut fields = Vec::new();
+    loop {
+        let line = read_line(reader, budget)?;
+        if line.is_empty() {
+            return Ok(fields);
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or(Error::Malformed("header without a colon"))?;
+        if name.is_empty() || name.contains([' ', '\t']) {
+            return Err(Error::Malformed("header name"));
+        }
+        fields.push((name.to_string(), value.trim().to_string()));
+    }
+}
+
+/// Reads the body as the headers frame it: chunked, `Content-Length`, or up to the close.
+fn read_body(reader: &mut impl BufRead, response: &mut Response) -> Result<(), Error> {
+    if is_chunked(response) {
+        return read_chunked(reader, response);
+    }
+    match response.header("content-length") {
+        Some(length) => {
+            let length = length
+                .trim()
+                .parse::<u64>()
+                .map_err(|_| Error::Malformed("Content-Length"))?;
+            let read = reader.take(length).read_to_end(&mut response.body)?;
+            if (read as u64) < length {
+                return Err(truncated());
+            }
+        }
+        None => {
+            reader.read_to_end(&mut response.body)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether the last transfer coding applied to the body is `chunked`.
+fn is_chunked(response: &Response) -> bool {
+    response
+        .header("transfer-encoding")
+        .and_then(|codings| codings.rsplit(',').next())
+        .is_some_and(|last| last.trim().eq_ignore_ascii_case("chunked"))
+}
+
+/// Reads a chunked body into `response.body` and its trailers into `response.trailers`.
+fn read_chunked(reader: &mut impl BufRead, response: &mut Response) -> Result<(), Error> {
+    loop {
+        let mut budget = MAX_HEAD_SIZE;
+        let line = read_line(reader, &mut budget)?;
+        // Chunk extensions after `;` carry nothing we use
+        let size = line.split(';').next().unwrap_or("").trim();
+        let size = u64::from_str_radix(size, 16).map_err(|_| Error::Malformed("chunk size"))?;
+
+        if size == 0 {
+            response.trailers = read_fields(reader, &mut budget)?;
+            return Ok(());
+        }
+
+        let read = reader.take(size).read_to_end(&mut response.body)?;
+        if (read as u64) < size {
+            return Err(truncated());
+        }
+        let mut crlf = [0; 2];
+        reader.read_exact(&mut crlf)?;
+        if &crlf != b"\r\n" {
+            return Err(Error::Malformed("chunk not followed by CRLF"));
+        }
+    }
+}
+
+/// Reads one line without its line ending, counting it against `budget`.
+fn read_line(reader: &mut impl BufRead, budget: &mut usize) -> Result<String, Error> {
+    let mut line = Vec::new();
+    let read = reader.take(*budget as u64).read_until(b'\n', &mut line)?;
+    *budget -= read;
+
+    if !line.ends_with(b"\n") {
+        return Err(if *budget == 0 {
+            Error::Malformed("response head too large")
+        } else {
+            truncated()
+        });
+    }
+    line.pop();
+    if line.ends_with(b"\r") {
+        line.pop();
+    }
+    String::from_utf8(line).map_err(|_| Error::Malformed("not UTF-8"))
+}
+
+/// The error for a response cut off by the server closing the connection.
+fn truncated() -> Error {
+    Error::Io(std::io::ErrorKind::UnexpectedEof.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    fn parse(raw: &str) -> Result<Response, Error> {
+        read_response(&mut Cursor::new(raw.as_bytes()), "GET")
+    }
+
+    #[test]
+    fn content_length_body() {
+        let response =
+            parse("HTTP/1.1 200 OK\r\nContent-Length: 5\r\nX-A: b\r\n\r\nhelloEXTRA").unwrap();
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.reason, "OK");
+        assert_eq!(response.header("x-a"), Some("b"));
+        assert_eq!(response.body, b"hello");
+    }
+
+    #[test]
+    fn chunked_body_with_trailers() {
+        let response = parse(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+             4;ext=1\r\nWiki\r\n5\r\npedia\r\n0\r\nExpires: never\r\nX-Sum: 9\r\n\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(response.body, b"Wikipedia");
+        assert_eq!(response.trailer("expires"), Some("never"));
+        assert_eq!(response.trailer("X-Sum"), Some("9"));
+    }
+
+    #[test]
+    fn body_until_close() {
+        let response = parse("HTTP/1.0 200 OK\r\n\r\nall of it").unwrap();
+
+        assert_eq!(response.version, "HTTP/1.0");
+        assert_eq!(response.body, b"all of it");
+    }
+
+    #[test]
+    fn interim_responses_are_skipped() {
+        let response =
+            parse("HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+
+        assert_eq!(response.status, 201);
+    }
+
+    #[test]
+    fn bodiless_responses_ignore_framing() {
+        let raw = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n";
+        let response = read_response(&mut Cursor::new(raw.as_bytes()), "HEAD").unwrap();
+        assert!(response.body.is_empty());
+
+        let response = parse("HTTP/1.1 304 Not Modified\r\nContent-Length: 5\r\n\r\n").unwrap();
+        assert!(response.body.is_empty());
+    }
+
+    #[test]
+    fn empty_reason_phrase_is_allowed() {
+        let response = parse("HTTP/1.1 404\r\nContent-Length: 0\r\n\r\n").unwrap();
+
+        assert_eq!(response.status, 404);
+        assert_eq!(response.reason, "");
+    }
+
+    #[test]
+    fn keep_alive_depends_on_version_headers_and_framing() {
+        let keeps = |raw: &str| parse(raw).unwrap().keeps_alive("GET");
+
+        assert!(keeps("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"));
+        assert!(keeps(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n"
+        ));
+        assert!(keeps("HTTP/1.1 204 No Content\r\n\r\n"));
+        assert!(keeps(
+            "HTTP/1.0 200 OK\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n"
+        ));
+
+        assert!(!keeps(
+            "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 0\r\n\r\n"
+        ));
+        assert!(!keeps("HTTP/1.1 200 OK\r\n\r\nuntil close"));
+        assert!(!keeps("HTTP/1.0 200 OK\r\nContent-Length: 0\r\n\r\n"));
+    }
+
+    #[test]
+    fn malformed_responses() {
+        for raw in [
+            "SMTP/1.0 200 OK\r\n\r\n",
+            "HTTP/1.1 2000 OK\r\n\r\n",
+            "HTTP/1.1 OK\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nno colon\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: many\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nxyz\r\n",
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n1\r\nab\r\n",
+        ] {
+            assert!(matches!(parse(raw), Err(Error::Malformed(_))), "{raw:?}");
+        }
+    }
+
+    #[test]
+    fn truncated_responses() {
+        for raw in [
+            "",
+            "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhel",
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhel",
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n",
+        ] {
+            assert!(matches!(parse(raw), Err(Error::Io(_))), "{raw:?}");
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! TCP streams that enforce the client's connect, read and overall time limits.
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// The time limits that apply to one exchange.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Limits {
+    /// Longest wait for a connection to be established.
+    pub(crate) connect: Option<Duration>,
+    /// Longest wait for any single read to make progress.
+    pub(crate) read: Option<Duration>,
+    /// When the whole request, redirects and retries included, must be done.
+    pub(crate) deadline: Option<Instant>,
+}
+
+impl Limits {
+    /// The shorter of `limit` and the time left before the deadline.
+    ///
+    /// Fails with `TimedOut` once the deadline has passed.
+    pub(crate) fn within(&self, limit: Option<Duration>) -> io::Result<Option<Duration>> {
+        let Some(deadline) = self.deadline else {
+            return Ok(limit);
+        };
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        Ok(Some(limit.map_or(remaining, |limit| limit.min(remaining))))
+    }
+}
+
+/// Which way bytes seen by a [`Client::wire_tap`](crate::Client::wire_tap) were going.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+type TapFn = dyn Fn(Direction, &[u8]) + Send + Sync;
+
+/// A callback shown every byte a connection sends or receives.
+#[derive(Clone)]
+pub(crate) struct Tap(pub(crate) Arc<TapFn>);
+
+impl fmt::Debug for Tap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Tap")
+    }
+}
+
+/// A TCP stream that applies the current [`Limits`] before every read and write.
+#[derive(Debug)]
+pub(crate) struct Socket {
+    pub(crate) stream: TcpStream,
+    pub(crate) limits: Limits,
+    /// Set for the exchange in progress, like `limits`.
+    pub(crate) tap: Option<Tap>,
+}
+
+impl Socket {
+    fn tap(&self, direction: Direction, bytes: &[u8]) {
+        if let Some(Tap(tap)) = &self.tap
+            && !bytes.is_empty()
+        {
+            tap(direction, bytes);
+        }
+    }
+}
+
+impl Socket {
+    /// Connects to the first address of `host:port` that answers within the limits.
+    pub(crate) fn connect(host: &str, port: u16, limits: Limits) -> io::Result<Self> {
+        let mut last_error = io::Error::new(io::ErrorKind::NotFound, "host has no addresses");
+        for addr in (host, port).to_socket_addrs()? {
+            let attempt = match limits.within(limits.connect)? {
+                Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+                None => TcpStream::connect(addr),
+            };
+            match attempt {
+                Ok(stream) => {
+                    // Requests are small and written whole, so don't hold them back
+                    stream.set_nodelay(true)?;
+                    return Ok(Self {
+                        stream,
+                        limits,
+                        tap: None,
+                    });
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+}
+
+impl Read for Socket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream
+            .set_read_timeout(self.limits.within(self.limits.read)?)?;
+        let read = self.stream.read(buf)?;
+        self.tap(Direction::Received, &buf[..read]);
+        Ok(read)
+    }
+}
+
+impl Write for Socket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.set_write_timeout(self.limits.within(None)?)?;
+        let written = self.stream.write(buf)?;
+        self.tap(Direction::Sent, &buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
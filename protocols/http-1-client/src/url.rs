@@ -0,0 +1,433 @@
+//! `http://` URLs, and the percent-encoding used in their queries and in form bodies.
+
+use std::{fmt, str::FromStr};
+
+use percent_encoding::{AsciiSet, CONTROLS, percent_decode_str, utf8_percent_encode};
+
+use crate::Error;
+
+/// Bytes escaped in a path given to [`Url::parse`]; `%` is left alone as it is already encoding.
+const PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'<')
+    .add(b'>')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Bytes escaped in a query or fragment given to [`Url::parse`].
+const QUERY: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'<').add(b'>');
+
+/// Bytes escaped in `application/x-www-form-urlencoded` names and values.
+///
+/// Everything but ASCII letters, digits and `*-._`; spaces become `+` afterwards.
+const FORM: &AsciiSet = &percent_encoding::NON_ALPHANUMERIC
+    .remove(b'*')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_');
+
+/// An absolute `http://host:port/path?query#fragment` URL.
+///
+/// The path, query and fragment are kept percent-encoded, exactly as they go on the wire.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Url {
+    host: String,
+    port: u16,
+    path: String,
+    query: Option<String>,
+    fragment: Option<String>,
+}
+
+impl Url {
+    /// Parses an absolute `http://` URL. Characters not allowed in a URL are escaped.
+    pub fn parse(input: &str) -> Result<Self, Error> {
+        let invalid = |reason: &str| Error::InvalidUrl(format!("{input:?}: {reason}"));
+
+        let (scheme, rest) = input
+            .trim()
+            .split_once("://")
+            .ok_or_else(|| invalid("not an absolute URL"))?;
+        if !scheme.eq_ignore_ascii_case("http") {
+            return Err(invalid("only http:// is supported"));
+        }
+
+        let (rest, fragment) = split_off(rest, '#');
+        let (rest, query) = split_off(rest, '?');
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        if authority.contains('@') {
+            return Err(invalid("credentials are not supported"));
+        }
+
+        let (host, port) = split_port(authority).ok_or_else(|| invalid("bad port"))?;
+        if host.is_empty() {
+            return Err(invalid("missing host"));
+        }
+
+        Ok(Self {
+            host: host.to_ascii_lowercase(),
+            port,
+            path: match path {
+                "" => "/".to_string(),
+                path => utf8_percent_encode(path, PATH).to_string(),
+            },
+            query: query.map(|q| utf8_percent_encode(q, QUERY).to_string()),
+            fragment: fragment.map(|f| utf8_percent_encode(f, QUERY).to_string()),
+        })
+    }
+
+    /// The host name or address, without brackets around an IPv6 address.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The path, always starting with `/`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The query without its `?`, if there is one.
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    /// The fragment without its `#`, if there is one. It is never sent to the server.
+    pub fn fragment(&self) -> Option<&str> {
+        self.fragment.as_deref()
+    }
+
+    /// The request target: the path plus any query.
+    pub fn target(&self) -> String {
+        match &self.query {
+            Some(query) => format!("{}?{query}", self.path),
+            None => self.path.clone(),
+        }
+    }
+
+    /// The value for the `Host` header; the port is only included when it is not 80.
+    pub fn authority(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        match self.port {
+            80 => host,
+            port => format!("{host}:{port}"),
+        }
+    }
+
+    /// The query decoded into name and value pairs.
+    pub fn query_pairs(&self) -> Vec<(String, String)> {
+        self.query.as_deref().map(form_decode).unwrap_or_default()
+    }
+
+    /// Appends `name=value` to the query, percent-encoding both.
+    pub fn query_pair(mut self, name: &str, value: &str) -> Self {
+        let pair = form_encode([(name, value)]);
+        self.query = Some(match self.query.take() {
+            Some(query) if !query.is_empty() => format!("{query}&{pair}"),
+            _ => pair,
+        });
+        self
+    }
+
+    /// Resolves `reference` (as found in a `Location` header) against this URL.
+    ///
+    /// Follows RFC 3986: absolute URLs replace this one, `//host/...` keeps only the scheme,
+    /// and paths are taken relative to this URL's path with `.` and `..` segments removed.
+    pub fn join(&self, reference: &str) -> Result<Url, Error> {
+        let reference = reference.trim();
+        // A scheme is letters and a few symbols ending in `:` before any `/`, `?` or `#`
+        let has_scheme = reference
+            .find(':')
+            .is_some_and(|i| !reference[..i].contains(['/', '?', '#']) && i > 0);
+        if has_scheme {
+            return Url::parse(reference);
+        }
+        if reference.starts_with("//") {
+            return Url::parse(&format!("http:{reference}"));
+        }
+
+        let (rest, fragment) = split_off(reference, '#');
+        let (path, query) = split_off(rest, '?');
+        let mut url = self.clone();
+        url.fragment = fragment.map(|f| utf8_percent_encode(f, QUERY).to_string());
+
+        if path.is_empty() {
+            // Same document; a query in the reference replaces ours
+            if query.is_some() {
+                url.query = query.map(|q| utf8_percent_encode(q, QUERY).to_string());
+            }
+            return Ok(url);
+        }
+
+        let merged = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            let directory = &self.path[..self.path.rfind('/').map_or(0, |i| i + 1)];
+            format!("{directory}{path}")
+        };
+        url.path = utf8_percent_encode(&remove_dot_segments(&merged), PATH).to_string();
+        url.query = query.map(|q| utf8_percent_encode(q, QUERY).to_string());
+        Ok(url)
+    }
+}
+
+impl fmt::Display for Url {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "http://{}{}", self.authority(), self.target())?;
+        if let Some(fragment) = &self.fragment {
+            write!(f, "#{fragment}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Url {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Url::parse(s)
+    }
+}
+
+/// Encodes pairs as `application/x-www-form-urlencoded`, as used in queries and form bodies.
+pub fn form_encode<N: AsRef<str>, V: AsRef<str>>(
+    pairs: impl IntoIterator<Item = (N, V)>,
+) -> String {
+    let encode = |s: &str| utf8_percent_encode(s, FORM).to_string().replace("%20", "+");
+    pairs
+        .into_iter()
+        .map(|(name, value)| format!("{}={}", encode(name.as_ref()), encode(value.as_ref())))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Decodes `application/x-www-form-urlencoded` text into pairs; invalid UTF-8 is replaced.
+pub fn form_decode(input: &str) -> Vec<(String, String)> {
+    let decode = |s: &str| {
+        percent_decode_str(&s.replace('+', " "))
+            .decode_utf8_lossy()
+            .into_owned()
+    };
+    input
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(name), decode(value))
+        })
+        .collect()
+}
+
+/// Splits `s` at the first `delimiter`, dropping the delimiter itself.
+fn split_off(s: &str, delimiter: char) -> (&str, Option<&str>) {
+    match s.split_once(delimiter) {
+        Some((before, after)) => (before, Some(after)),
+        None => (s, None),
+    }
+}
+
+/// Splits `host[:port]` or `[v6]:port`, defaulting the port to 80.
+fn split_port(authority: &str) -> Option<(&str, u16)> {
+    let (host, port) = if let Some(rest) = authority.strip_prefix('[') {
+        let (host, after) = rest.split_once(']')?;
+        match after {
+            "" => (host, None),
+            after => (host, Some(after.strip_prefix(':')?)),
+        }
+    } else {
+        match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+    let port = match port {
+        None | Some("") => 80,
+        Some(port) => port.parse().ok()?,
+    };
+    Some((host, port))
+}
+
+/// Applies `.` and `..` segments in an absolute path, as RFC 3986 section 5.2.4 describes.
+fn remove_dot_segments(path: &str) -> String {
+    let mut output: Vec<&str> = Vec::new();
+    let segments: Vec<&str> = path.split('/').skip(1).collect();
+    for (i, segment) in segments.iter().enumerate() {
+        let last = i + 1 == segments.len();
+        match *segment {
+            "." | ".." => {
+                if *segment == ".." {
+                    output.pop();
+                }
+                // A trailing dot segment still names a directory
+                if last {
+                    output.push("");
+                }
+            }
+            segment => output.push(segment),
+        }
+    }
+    format!("/{}", output.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn parses_every_part() {
+        let url = url("http://Example.COM:8080/a/b?x=1&y=2#top");
+
+        assert_eq!(url.host(), "example.com");
+        assert_eq!(url.port(), 8080);
+        assert_eq!(url.path(), "/a/b");
+        assert_eq!(url.query(), Some("x=1&y=2"));
+        assert_eq!(url.fragment(), Some("top"));
+        assert_eq!(url.target(), "/a/b?x=1&y=2");
+        assert_eq!(url.authority(), "example.com:8080");
+        assert_eq!(url.to_string(), "http://example.com:8080/a/b?x=1&y=2#top");
+    }
+
+    #[test]
+    fn defaults_and_edge_cases() {
+        assert_eq!(url("http://h").target(), "/");
+        assert_eq!(url("http://h:").port(), 80);
+        assert_eq!(url("http://h?q").target(), "/?q");
+        assert_eq!(url("HTTP://h/").authority(), "h");
+        assert_eq!(url("http://h:80/").to_string(), "http://h/");
+        assert_eq!(url("http://h/a b/ü").path(), "/a%20b/%C3%BC");
+        assert_eq!(url("http://h/%41").path(), "/%41");
+    }
+
+    #[test]
+    fn ipv6_hosts() {
+        let url = url("http://[::1]:9000/x");
+
+        assert_eq!(url.host(), "::1");
+        assert_eq!(url.port(), 9000);
+        assert_eq!(url.authority(), "[::1]:9000");
+        assert_eq!(Url::parse("http://[::1]/").unwrap().authority(), "[::1]");
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_use() {
+        for input in [
+            "example.com/path",
+            "https://example.com/",
+            "ftp://example.com/",
+            "http://:80/",
+            "http://h:99999/",
+            "http://h:x/",
+            "http://user:pass@h/",
+            "http://[::1/",
+        ] {
+            assert!(
+                matches!(Url::parse(input), Err(Error::InvalidUrl(_))),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn query_pairs_are_encoded_and_decoded() {
+        let url = url("http://h/search?lang=en")
+            .query_pair("q", "rust & tea")
+            .query_pair("page", "2/3");
+
+        assert_eq!(url.query(), Some("lang=en&q=rust+%26+tea&page=2%2F3"));
+        assert_eq!(
+            url.query_pairs(),
+            [
+                ("lang".to_string(), "en".to_string()),
+                ("q".to_string(), "rust & tea".to_string()),
+                ("page".to_string(), "2/3".to_string()),
+            ]
+        );
+        assert_eq!(
+            Url::parse("http://h/")
+                .unwrap()
+                .query_pair("a", "")
+                .target(),
+            "/?a="
+        );
+    }
+
+    #[test]
+    fn form_encoding() {
+        assert_eq!(
+            form_encode([
+                ("name", "Zoë Smith"),
+                ("note", "1+1=2; 100%"),
+                ("ok", "a-b_c.d*")
+            ]),
+            "name=Zo%C3%AB+Smith&note=1%2B1%3D2%3B+100%25&ok=a-b_c.d*"
+        );
+        assert_eq!(
+            form_decode("name=Zo%C3%AB+Smith&empty=&flag&&note=1%2B1"),
+            [
+                ("name".to_string(), "Zoë Smith".to_string()),
+                ("empty".to_string(), String::new()),
+                ("flag".to_string(), String::new()),
+                ("note".to_string(), "1+1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn join_follows_rfc_3986_examples() {
+        // From RFC 3986 section 5.4, with the base adapted to http
+        let base = url("http://a/b/c/d;p?q");
+        for (reference, expected) in [
+            ("g", "http://a/b/c/g"),
+            ("./g", "http://a/b/c/g"),
+            ("g/", "http://a/b/c/g/"),
+            ("/g", "http://a/g"),
+            ("//g", "http://g/"),
+            ("?y", "http://a/b/c/d;p?y"),
+            ("g?y", "http://a/b/c/g?y"),
+            ("#s", "http://a/b/c/d;p?q#s"),
+            ("g#s", "http://a/b/c/g#s"),
+            (";x", "http://a/b/c/;x"),
+            ("", "http://a/b/c/d;p?q"),
+            (".", "http://a/b/c/"),
+            ("./", "http://a/b/c/"),
+            ("..", "http://a/b/"),
+            ("../", "http://a/b/"),
+            ("../g", "http://a/b/g"),
+            ("../..", "http://a/"),
+            ("../../g", "http://a/g"),
+            ("../../../g", "http://a/g"),
+            ("/./g", "http://a/g"),
+            ("/../g", "http://a/g"),
+            ("g.", "http://a/b/c/g."),
+            ("..g", "http://a/b/c/..g"),
+            ("./g/.", "http://a/b/c/g/"),
+            ("g/../h", "http://a/b/c/h"),
+            ("http://other:81/x", "http://other:81/x"),
+        ] {
+            assert_eq!(
+                base.join(reference).unwrap().to_string(),
+                expected,
+                "{reference:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn join_rejects_other_schemes() {
+        let base = url("http://a/");
+        assert!(base.join("https://a/").is_err());
+        assert!(base.join("mailto:x@y").is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    fs,
+    net::TcpListener,
+    process::{Command, Output},
+};
+
+mod common;
+use common::start_mock_server;
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_http-1-client"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn prints_the_body() {
+    let (port, request) = start_mock_server("HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+
+    let output = run(&[&format!("http://127.0.0.1:{port}/greeting")]);
+    let request = request.recv().unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "hello");
+    assert!(request.starts_with("GET /greeting HTTP/1.1\r\n"));
+}
+
+#[test]
+fn include_prints_status_line_and_headers() {
+    let (port, _) =
+        start_mock_server("HTTP/1.1 200 OK\r\nContent-Length: 2\r\nX-Served-By: mock\r\n\r\nok");
+
+    let output = run(&["-i", &format!("http://127.0.0.1:{port}/")]);
+
+    assert_eq!(
+        stdout(&output),
+        "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nX-Served-By: mock\r\n\r\nok"
+    );
+}
+
+#[test]
+fn method_headers_and_data_are_sent() {
+    let (port, request) = start_mock_server("HTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n");
+
+    let output = run(&[
+        "-X",
+        "put",
+        "-H",
+        "Content-Type: application/json",
+        "-H",
+        "X-Trace:42",
+        "-d",
+        r#"{"name":"widget"}"#,
+        &format!("http://127.0.0.1:{port}/items/1"),
+    ]);
+    let request = request.recv().unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(request.starts_with("PUT /items/1 HTTP/1.1\r\n"));
+    assert!(request.contains("\r\nContent-Type: application/json\r\n"));
+    assert!(request.contains("\r\nX-Trace: 42\r\n"));
+    assert!(!request.contains("x-www-form-urlencoded"));
+    assert!(request.ends_with("\r\nContent-Length: 17\r\n\r\n{\"name\":\"widget\"}"));
+}
+
+#[test]
+fn data_implies_a_form_post() {
+    let (port, request) = start_mock_server("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+    let file = std::env::temp_dir().join(format!("http-1-cli-data-{}", std::process::id()));
+    fs::write(&file, "c=3").unwrap();
+
+    run(&[
+        "-d",
+        "a=1",
+        "--data",
+        "b=2",
+        "-d",
+        &format!("@{}", file.display()),
+        &format!("http://127.0.0.1:{port}/form"),
+    ]);
+    let request = request.recv().unwrap();
+
+    assert!(request.starts_with("POST /form HTTP/1.1\r\n"));
+    assert!(request.contains("\r\nContent-Type: application/x-www-form-urlencoded\r\n"));
+    assert!(request.ends_with("\r\n\r\na=1&b=2&c=3"));
+}
+
+#[test]
+fn output_goes_to_a_file() {
+    let (port, _) = start_mock_server("HTTP/1.1 200 OK\r\nContent-Length: 7\r\n\r\ncontent");
+    let file = std::env::temp_dir().join(format!("http-1-cli-output-{}", std::process::id()));
+
+    let output = run(&[
+        "-o",
+        file.to_str().unwrap(),
+        &format!("http://127.0.0.1:{port}/"),
+    ]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "");
+    assert_eq!(fs::read_to_string(&file).unwrap(), "content");
+}
+
+#[test]
+fn verbose_shows_both_heads_but_not_bodies() {
+    let (port, _) = start_mock_server("HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nbody");
+
+    let output = run(&[
+        "-v",
+        "-d",
+        "secret=1",
+        &format!("http://127.0.0.1:{port}/v"),
+    ]);
+    let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+
+    assert!(stderr.contains("> POST /v HTTP/1.1\n"), "{stderr}");
+    assert!(stderr.contains(&format!("> Host: 127.0.0.1:{port}\n")));
+    assert!(stderr.contains("< HTTP/1.1 200 OK\n< Content-Length: 4\n< \n"));
+    assert!(!stderr.contains("secret=1"));
+    assert!(!stderr.contains("body"));
+    assert_eq!(stdout(&output), "body");
+}
+
+#[test]
+fn exit_code_follows_the_status_class() {
+    for (response, code) in [
+        ("HTTP/1.1 204 No Content\r\n\r\n", 0),
+        (
+            "HTTP/1.1 301 Moved Permanently\r\nLocation: /elsewhere\r\nContent-Length: 0\r\n\r\n",
+            3,
+        ),
+        ("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n", 4),
+        (
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n",
+            5,
+        ),
+    ] {
+        let (port, _) = start_mock_server(response);
+
+        let output = run(&[&format!("http://127.0.0.1:{port}/")]);
+
+        assert_eq!(output.status.code(), Some(code), "{response}");
+    }
+}
+
+#[test]
+fn failures_and_bad_arguments_have_their_own_codes() {
+    // Nothing listens on a port that was just released
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    assert_eq!(
+        run(&[&format!("http://127.0.0.1:{port}/")]).status.code(),
+        Some(1)
+    );
+    assert_eq!(run(&["https://example.com/"]).status.code(), Some(1));
+    assert_eq!(
+        run(&["-H", "no colon", "http://127.0.0.1/"]).status.code(),
+        Some(2)
+    );
+    assert_eq!(run(&[]).status.code(), Some(2));
+}
@@ -1,24 +1,12 @@
-use std::{
-    io::{Read, Write},
-    net::{TcpListener, TcpStream},
-    thread,
-    time::Duration,
-};
-
-fn start_mock_server(response: &'static str) -> u16 {
-    let listener = TcpListener::bind("127.0.0.1:0").unwrap(); // Bind to a random port
-    let port = listener.local_addr().unwrap().port();
-
-    thread::spawn(move || {
-        if let Ok((mut stream, _)) = listener.accept() {
-            let mut buffer = [0; 1024];
-            let _ = stream.read(&mut buffer);
-            stream.write_all(response.as_bytes()).unwrap();
-            stream.flush().unwrap();
-        }
-    });
-
-    port
+use std::net::TcpListener;
+
+use http_1_client::{Client, Error, Request, Url};
+
+mod common;
+use common::start_mock_server;
+
+fn url(port: u16, target: &str) -> Url {
+    Url::parse(&format!("http://127.0.0.1:{port}{target}")).unwrap()
 }
 
 #[test]
@@ -30,19 +18,17 @@ Content-Type: text/plain\r\n\
 \r\n\
 Hello, world!";
 
-    let port = start_mock_server(response);
+    let (port, _) = start_mock_server(response);
 
-    // Simulate the client
-    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
-    let request = format!("GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
-    stream.write_all(request.as_bytes()).unwrap();
+    let response = Client::new()
+        .get(&format!("http://127.0.0.1:{port}/"))
+        .unwrap();
 
-    let mut response = String::new();
-    stream.read_to_string(&mut response).unwrap();
-
-    assert!(response.contains("HTTP/1.1 200 OK"));
-    assert!(response.contains("Content-Length: 13"));
-    assert!(response.contains("Hello, world!"));
+    assert_eq!(response.status, 200);
+    assert_eq!(response.reason, "OK");
+    assert_eq!(response.header("content-length"), Some("13"));
+    assert_eq!(response.header("Content-Type"), Some("text/plain"));
+    assert_eq!(response.text(), "Hello, world!");
 }
 
 #[test]
@@ -54,20 +40,136 @@ Content-Type: text/plain\r\n\
 \r\n\
 7\r\n\
 Chunk 1\r\n\
-6\r\n\
+5\r\n\
 -1234\r\n\
 0\r\n\
 \r\n";
 
-    let port = start_mock_server(response);
+    let (port, _) = start_mock_server(response);
+
+    let response = Client::new()
+        .get(&format!("http://127.0.0.1:{port}/"))
+        .unwrap();
+
+    assert_eq!(response.text(), "Chunk 1-1234");
+    assert!(response.trailers.is_empty());
+}
+
+#[test]
+fn test_client_reads_trailers() {
+    let response = "\
+HTTP/1.1 200 OK\r\n\
+Transfer-Encoding: chunked\r\n\
+Trailer: X-Checksum\r\n\
+\r\n\
+5\r\n\
+hello\r\n\
+0\r\n\
+X-Checksum: abc123\r\n\
+\r\n";
+
+    let (port, _) = start_mock_server(response);
+
+    let response = Client::new()
+        .get(&format!("http://127.0.0.1:{port}/"))
+        .unwrap();
+
+    assert_eq!(response.text(), "hello");
+    assert_eq!(response.trailer("x-checksum"), Some("abc123"));
+}
+
+#[test]
+fn test_client_sends_method_headers_and_body() {
+    let (port, request) = start_mock_server("HTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n");
+
+    let response = Client::new()
+        .send(
+            &Request::new("PUT", url(port, "/items/1?force=yes"))
+                .header("Content-Type", "application/json")
+                .header("X-Trace", "42")
+                .body(r#"{"name":"widget"}"#),
+        )
+        .unwrap();
+    let request = request.recv().unwrap();
+
+    assert_eq!(response.status, 201);
+    assert!(request.starts_with("PUT /items/1?force=yes HTTP/1.1\r\n"));
+    assert!(request.contains(&format!("\r\nHost: 127.0.0.1:{port}\r\n")));
+    assert!(request.contains("\r\nContent-Type: application/json\r\n"));
+    assert!(request.contains("\r\nX-Trace: 42\r\n"));
+    assert!(request.contains("\r\nContent-Length: 17\r\n"));
+    assert!(request.ends_with("\r\n\r\n{\"name\":\"widget\"}"));
+}
+
+#[test]
+fn test_client_head_ignores_content_length() {
+    let (port, _) = start_mock_server("HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\n");
+
+    let response = Client::new()
+        .send(&Request::new("HEAD", url(port, "/")))
+        .unwrap();
+
+    assert_eq!(response.header("Content-Length"), Some("1000"));
+    assert!(response.body.is_empty());
+}
+
+#[test]
+fn test_client_reports_truncated_body() {
+    let (port, _) = start_mock_server("HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\nshort");
+
+    let result = Client::new().get(&format!("http://127.0.0.1:{port}/"));
+
+    assert!(matches!(result, Err(Error::Io(_))));
+}
+
+#[test]
+fn test_client_reports_malformed_response() {
+    let (port, _) = start_mock_server("I am not HTTP\r\n\r\n");
+
+    let result = Client::new().get(&format!("http://127.0.0.1:{port}/"));
+
+    assert!(matches!(result, Err(Error::Malformed(_))));
+}
+
+#[test]
+fn test_client_reports_connection_refused() {
+    // Grab a free port and close it again so nothing is listening there
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let result = Client::new().get(&format!("http://127.0.0.1:{port}/"));
 
-    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
-    let request = "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
-    stream.write_all(request.as_bytes()).unwrap();
+    assert!(matches!(result, Err(Error::Io(_))));
+}
+
+#[test]
+fn test_client_sends_encoded_query_and_form() {
+    let (port, request) = start_mock_server("HTTP/1.1 204 No Content\r\n\r\n");
 
-    let mut buffer = String::new();
-    stream.read_to_string(&mut buffer).unwrap();
+    let target = url(port, "/search").query_pair("q", "fish & chips");
+    Client::new()
+        .send(&Request::new("POST", target).form([("city", "São Paulo"), ("n", "1")]))
+        .unwrap();
+    let request = request.recv().unwrap();
 
-    assert!(buffer.contains("Chunk 1"));
-    assert!(buffer.contains("-1234"));
+    assert!(request.starts_with("POST /search?q=fish+%26+chips HTTP/1.1\r\n"));
+    assert!(request.contains("\r\nContent-Type: application/x-www-form-urlencoded\r\n"));
+    assert!(request.ends_with("\r\n\r\ncity=S%C3%A3o+Paulo&n=1"));
+}
+
+#[test]
+fn test_client_rejects_invalid_urls() {
+    for input in [
+        "localhost:8080/",
+        "https://localhost/",
+        "http://localhost:port/",
+    ] {
+        assert!(
+            matches!(Client::new().get(input), Err(Error::InvalidUrl(_))),
+            "{input}"
+        );
+    }
 }
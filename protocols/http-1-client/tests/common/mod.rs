@@ -0,0 +1,47 @@
+//! A mock server shared by the integration tests.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+// Answer one connection with `response`, handing back the raw request it received
+pub fn start_mock_server(response: &'static str) -> (u16, Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap(); // Bind to a random port
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        if let Ok((mut stream, _)) = listener.accept() {
+            let request = read_request(&mut BufReader::new(&mut stream));
+            stream.write_all(response.as_bytes()).unwrap();
+            stream.flush().unwrap();
+            let _ = tx.send(request);
+        }
+    });
+
+    (port, rx)
+}
+
+// Read the head and a Content-Length body, which is all the client ever sends
+fn read_request(reader: &mut impl BufRead) -> String {
+    let mut request = String::new();
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+            length = value.trim().parse().unwrap();
+        }
+        request.push_str(&line);
+        if line == "\r\n" {
+            break;
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    request.push_str(&String::from_utf8(body).unwrap());
+    request
+}
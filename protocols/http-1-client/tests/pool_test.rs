@@ -0,0 +1,270 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use http_1_client::{Client, Error, Request, Url};
+
+/// How the stand-in server treats each connection.
+#[derive(Clone, Copy)]
+struct Behaviour {
+    /// Requests answered on a connection before the server hangs up.
+    per_connection: usize,
+    /// Silently drop the connection when the next request arrives, instead of answering it.
+    drop_unanswered: bool,
+    /// Add `Connection: close` to every response.
+    close: bool,
+    delay: Duration,
+}
+
+impl Default for Behaviour {
+    fn default() -> Self {
+        Self {
+            per_connection: usize::MAX,
+            drop_unanswered: false,
+            close: false,
+            delay: Duration::ZERO,
+        }
+    }
+}
+
+struct Stats {
+    accepted: AtomicUsize,
+    active: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+// A keep-alive server answering each request with the number of the connection it came on
+fn start_server(behaviour: Behaviour) -> (u16, Arc<Stats>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let stats = Arc::new(Stats {
+        accepted: AtomicUsize::new(0),
+        active: AtomicUsize::new(0),
+        peak: AtomicUsize::new(0),
+    });
+
+    let server_stats = Arc::clone(&stats);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let id = server_stats.accepted.fetch_add(1, Ordering::SeqCst) + 1;
+            let active = server_stats.active.fetch_add(1, Ordering::SeqCst) + 1;
+            server_stats.peak.fetch_max(active, Ordering::SeqCst);
+
+            let stats = Arc::clone(&server_stats);
+            thread::spawn(move || {
+                serve(stream, id, behaviour);
+                stats.active.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    });
+
+    (port, stats)
+}
+
+fn serve(mut stream: TcpStream, id: usize, behaviour: Behaviour) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    for answered in 0.. {
+        // Read one request head; the tests send no bodies
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                return;
+            }
+            if line == "\r\n" {
+                break;
+            }
+        }
+        // Only reached with `drop_unanswered`; otherwise the close came after the last answer
+        if answered == behaviour.per_connection {
+            return;
+        }
+
+        thread::sleep(behaviour.delay);
+        let body = id.to_string();
+        let connection = if behaviour.close {
+            "Connection: close\r\n"
+        } else {
+            ""
+        };
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n{connection}\r\n{body}",
+            body.len()
+        );
+        if stream.write_all(response.as_bytes()).is_err() || behaviour.close {
+            return;
+        }
+        if answered + 1 == behaviour.per_connection && !behaviour.drop_unanswered {
+            return;
+        }
+    }
+}
+
+fn accepted(stats: &Stats) -> usize {
+    stats.accepted.load(Ordering::SeqCst)
+}
+
+fn url(port: u16, target: &str) -> Url {
+    Url::parse(&format!("http://127.0.0.1:{port}{target}")).unwrap()
+}
+
+#[test]
+fn sequential_requests_share_one_connection() {
+    let (port, stats) = start_server(Behaviour::default());
+    let client = Client::new();
+
+    for _ in 0..20 {
+        let response = client.get(&format!("http://127.0.0.1:{port}/")).unwrap();
+        assert_eq!(response.text(), "1");
+    }
+
+    assert_eq!(accepted(&stats), 1);
+}
+
+#[test]
+fn hosts_get_separate_connections() {
+    let (first, first_stats) = start_server(Behaviour::default());
+    let (second, second_stats) = start_server(Behaviour::default());
+    let client = Client::new();
+
+    for _ in 0..5 {
+        client.get(&format!("http://127.0.0.1:{first}/")).unwrap();
+        client.get(&format!("http://127.0.0.1:{second}/")).unwrap();
+    }
+
+    assert_eq!(accepted(&first_stats), 1);
+    assert_eq!(accepted(&second_stats), 1);
+}
+
+#[test]
+fn connections_closed_by_the_server_are_replaced() {
+    let (port, stats) = start_server(Behaviour {
+        per_connection: 1,
+        ..Behaviour::default()
+    });
+    let client = Client::new();
+
+    for expected in 1..=5 {
+        // Give the server's close time to arrive so the idle connection is seen to be stale
+        thread::sleep(Duration::from_millis(20));
+        let response = client.get(&format!("http://127.0.0.1:{port}/")).unwrap();
+        assert_eq!(response.text(), expected.to_string());
+    }
+
+    assert_eq!(accepted(&stats), 5);
+}
+
+#[test]
+fn idempotent_request_is_retried_when_a_reused_connection_dies() {
+    let (port, stats) = start_server(Behaviour {
+        per_connection: 1,
+        drop_unanswered: true,
+        ..Behaviour::default()
+    });
+    let client = Client::new();
+
+    assert_eq!(
+        client
+            .get(&format!("http://127.0.0.1:{port}/"))
+            .unwrap()
+            .text(),
+        "1"
+    );
+    // The pooled connection looks fine, but the server drops it on the next request
+    assert_eq!(
+        client
+            .get(&format!("http://127.0.0.1:{port}/"))
+            .unwrap()
+            .text(),
+        "2"
+    );
+    assert_eq!(accepted(&stats), 2);
+}
+
+#[test]
+fn non_idempotent_request_is_not_retried() {
+    let (port, stats) = start_server(Behaviour {
+        per_connection: 1,
+        drop_unanswered: true,
+        ..Behaviour::default()
+    });
+    let client = Client::new();
+
+    client.get(&format!("http://127.0.0.1:{port}/")).unwrap();
+    let result = client.send(&Request::new("POST", url(port, "/")));
+
+    assert!(matches!(result, Err(Error::Io(_))));
+    assert_eq!(accepted(&stats), 1);
+}
+
+#[test]
+fn connection_close_is_honoured() {
+    let (port, stats) = start_server(Behaviour {
+        close: true,
+        ..Behaviour::default()
+    });
+    let client = Client::new();
+
+    for _ in 0..3 {
+        client.get(&format!("http://127.0.0.1:{port}/")).unwrap();
+    }
+    assert_eq!(accepted(&stats), 3);
+
+    let (port, stats) = start_server(Behaviour::default());
+    for _ in 0..3 {
+        client
+            .send(&Request::get(url(port, "/")).header("Connection", "close"))
+            .unwrap();
+    }
+    assert_eq!(accepted(&stats), 3);
+}
+
+#[test]
+fn idle_timeout_discards_old_connections() {
+    let (port, stats) = start_server(Behaviour::default());
+    let client = Client::new().idle_timeout(Duration::from_millis(50));
+
+    client.get(&format!("http://127.0.0.1:{port}/")).unwrap();
+    client.get(&format!("http://127.0.0.1:{port}/")).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    client.get(&format!("http://127.0.0.1:{port}/")).unwrap();
+
+    assert_eq!(accepted(&stats), 2);
+}
+
+#[test]
+fn connections_per_host_are_limited() {
+    let (port, stats) = start_server(Behaviour {
+        delay: Duration::from_millis(20),
+        ..Behaviour::default()
+    });
+    let client = Arc::new(Client::new().max_connections_per_host(2));
+
+    let workers: Vec<_> = (0..8)
+        .map(|_| {
+            let client = Arc::clone(&client);
+            thread::spawn(move || {
+                for _ in 0..5 {
+                    assert!(
+                        client
+                            .get(&format!("http://127.0.0.1:{port}/"))
+                            .unwrap()
+                            .is_success()
+                    );
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    assert_eq!(accepted(&stats), 2);
+    assert!(stats.peak.load(Ordering::SeqCst) <= 2);
+}
@@ -0,0 +1,225 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use http_1_client::{Client, Error, Request, Url};
+
+/// One request as the stand-in server saw it.
+#[derive(Clone, Debug)]
+struct Seen {
+    method: String,
+    target: String,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Seen {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+// A keep-alive server answering each request with whatever `respond` returns for it
+fn start_server(
+    respond: impl Fn(&Seen) -> String + Send + Sync + 'static,
+) -> (u16, Arc<Mutex<Vec<Seen>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let respond = Arc::new(respond);
+
+    let log = Arc::clone(&seen);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let (log, respond) = (Arc::clone(&log), Arc::clone(&respond));
+            thread::spawn(move || {
+                let mut writer = stream.try_clone().unwrap();
+                let mut reader = BufReader::new(stream);
+                while let Some(request) = read_request(&mut reader) {
+                    log.lock().unwrap().push(request.clone());
+                    let response = respond(&request);
+                    if writer.write_all(response.as_bytes()).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+
+    (port, seen)
+}
+
+fn read_request(reader: &mut impl BufRead) -> Option<Seen> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).ok()? == 0 {
+        return None;
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?.to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':')?;
+        headers.push((name.to_string(), value.trim().to_string()));
+    }
+
+    let mut request = Seen {
+        method,
+        target,
+        headers,
+        body: String::new(),
+    };
+    let length = request
+        .header("content-length")
+        .map_or(0, |l| l.parse().unwrap());
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+    request.body = String::from_utf8(body).unwrap();
+    Some(request)
+}
+
+fn redirect(status: u16, location: &str) -> String {
+    format!("HTTP/1.1 {status} Redirect\r\nLocation: {location}\r\nContent-Length: 0\r\n\r\n")
+}
+
+fn ok(body: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+fn seen(log: &Mutex<Vec<Seen>>) -> Vec<Seen> {
+    log.lock().unwrap().clone()
+}
+
+fn url(port: u16, target: &str) -> Url {
+    Url::parse(&format!("http://127.0.0.1:{port}{target}")).unwrap()
+}
+
+#[test]
+fn redirect_chain_is_followed() {
+    let (port, log) = start_server(|request| match request.target.as_str() {
+        "/a" => redirect(301, "/b"),
+        "/b" => redirect(302, "c?x=1"),
+        "/c?x=1" => redirect(308, "/d"),
+        _ => ok("arrived"),
+    });
+
+    let response = Client::new()
+        .get(&format!("http://127.0.0.1:{port}/a"))
+        .unwrap();
+
+    assert_eq!(response.text(), "arrived");
+    let targets: Vec<_> = seen(&log).into_iter().map(|r| r.target).collect();
+    assert_eq!(targets, ["/a", "/b", "/c?x=1", "/d"]);
+}
+
+#[test]
+fn post_becomes_get_after_moved_and_found() {
+    for status in [301, 302, 303] {
+        let (port, log) = start_server(move |request| match request.target.as_str() {
+            "/form" => redirect(status, "/done"),
+            _ => ok("done"),
+        });
+
+        let request = Request::new("POST", url(port, "/form"))
+            .header("Content-Type", "text/plain")
+            .body("payload");
+        Client::new().send(&request).unwrap();
+
+        let log = seen(&log);
+        assert_eq!(log[1].method, "GET", "{status}");
+        assert_eq!(log[1].body, "");
+        assert_eq!(log[1].header("content-type"), None);
+    }
+}
+
+#[test]
+fn temporary_and_permanent_redirects_keep_method_and_body() {
+    for status in [307, 308] {
+        let (port, log) = start_server(move |request| match request.target.as_str() {
+            "/old" => redirect(status, "/new"),
+            _ => ok("stored"),
+        });
+
+        let request = Request::new("POST", url(port, "/old")).body("payload");
+        let response = Client::new().send(&request).unwrap();
+
+        assert_eq!(response.text(), "stored");
+        let log = seen(&log);
+        assert_eq!(log[1].method, "POST", "{status}");
+        assert_eq!(log[1].body, "payload");
+    }
+}
+
+#[test]
+fn redirect_loop_hits_the_hop_limit() {
+    let (port, log) = start_server(|_| redirect(302, "/again"));
+
+    let result = Client::new()
+        .max_redirects(3)
+        .get(&format!("http://127.0.0.1:{port}/start"));
+
+    assert!(matches!(result, Err(Error::TooManyRedirects)));
+    // The original request plus three redirects
+    assert_eq!(seen(&log).len(), 4);
+}
+
+#[test]
+fn following_can_be_turned_off() {
+    let (port, log) = start_server(|_| redirect(301, "/elsewhere"));
+
+    let response = Client::new()
+        .max_redirects(0)
+        .get(&format!("http://127.0.0.1:{port}/"))
+        .unwrap();
+
+    assert_eq!(response.status, 301);
+    assert_eq!(response.header("location"), Some("/elsewhere"));
+    assert_eq!(seen(&log).len(), 1);
+}
+
+#[test]
+fn cross_host_redirect_drops_credentials() {
+    let (target_port, target_log) = start_server(|_| ok("other host"));
+    let (port, log) =
+        start_server(move |_| redirect(307, &format!("http://localhost:{target_port}/landing")));
+
+    let request = Request::get(url(port, "/")).header("Authorization", "Bearer secret");
+    let response = Client::new().send(&request).unwrap();
+
+    assert_eq!(response.text(), "other host");
+    assert_eq!(seen(&log)[0].header("authorization"), Some("Bearer secret"));
+    let landed = &seen(&target_log)[0];
+    assert_eq!(landed.target, "/landing");
+    assert_eq!(landed.header("authorization"), None);
+    assert_eq!(
+        landed.header("host"),
+        Some(&*format!("localhost:{target_port}"))
+    );
+}
+
+#[test]
+fn unsupported_location_is_an_error() {
+    let (port, _) = start_server(|_| redirect(302, "https://secure.test/"));
+
+    let result = Client::new().get(&format!("http://127.0.0.1:{port}/"));
+
+    assert!(
+        matches!(result, Err(Error::InvalidRedirect(location)) if location == "https://secure.test/")
+    );
+}
@@ -0,0 +1,236 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use http_1_client::{Client, Error, Request, Url};
+
+/// What the stand-in server does with the nth request it receives.
+enum Action {
+    Reply(&'static str),
+    /// Read the request and never answer it.
+    Hang,
+    /// Send the response a byte at a time with a pause before each.
+    Drip(&'static str, Duration),
+    /// Hang up without answering.
+    Drop,
+}
+
+const OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+const UNAVAILABLE: &str = "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n";
+
+// Answer the nth request (counting from 0) with `script(n)`, returning the request count
+fn start_server(
+    script: impl Fn(usize) -> Action + Send + Sync + 'static,
+) -> (u16, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let count = Arc::new(AtomicUsize::new(0));
+    let script = Arc::new(script);
+
+    let counter = Arc::clone(&count);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let (counter, script) = (Arc::clone(&counter), Arc::clone(&script));
+            thread::spawn(move || serve(stream, &counter, &*script));
+        }
+    });
+
+    (port, count)
+}
+
+fn serve(mut stream: TcpStream, counter: &AtomicUsize, script: &dyn Fn(usize) -> Action) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    loop {
+        // Read one request head; these tests send no bodies
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                return;
+            }
+            if line == "\r\n" {
+                break;
+            }
+        }
+
+        match script(counter.fetch_add(1, Ordering::SeqCst)) {
+            Action::Reply(response) => {
+                if stream.write_all(response.as_bytes()).is_err() {
+                    return;
+                }
+            }
+            Action::Hang => thread::sleep(Duration::from_secs(5)),
+            Action::Drip(response, pause) => {
+                for byte in response.as_bytes() {
+                    thread::sleep(pause);
+                    if stream.write_all(&[*byte]).is_err() {
+                        return;
+                    }
+                }
+            }
+            Action::Drop => return,
+        }
+    }
+}
+
+fn requests(count: &AtomicUsize) -> usize {
+    count.load(Ordering::SeqCst)
+}
+
+fn url(port: u16, target: &str) -> Url {
+    Url::parse(&format!("http://127.0.0.1:{port}{target}")).unwrap()
+}
+
+#[test]
+fn read_timeout_stops_waiting_for_a_silent_server() {
+    let (port, _) = start_server(|_| Action::Hang);
+    let client = Client::new().read_timeout(Duration::from_millis(200));
+
+    let start = Instant::now();
+    let result = client.get(&format!("http://127.0.0.1:{port}/"));
+
+    assert!(matches!(result, Err(Error::TimedOut)));
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn slow_but_steady_response_is_fine_within_the_read_timeout() {
+    let (port, _) = start_server(|_| Action::Drip(OK, Duration::from_millis(5)));
+    let client = Client::new().read_timeout(Duration::from_millis(500));
+
+    assert_eq!(
+        client
+            .get(&format!("http://127.0.0.1:{port}/"))
+            .unwrap()
+            .text(),
+        "ok"
+    );
+}
+
+#[test]
+fn total_timeout_cuts_off_a_drip_feed() {
+    // Every byte arrives well within the read timeout, but the whole takes far too long
+    let (port, _) = start_server(|_| Action::Drip(OK, Duration::from_millis(50)));
+    let client = Client::new()
+        .read_timeout(Duration::from_secs(1))
+        .timeout(Duration::from_millis(300));
+
+    let start = Instant::now();
+    let result = client.get(&format!("http://127.0.0.1:{port}/"));
+
+    assert!(matches!(result, Err(Error::TimedOut)));
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn total_timeout_covers_redirects() {
+    let (port, _) = start_server(|_| {
+        Action::Drip(
+            "HTTP/1.1 302 Found\r\nLocation: /next\r\nContent-Length: 0\r\n\r\n",
+            Duration::from_millis(1),
+        )
+    });
+    let client = Client::new().timeout(Duration::from_millis(300));
+
+    let start = Instant::now();
+    let result = client.get(&format!("http://127.0.0.1:{port}/"));
+
+    // Each hop is quick, but not quick enough for all ten
+    assert!(matches!(result, Err(Error::TimedOut)));
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn connect_timeout_gives_up_on_an_unreachable_host() {
+    // A non-routable address: connecting either hangs until the timeout or fails outright
+    let client = Client::new().connect_timeout(Duration::from_millis(200));
+
+    let start = Instant::now();
+    let result = client.get("http://10.255.255.1/");
+
+    assert!(matches!(result, Err(Error::TimedOut | Error::Io(_))));
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn idempotent_requests_are_retried_with_backoff() {
+    let (port, count) = start_server(|n| match n {
+        0 => Action::Drop,
+        1 => Action::Reply(UNAVAILABLE),
+        _ => Action::Reply(OK),
+    });
+    let client = Client::new()
+        .retries(3)
+        .retry_backoff(Duration::from_millis(50));
+
+    let start = Instant::now();
+    let response = client.get(&format!("http://127.0.0.1:{port}/")).unwrap();
+
+    assert_eq!(response.text(), "ok");
+    assert_eq!(requests(&count), 3);
+    // Two pauses: 50ms, then 100ms
+    assert!(start.elapsed() >= Duration::from_millis(150));
+}
+
+#[test]
+fn retries_give_up_with_the_last_response() {
+    let (port, count) = start_server(|_| Action::Reply(UNAVAILABLE));
+    let client = Client::new()
+        .retries(2)
+        .retry_backoff(Duration::from_millis(1));
+
+    let response = client.get(&format!("http://127.0.0.1:{port}/")).unwrap();
+
+    assert_eq!(response.status, 503);
+    assert_eq!(requests(&count), 3);
+}
+
+#[test]
+fn timed_out_request_is_retried() {
+    let (port, count) = start_server(|n| match n {
+        0 => Action::Hang,
+        _ => Action::Reply(OK),
+    });
+    let client = Client::new()
+        .read_timeout(Duration::from_millis(100))
+        .retries(1)
+        .retry_backoff(Duration::from_millis(1));
+
+    assert_eq!(
+        client
+            .get(&format!("http://127.0.0.1:{port}/"))
+            .unwrap()
+            .text(),
+        "ok"
+    );
+    assert_eq!(requests(&count), 2);
+}
+
+#[test]
+fn post_is_never_retried() {
+    let (port, count) = start_server(|_| Action::Reply(UNAVAILABLE));
+    let client = Client::new()
+        .retries(3)
+        .retry_backoff(Duration::from_millis(1));
+
+    let response = client.send(&Request::new("POST", url(port, "/"))).unwrap();
+
+    assert_eq!(response.status, 503);
+    assert_eq!(requests(&count), 1);
+}
+
+#[test]
+fn no_retries_by_default() {
+    let (port, count) = start_server(|_| Action::Drop);
+
+    let result = Client::new().get(&format!("http://127.0.0.1:{port}/"));
+
+    assert!(matches!(result, Err(Error::Io(_))));
+    assert_eq!(requests(&count), 1);
+}
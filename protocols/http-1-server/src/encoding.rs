@@ -0,0 +1,238 @@
+//! Content negotiation on `Accept-Encoding`, and compressing bodies on the fly.
+
+use std::io::Read;
+
+use brotli::CompressorReader;
+use flate2::{Compression, read::GzEncoder};
+use mime_guess::{Mime, mime};
+
+/// Files smaller than this are sent as they are; compressing them saves next to nothing.
+pub const MIN_COMPRESS_SIZE: u64 = 1024;
+
+/// Brotli quality for on-the-fly compression, well below the slow maximum of 11.
+const BROTLI_QUALITY: u32 = 5;
+
+/// Brotli window size, as a power of two.
+const BROTLI_WINDOW: u32 = 22;
+
+/// Buffer size for the brotli encoder.
+const BROTLI_BUFFER: usize = 8 * 1024;
+
+/// A content coding this server can send.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+    Identity,
+}
+
+impl Encoding {
+    /// Every coding, in the order preferred when the client rates several equally.
+    const ALL: [Encoding; 3] = [Encoding::Brotli, Encoding::Gzip, Encoding::Identity];
+
+    /// The name used in `Accept-Encoding` and `Content-Encoding`.
+    pub fn token(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+            Encoding::Identity => "identity",
+        }
+    }
+
+    /// The extension of a precompressed sibling file, as in `app.js.br`.
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            Encoding::Brotli => Some("br"),
+            Encoding::Gzip => Some("gz"),
+            Encoding::Identity => None,
+        }
+    }
+
+    /// Wraps `reader` so it yields its bytes in this coding.
+    pub fn encode(self, reader: impl Read + Send + 'static) -> Box<dyn Read + Send> {
+        match self {
+            Encoding::Brotli => Box::new(CompressorReader::new(
+                reader,
+                BROTLI_BUFFER,
+                BROTLI_QUALITY,
+                BROTLI_WINDOW,
+            )),
+            Encoding::Gzip => Box::new(GzEncoder::new(reader, Compression::default())),
+            Encoding::Identity => Box::new(reader),
+        }
+    }
+
+    fn from_token(token: &str) -> Option<Self> {
+        match token.to_ascii_lowercase().as_str() {
+            "br" => Some(Encoding::Brotli),
+            "gzip" | "x-gzip" => Some(Encoding::Gzip),
+            "identity" => Some(Encoding::Identity),
+            _ => None,
+        }
+    }
+}
+
+/// The codings a client accepts according to its `Accept-Encoding` header, best first.
+///
+/// `q=0` refuses a coding and `*` stands for every coding not named. Identity is acceptable
+/// unless refused, but ranks below any coding the client asked for. Without the header
+/// only identity is offered.
+pub fn negotiate(accept_encoding: Option<&str>) -> Vec<Encoding> {
+    let Some(header) = accept_encoding else {
+        return vec![Encoding::Identity];
+    };
+
+    let mut named: Vec<(Encoding, f32)> = Vec::new();
+    let mut wildcard = None;
+    for item in header.split(',') {
+        let mut params = item.split(';');
+        let token = params.next().unwrap_or("").trim();
+        let q = params
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+            .map_or(Some(1.0), |(_, value)| value.trim().parse::<f32>().ok());
+        // An unparseable weight makes the whole item meaningless
+        let Some(q) = q.filter(|q| (0.0..=1.0).contains(q)) else {
+            continue;
+        };
+
+        if token == "*" {
+            wildcard = Some(q);
+        } else if let Some(encoding) = Encoding::from_token(token) {
+            named.push((encoding, q));
+        }
+    }
+
+    let mut ranked: Vec<(Encoding, f32)> = Encoding::ALL
+        .iter()
+        .filter_map(|&encoding| {
+            let q = named
+                .iter()
+                .find(|(e, _)| *e == encoding)
+                .map(|&(_, q)| q)
+                .or(wildcard)
+                .or((encoding == Encoding::Identity).then_some(0.001))?;
+            (q > 0.0).then_some((encoding, q))
+        })
+        .collect();
+
+    // Stable, so equally rated codings keep the server's order
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked.into_iter().map(|(encoding, _)| encoding).collect()
+}
+
+/// Whether a body of this type is worth compressing: text, and structured text formats.
+pub fn is_compressible(mime: &Mime) -> bool {
+    if mime.type_() == mime::TEXT {
+        return true;
+    }
+    if mime
+        .suffix()
+        .is_some_and(|suffix| suffix == mime::JSON || suffix == mime::XML)
+    {
+        return true;
+    }
+    mime.type_() == mime::APPLICATION
+        && matches!(
+            mime.subtype().as_str(),
+            "json" | "javascript" | "x-javascript" | "ecmascript" | "xml" | "wasm"
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    use Encoding::{Brotli, Gzip, Identity};
+
+    #[test]
+    fn no_header_means_identity() {
+        assert_eq!(negotiate(None), [Identity]);
+        assert_eq!(negotiate(Some("")), [Identity]);
+    }
+
+    #[test]
+    fn equal_weights_prefer_brotli() {
+        assert_eq!(
+            negotiate(Some("gzip, deflate, br")),
+            [Brotli, Gzip, Identity]
+        );
+    }
+
+    #[test]
+    fn weights_order_codings() {
+        assert_eq!(
+            negotiate(Some("br;q=0.5, gzip;q=0.9")),
+            [Gzip, Brotli, Identity]
+        );
+        assert_eq!(negotiate(Some("GZIP;Q=1")), [Gzip, Identity]);
+    }
+
+    #[test]
+    fn zero_weight_refuses() {
+        assert_eq!(negotiate(Some("br;q=0, gzip")), [Gzip, Identity]);
+        assert_eq!(negotiate(Some("gzip, identity;q=0")), [Gzip]);
+        assert_eq!(negotiate(Some("*;q=0")), []);
+    }
+
+    #[test]
+    fn wildcard_covers_unnamed_codings() {
+        assert_eq!(negotiate(Some("*")), [Brotli, Gzip, Identity]);
+        assert_eq!(
+            negotiate(Some("gzip;q=0.2, *;q=0.5")),
+            [Brotli, Identity, Gzip]
+        );
+    }
+
+    #[test]
+    fn bad_weights_are_ignored() {
+        assert_eq!(negotiate(Some("br;q=high, gzip;q=2")), [Identity]);
+    }
+
+    #[test]
+    fn compressible_types() {
+        for mime in [
+            "text/html",
+            "text/css",
+            "text/javascript",
+            "application/json",
+            "application/ld+json",
+            "image/svg+xml",
+            "application/wasm",
+        ] {
+            assert!(is_compressible(&mime.parse().unwrap()), "{mime}");
+        }
+        for mime in ["image/png", "application/octet-stream", "application/zip"] {
+            assert!(!is_compressible(&mime.parse().unwrap()), "{mime}");
+        }
+    }
+
+    #[test]
+    fn encoders_round_trip() {
+        let text = "compress me ".repeat(200);
+
+        let mut gzip = Vec::new();
+        Gzip.encode(io::Cursor::new(text.clone()))
+            .read_to_end(&mut gzip)
+            .unwrap();
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(&gzip[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, text);
+        assert!(gzip.len() < text.len());
+
+        let mut br = Vec::new();
+        Brotli
+            .encode(io::Cursor::new(text.clone()))
+            .read_to_end(&mut br)
+            .unwrap();
+        let mut decoded = String::new();
+        brotli::Decompressor::new(&br[..], 4096)
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, text);
+        assert!(br.len() < text.len());
+    }
+}
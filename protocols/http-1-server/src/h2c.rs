@@ -0,0 +1,285 @@
+//! HTTP/2 over plaintext ("h2c"): switching to it when an HTTP/1.1 request asks with
+//! `Upgrade: h2c`, or right away when the client opens with the HTTP/2 preface.
+
+use std::{
+    io::{self, Cursor, Read, Write},
+    net::TcpStream,
+};
+
+use toy_http2::{PREFACE, Settings, settings};
+
+use crate::{Body, Request, Response, Server, request::RequestError};
+
+/// The whole response to an upgrade request; HTTP/2 frames follow it directly.
+pub(crate) const SWITCHING_PROTOCOLS: &[u8] =
+    b"HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\nUpgrade: h2c\r\n\r\n";
+
+/// Fields about the HTTP/1.1 connection or the upgrade itself, which HTTP/2 has no use for.
+const HOP_BY_HOP: [&str; 7] = [
+    "connection",
+    "host",
+    "http2-settings",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// The client's SETTINGS if `request` is a valid h2c upgrade (RFC 7540 section 3.2), which
+/// needs `Upgrade: h2c` and exactly one decodable HTTP2-Settings, both named in `Connection`.
+pub(crate) fn upgrade_settings(request: &Request) -> Option<Vec<(u16, u32)>> {
+    let upgrade = request.header("upgrade")?;
+    let connection = request.header("connection")?;
+    if !has_token(upgrade, "h2c")
+        || !has_token(connection, "upgrade")
+        || !has_token(connection, "http2-settings")
+    {
+        return None;
+    }
+
+    let mut values = request
+        .headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("http2-settings"));
+    let (Some((_, value)), None) = (values.next(), values.next()) else {
+        return None;
+    };
+    let parameters = settings::from_header(value)?;
+    // Settings the HTTP/2 side would refuse mean staying on HTTP/1.1
+    Settings::default().apply(&parameters).ok()?;
+    Some(parameters)
+}
+
+/// The same request as the HTTP/2 side sees it, arriving on stream 1.
+pub(crate) fn into_http2(request: Request) -> http_2_server::Request {
+    let authority = request.header("host").map(str::to_string);
+    let headers = request
+        .headers
+        .into_iter()
+        .map(|(name, value)| (name.to_ascii_lowercase(), value))
+        .filter(|(name, value)| {
+            !HOP_BY_HOP.contains(&name.as_str()) && (name != "te" || value == "trailers")
+        })
+        .collect();
+
+    http_2_server::Request {
+        method: request.method,
+        scheme: "http".to_string(),
+        path: request.target,
+        authority,
+        headers,
+        body: request.body,
+        trailers: Vec::new(),
+    }
+}
+
+/// Answers an HTTP/2 request from `server`'s routes and files, as the same request over
+/// HTTP/1.1 would be.
+pub(crate) fn respond(
+    server: &Server,
+    request: &http_2_server::Request,
+) -> http_2_server::Response {
+    from_http1(server.respond(&from_http2(request)))
+}
+
+/// The same request as the HTTP/1.1 side sees it, with the authority as its Host.
+fn from_http2(request: &http_2_server::Request) -> Request {
+    let mut headers = request.headers.clone();
+    if let Some(authority) = &request.authority {
+        headers.insert(0, ("host".to_string(), authority.clone()));
+    }
+
+    Request {
+        method: request.method.clone(),
+        target: request.path.clone(),
+        version: "HTTP/2".to_string(),
+        headers,
+        body: request.body.clone(),
+    }
+}
+
+/// The same response as HTTP/2 sends it, without the fields about the HTTP/1.1 connection.
+/// Files and streams are read a frame at a time as the client's windows allow.
+fn from_http1(response: Response) -> http_2_server::Response {
+    let mut converted = http_2_server::Response::new(response.status);
+    for (name, value) in response.headers {
+        if !HOP_BY_HOP.contains(&name.to_ascii_lowercase().as_str()) {
+            converted = converted.header(name, value);
+        }
+    }
+
+    // A HEAD response still tells the length of the body it leaves out, where that is known
+    if response.omit_body {
+        let len = match response.body {
+            Body::Bytes(bytes) => Some(bytes.len() as u64),
+            Body::File { len, .. } => Some(len),
+            Body::Empty | Body::Stream(_) => None,
+        };
+        return match len {
+            Some(len) => converted.header("content-length", len.to_string()),
+            None => converted,
+        };
+    }
+
+    match response.body {
+        Body::Empty => converted,
+        Body::Bytes(bytes) => converted.body(bytes),
+        Body::File { file, len } => converted.stream(file.take(len)),
+        Body::Stream(reader) => converted.stream(reader),
+    }
+}
+
+/// Reads the client's first bytes and tells whether they open the HTTP/2 preface.
+///
+/// No HTTP/1.1 request starts like the preface's `PRI ` does, so four bytes settle it. Each
+/// read waits no longer than the connection's read timeout; a client that sends nothing in
+/// that time is closed like an idle one, and one that stalls part way is left to the
+/// HTTP/1.1 side to time out.
+pub(crate) fn read_opening(stream: &TcpStream) -> Result<(Vec<u8>, bool), RequestError> {
+    let mut buf = vec![0u8; 4096];
+    let mut len = 0;
+    while len < 4 {
+        let n = match (&mut &*stream).read(&mut buf[len..]) {
+            Ok(0) if len == 0 => return Err(RequestError::Closed),
+            Err(e) if len == 0 => {
+                return Err(match RequestError::from(e) {
+                    RequestError::TimedOut => RequestError::Idle,
+                    e => e,
+                });
+            }
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        len += n;
+        let compared = len.min(PREFACE.len());
+        if buf[..compared] != PREFACE[..compared] {
+            break;
+        }
+    }
+    buf.truncate(len);
+    let preface = len >= 4 && buf[..4] == PREFACE[..4];
+    Ok((buf, preface))
+}
+
+/// A connection whose first bytes were already read, handing them out again before the rest.
+pub(crate) struct Replay<'a> {
+    opening: Cursor<Vec<u8>>,
+    stream: &'a TcpStream,
+}
+
+impl<'a> Replay<'a> {
+    pub(crate) fn new(opening: Vec<u8>, stream: &'a TcpStream) -> Self {
+        Replay {
+            opening: Cursor::new(opening),
+            stream,
+        }
+    }
+
+    /// The opening bytes not read yet.
+    pub(crate) fn unread(&self) -> &[u8] {
+        let position = self.opening.position() as usize;
+        &self.opening.get_ref()[position..]
+    }
+}
+
+impl Read for Replay<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.unread().is_empty() {
+            (&mut &*self.stream).read(buf)
+        } else {
+            self.opening.read(buf)
+        }
+    }
+}
+
+impl Write for Replay<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&mut &*self.stream).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&mut &*self.stream).flush()
+    }
+}
+
+/// Whether the comma-separated header `value` contains `token`, ignoring case.
+fn has_token(value: &str, token: &str) -> bool {
+    value
+        .split(',')
+        .any(|item| item.trim().eq_ignore_ascii_case(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(headers: &[(&str, &str)]) -> Request {
+        Request {
+            method: "GET".to_string(),
+            target: "/a?b".to_string(),
+            version: "HTTP/1.1".to_string(),
+            headers: headers
+                .iter()
+                .map(|&(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+            body: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn upgrades_need_every_part() {
+        let valid = [
+            ("Host", "example.com"),
+            ("Connection", "Upgrade, HTTP2-Settings"),
+            ("Upgrade", "h2c"),
+            ("HTTP2-Settings", "AAMAAABk"),
+        ];
+        assert_eq!(
+            upgrade_settings(&request(&valid)),
+            Some(vec![(settings::MAX_CONCURRENT_STREAMS, 100)])
+        );
+
+        for missing in 1..valid.len() {
+            let mut headers = valid.to_vec();
+            headers.remove(missing);
+            assert_eq!(upgrade_settings(&request(&headers)), None, "{missing}");
+        }
+
+        for (name, value) in [
+            ("Upgrade", "websocket"),
+            ("Connection", "Upgrade"),
+            ("HTTP2-Settings", "not base64!"),
+            // SETTINGS_ENABLE_PUSH of 2
+            ("HTTP2-Settings", "AAIAAAAC"),
+        ] {
+            let mut headers = valid.to_vec();
+            headers.retain(|(n, _)| *n != name);
+            headers.push((name, value));
+            assert_eq!(upgrade_settings(&request(&headers)), None, "{value}");
+        }
+
+        let mut twice = valid.to_vec();
+        twice.push(("HTTP2-Settings", ""));
+        assert_eq!(upgrade_settings(&request(&twice)), None);
+    }
+
+    #[test]
+    fn converted_requests_drop_connection_fields() {
+        let converted = into_http2(request(&[
+            ("Host", "example.com"),
+            ("Connection", "Upgrade, HTTP2-Settings"),
+            ("Upgrade", "h2c"),
+            ("HTTP2-Settings", ""),
+            ("Accept", "*/*"),
+            ("TE", "gzip"),
+        ]));
+
+        assert_eq!(converted.path, "/a?b");
+        assert_eq!(from_http2(&converted).header("host"), Some("example.com"));
+        assert_eq!(converted.authority.as_deref(), Some("example.com"));
+        assert_eq!(
+            converted.headers,
+            [("accept".to_string(), "*/*".to_string())]
+        );
+    }
+}
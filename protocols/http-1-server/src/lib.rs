@@ -0,0 +1,362 @@
+use std::{
+    fs,
+    io::{self, BufReader, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use encoding::Encoding;
+use http_2_server::ConnectionOptions;
+use mime_guess::from_path;
+
+pub mod encoding;
+mod h2c;
+pub mod request;
+pub mod resolve;
+pub mod response;
+pub mod router;
+#[cfg(feature = "tls")]
+pub mod tls;
+
+pub use request::Request;
+use resolve::ResolveError;
+pub use response::{Body, Response, reason_phrase};
+pub use router::Handler;
+use router::Route;
+
+/// How long an idle keep-alive connection may wait for its next request.
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a closing connection waits for the client to stop sending.
+const LINGER_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Most unread request bytes discarded while closing a connection.
+const LINGER_LIMIT: u64 = 64 * 1024;
+
+/// A static file server speaking HTTP/1.1 over any `Read + Write` stream.
+pub struct Server {
+    root: PathBuf,
+    routes: Vec<Route>,
+    /// Options for plaintext connections that switch to HTTP/2, if they may
+    http2: Option<ConnectionOptions>,
+}
+
+/// How a run of HTTP/1.1 requests on one connection ended.
+enum Ending {
+    /// The connection is done, for the reason given.
+    Closed(String),
+    /// The last request asked to continue in HTTP/2, with these SETTINGS.
+    Upgrade(Request, Vec<(u16, u32)>),
+}
+
+impl Server {
+    /// Creates a server that serves files below `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            routes: Vec::new(),
+            http2: None,
+        }
+    }
+
+    /// Sends requests with `method` whose path matches `pattern` to `handler`.
+    ///
+    /// A pattern ending in `*` matches every path with that prefix. Routes are tried in
+    /// the order they were added; GET requests no route claims fall back to static files.
+    pub fn route(mut self, method: &str, pattern: &str, handler: Handler) -> Self {
+        self.routes.push(Route::new(method, pattern, handler));
+        self
+    }
+
+    /// Lets plaintext connections switch to HTTP/2, whether by an `Upgrade: h2c` request or
+    /// by opening with the HTTP/2 preface, and answers them from the same routes and files
+    /// with `options`.
+    ///
+    /// Unless `options` PING the client, HTTP/2 connections close after the same idle
+    /// timeout as HTTP/1.1 ones.
+    pub fn http2(mut self, options: ConnectionOptions) -> Self {
+        self.http2 = Some(options);
+        self
+    }
+
+    /// Accepts plaintext connections forever, one thread per connection.
+    pub fn serve(self: Arc<Self>, listener: TcpListener) -> io::Result<()> {
+        for stream in listener.incoming().flatten() {
+            let server = Arc::clone(&self);
+            thread::spawn(move || match prepare(&stream) {
+                Ok(peer) => {
+                    server.handle_tcp(&stream, peer);
+                    linger(&stream);
+                }
+                Err(e) => eprintln!("Dropping connection: {e}"),
+            });
+        }
+        Ok(())
+    }
+
+    /// Serves requests from `stream` until the client closes or asks to.
+    ///
+    /// Requests that cannot be read are answered with an error status where one applies and
+    /// end the connection; the reason it ended is logged either way.
+    pub fn handle_connection<S: Read + Write>(&self, stream: &mut S, peer: SocketAddr) {
+        let mut reader = BufReader::new(stream);
+        match self.serve_requests(&mut reader, false) {
+            Ending::Closed(reason) => println!("Connection closed: {peer} ({reason})"),
+            Ending::Upgrade(..) => unreachable!("upgrades are only offered on TCP"),
+        }
+    }
+
+    /// Serves a plaintext connection, in HTTP/2 from the start or after an upgrade if the
+    /// client wants it and HTTP/2 is enabled.
+    fn handle_tcp(self: &Arc<Self>, stream: &TcpStream, peer: SocketAddr) {
+        let Some(options) = &self.http2 else {
+            return self.handle_connection(&mut &*stream, peer);
+        };
+        let server = Arc::clone(self);
+        let http2 = http_2_server::Server::new(move |request| h2c::respond(&server, request))
+            .options(options.clone());
+        // Connections the server PINGs stay open between requests for as long as they answer
+        let keep_open = || {
+            if options.pings() {
+                let _ = stream.set_read_timeout(None);
+            }
+        };
+
+        let (opening, preface) = match h2c::read_opening(stream) {
+            Ok(opening) => opening,
+            Err(e) => return println!("Connection closed: {peer} ({e})"),
+        };
+        if preface {
+            keep_open();
+            http2.handle_buffered_connection(stream, &opening);
+            return println!("Connection closed: {peer} (HTTP/2)");
+        }
+
+        let mut reader = BufReader::new(h2c::Replay::new(opening, stream));
+        let (request, settings) = match self.serve_requests(&mut reader, true) {
+            Ending::Closed(reason) => return println!("Connection closed: {peer} ({reason})"),
+            Ending::Upgrade(request, settings) => (request, settings),
+        };
+        // The client may already have sent the preface after its request
+        let mut buffered = reader.buffer().to_vec();
+        buffered.extend_from_slice(reader.get_ref().unread());
+        if let Err(e) = reader.get_mut().write_all(h2c::SWITCHING_PROTOCOLS) {
+            return println!("Connection closed: {peer} (write failed: {e})");
+        }
+
+        keep_open();
+        let upgrade = http_2_server::Upgrade {
+            settings,
+            request: h2c::into_http2(request),
+            buffered,
+        };
+        http2.upgrade_connection(stream, upgrade);
+        println!("Connection closed: {peer} (HTTP/2 after upgrade)");
+    }
+
+    /// Answers requests until the connection ends, or until one asks to upgrade to h2c
+    /// while `upgrades` are offered.
+    fn serve_requests<S: Read + Write>(&self, reader: &mut BufReader<S>, upgrades: bool) -> Ending {
+        let reason = loop {
+            let request = match request::read_request(reader) {
+                Ok(request) => request,
+                Err(e) => {
+                    if let Some(status) = e.status() {
+                        // The connection is dropped next, so a failed write changes nothing
+                        let _ = error_response(status)
+                            .header("Connection", "close")
+                            .write_to(reader.get_mut());
+                    }
+                    break e.to_string();
+                }
+            };
+
+            if upgrades && let Some(settings) = h2c::upgrade_settings(&request) {
+                return Ending::Upgrade(request, settings);
+            }
+
+            // A panicking handler costs this request a 500, not the whole connection thread
+            let response = panic::catch_unwind(AssertUnwindSafe(|| self.respond(&request)))
+                .unwrap_or_else(|_| error_response(500));
+
+            let keep_alive = !request.wants_close();
+            let connection = if keep_alive { "keep-alive" } else { "close" };
+            if let Err(e) = response
+                .header("Connection", connection)
+                .write_to(reader.get_mut())
+            {
+                break format!("write failed: {e}");
+            }
+
+            if !keep_alive {
+                break "closed on request".to_string();
+            }
+        };
+        Ending::Closed(reason)
+    }
+
+    /// Picks the response for a request: a matching route, a static file, or 405. HEAD is
+    /// answered as GET would be, without the body.
+    fn respond(&self, request: &Request) -> Response {
+        let response = self.respond_in_full(request);
+        if request.method == "HEAD" {
+            response.without_body()
+        } else {
+            response
+        }
+    }
+
+    fn respond_in_full(&self, request: &Request) -> Response {
+        // Routes see the path as the files do, so `/uploads/../index.html` is not an upload
+        let path = match route_path(request.path()) {
+            Ok(path) => path,
+            Err(e) => return error_response(e.status()),
+        };
+        let path = path.as_str();
+
+        if let Some(route) = self
+            .routes
+            .iter()
+            .find(|route| accepts(&route.method, &request.method) && route.matches(path))
+        {
+            return route.handler.call(request, &self.root);
+        }
+
+        if accepts("GET", &request.method) {
+            return self.serve_static(request);
+        }
+
+        // Everything else is refused, listing what this path does accept
+        let mut allowed = vec!["GET", "HEAD"];
+        for route in self.routes.iter().filter(|route| route.matches(path)) {
+            if !allowed.contains(&route.method.as_str()) {
+                allowed.push(&route.method);
+            }
+        }
+        error_response(405).header("Allow", allowed.join(", "))
+    }
+
+    /// Serves the file under the static root named by the request path, or the matching
+    /// error status.
+    ///
+    /// The body is encoded as the client's `Accept-Encoding` prefers: a precompressed
+    /// `.br` or `.gz` sibling of the file if there is one, otherwise the file compressed on
+    /// the fly when its type is worth it.
+    fn serve_static(&self, request: &Request) -> Response {
+        let path = match resolve::resolve(&self.root, request.path()) {
+            Ok(path) => path,
+            Err(e) => return error_response(e.status()),
+        };
+        let mime = from_path(&path).first_or_octet_stream();
+        let compressible = encoding::is_compressible(&mime);
+
+        let siblings: Vec<(Encoding, PathBuf)> = [Encoding::Brotli, Encoding::Gzip]
+            .into_iter()
+            .filter_map(|e| Some((e, resolve::sibling(&self.root, &path, e.extension()?)?)))
+            .collect();
+
+        let response = match encoded_file(
+            request.header("accept-encoding"),
+            &path,
+            compressible,
+            &siblings,
+        ) {
+            Ok(response) => response.header("Content-Type", mime.to_string()),
+            Err(e) => {
+                eprintln!("Serving {} failed: {e}", path.display());
+                return error_response(500);
+            }
+        };
+
+        // Caches must not hand one client's encoding to another
+        if compressible || !siblings.is_empty() {
+            response.header("Vary", "Accept-Encoding")
+        } else {
+            response
+        }
+    }
+}
+
+/// Whether something answering `method` answers a `requested` one; GET answers HEAD too.
+fn accepts(method: &str, requested: &str) -> bool {
+    method == requested || (method == "GET" && requested == "HEAD")
+}
+
+/// The request path decoded and normalised for matching against routes, keeping a trailing
+/// slash.
+fn route_path(target: &str) -> Result<String, ResolveError> {
+    let normalized = resolve::normalize(target)?;
+    let mut path = String::from("/");
+    for segment in normalized.iter() {
+        if path.len() > 1 {
+            path.push('/');
+        }
+        path.push_str(&segment.to_string_lossy());
+    }
+    if target.ends_with('/') && path.len() > 1 {
+        path.push('/');
+    }
+    Ok(path)
+}
+
+/// Opens the best encoding of `path` that the client's `Accept-Encoding` allows.
+fn encoded_file(
+    accept_encoding: Option<&str>,
+    path: &Path,
+    compressible: bool,
+    siblings: &[(Encoding, PathBuf)],
+) -> io::Result<Response> {
+    let file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+
+    for accepted in encoding::negotiate(accept_encoding) {
+        if accepted == Encoding::Identity {
+            break;
+        }
+        if let Some((_, sibling)) = siblings.iter().find(|(e, _)| *e == accepted) {
+            return Response::new(200)
+                .header("Content-Encoding", accepted.token())
+                .file(fs::File::open(sibling)?);
+        }
+        if compressible && len >= encoding::MIN_COMPRESS_SIZE {
+            return Ok(Response::new(200)
+                .header("Content-Encoding", accepted.token())
+                .stream(accepted.encode(file)));
+        }
+    }
+
+    // Identity, also when the client refused it but accepts nothing else we have
+    Response::new(200).file(file)
+}
+
+/// A plain-text response whose body is just the status line, e.g. `404 Not Found`.
+pub(crate) fn error_response(status: u16) -> Response {
+    Response::new(status)
+        .header("Content-Type", "text/plain")
+        .body(format!("{status} {}", reason_phrase(status)))
+}
+
+/// Closes our side of `stream` and discards what the client still sends, for a short while.
+///
+/// Closing a socket with unread input makes the kernel send a reset, which can destroy an
+/// error response the client has not read yet.
+pub(crate) fn linger(stream: &TcpStream) {
+    if stream.shutdown(Shutdown::Write).is_err()
+        || stream.set_read_timeout(Some(LINGER_TIMEOUT)).is_err()
+    {
+        return;
+    }
+    let _ = io::copy(&mut stream.take(LINGER_LIMIT), &mut io::sink());
+}
+
+/// Applies the keep-alive read timeout to a freshly accepted socket and returns its peer.
+///
+/// Fails if the client has already gone, in which case the socket should just be dropped.
+pub(crate) fn prepare(stream: &TcpStream) -> io::Result<SocketAddr> {
+    stream.set_read_timeout(Some(KEEP_ALIVE_TIMEOUT))?;
+    stream.peer_addr()
+}
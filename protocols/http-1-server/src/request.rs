@@ -0,0 +1,460 @@
+//! A parsed HTTP/1.1 request as handed to handlers, and reading one off the wire.
+
+use std::{
+    fmt,
+    io::{self, BufRead, Read},
+};
+
+/// Most bytes the request line and headers (or chunked trailers) may take together.
+pub const MAX_HEAD_SIZE: usize = 8 * 1024;
+
+/// Largest request body accepted, whether sized by `Content-Length` or chunked.
+pub const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// The request line, headers and fully read body of one request.
+pub struct Request {
+    pub method: String,
+    /// The request target exactly as sent, including any query string.
+    pub target: String,
+    /// The protocol version from the request line, such as `HTTP/1.1`.
+    pub version: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// The target without its query string or fragment, still percent-encoded.
+    pub fn path(&self) -> &str {
+        self.target.split(['?', '#']).next().unwrap_or("")
+    }
+
+    /// The query string without the leading `?`, if there is one.
+    pub fn query(&self) -> Option<&str> {
+        let without_fragment = self.target.split('#').next().unwrap_or("");
+        without_fragment.split_once('?').map(|(_, query)| query)
+    }
+
+    /// The value of the first header called `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Whether the connection should be closed after this request: the client asked for it,
+    /// or spoke HTTP/1.0 without asking for keep-alive.
+    pub fn wants_close(&self) -> bool {
+        let connection = |wanted: &str| {
+            self.headers.iter().any(|(name, value)| {
+                name.eq_ignore_ascii_case("connection")
+                    && value
+                        .split(',')
+                        .any(|option| option.trim().eq_ignore_ascii_case(wanted))
+            })
+        };
+        connection("close") || (self.version == "HTTP/1.0" && !connection("keep-alive"))
+    }
+}
+
+/// Why no request could be read from a connection.
+#[derive(Debug)]
+pub enum RequestError {
+    /// The client closed the connection between requests.
+    Closed,
+    /// The connection sat idle past the read timeout between requests.
+    Idle,
+    /// The client stalled part way through a request.
+    TimedOut,
+    /// The request line, a header or the body framing does not parse.
+    Malformed(&'static str),
+    /// The request line and headers exceed [`MAX_HEAD_SIZE`].
+    HeadTooLarge,
+    /// The body exceeds [`MAX_BODY_SIZE`].
+    BodyTooLarge,
+    /// An HTTP version other than 1.0 or 1.1.
+    UnsupportedVersion,
+    /// A transfer coding other than `chunked`.
+    UnsupportedEncoding,
+    /// The connection failed or was cut off part way through a request.
+    Io(io::Error),
+}
+
+impl RequestError {
+    /// The status to answer with, or `None` when the connection should just be closed.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            RequestError::Closed | RequestError::Idle | RequestError::Io(_) => None,
+            RequestError::TimedOut => Some(408),
+            RequestError::Malformed(_) => Some(400),
+            RequestError::HeadTooLarge => Some(431),
+            RequestError::BodyTooLarge => Some(413),
+            RequestError::UnsupportedVersion => Some(505),
+            RequestError::UnsupportedEncoding => Some(501),
+        }
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::Closed => write!(f, "closed by client"),
+            RequestError::Idle => write!(f, "idle timeout"),
+            RequestError::TimedOut => write!(f, "request timed out"),
+            RequestError::Malformed(what) => write!(f, "malformed request: {what}"),
+            RequestError::HeadTooLarge => write!(f, "request head too large"),
+            RequestError::BodyTooLarge => write!(f, "request body too large"),
+            RequestError::UnsupportedVersion => write!(f, "unsupported HTTP version"),
+            RequestError::UnsupportedEncoding => write!(f, "unsupported transfer encoding"),
+            RequestError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for RequestError {}
+
+impl From<io::Error> for RequestError {
+    fn from(e: io::Error) -> Self {
+        // Socket read timeouts surface as WouldBlock on Unix and TimedOut on Windows
+        match e.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => RequestError::TimedOut,
+            _ => RequestError::Io(e),
+        }
+    }
+}
+
+/// Reads one complete request, body included, from `reader`.
+pub fn read_request(reader: &mut impl BufRead) -> Result<Request, RequestError> {
+    let mut budget = MAX_HEAD_SIZE;
+
+    // Nothing at all before a close or timeout is the end of a keep-alive connection
+    match reader.fill_buf() {
+        Ok([]) => return Err(RequestError::Closed),
+        Ok(_) => {}
+        Err(e) => {
+            return Err(match RequestError::from(e) {
+                RequestError::TimedOut => RequestError::Idle,
+                e => e,
+            });
+        }
+    }
+
+    let request_line = read_line(reader, &mut budget)?.ok_or_else(truncated)?;
+    let (method, target, version) = parse_request_line(&request_line)?;
+    let headers = read_fields(reader, &mut budget)?;
+    let body = read_body(reader, &headers)?;
+
+    Ok(Request {
+        method,
+        target,
+        version,
+        headers,
+        body,
+    })
+}
+
+/// Splits `METHOD target HTTP/1.x` into its method, target and version.
+fn parse_request_line(line: &str) -> Result<(String, String, String), RequestError> {
+    let mut parts = line.split(' ');
+    let (Some(method), Some(target), Some(version), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(RequestError::Malformed("request line"));
+    };
+
+    if !is_token(method) {
+        return Err(RequestError::Malformed("method"));
+    }
+    if target.is_empty() || target.bytes().any(|b| b.is_ascii_control()) {
+        return Err(RequestError::Malformed("request target"));
+    }
+    match version {
+        "HTTP/1.1" | "HTTP/1.0" => {}
+        v if v.starts_with("HTTP/") => return Err(RequestError::UnsupportedVersion),
+        _ => return Err(RequestError::Malformed("HTTP version")),
+    }
+
+    Ok((method.to_string(), target.to_string(), version.to_string()))
+}
+
+/// Reads `name: value` lines up to the blank line that ends a header or trailer section.
+fn read_fields(
+    reader: &mut impl BufRead,
+    budget: &mut usize,
+) -> Result<Vec<(String, String)>, RequestError> {
+    let mut fields = Vec::new();
+    loop {
+        let line = read_line(reader, budget)?.ok_or_else(truncated)?;
+        if line.is_empty() {
+            return Ok(fields);
+        }
+        // Folded continuation lines are obsolete and a known smuggling vector
+        if line.starts_with([' ', '\t']) {
+            return Err(RequestError::Malformed("folded header"));
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(RequestError::Malformed("header without a colon"));
+        };
+        if !is_token(name) {
+            return Err(RequestError::Malformed("header name"));
+        }
+        fields.push((name.to_string(), value.trim().to_string()));
+    }
+}
+
+/// Reads the body the headers describe: chunked, `Content-Length` bytes, or none.
+fn read_body(
+    reader: &mut impl BufRead,
+    headers: &[(String, String)],
+) -> Result<Vec<u8>, RequestError> {
+    let codings: Vec<&str> = field_values(headers, "transfer-encoding")
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .collect();
+    if !codings.is_empty() {
+        // With both framings the two ends may disagree on where the body stops
+        if field_values(headers, "content-length").next().is_some() {
+            return Err(RequestError::Malformed(
+                "both Content-Length and Transfer-Encoding",
+            ));
+        }
+        return match codings[..] {
+            [coding] if coding.eq_ignore_ascii_case("chunked") => read_chunked(reader),
+            _ => Err(RequestError::UnsupportedEncoding),
+        };
+    }
+
+    // Repeated Content-Length headers are only acceptable if they all agree
+    let mut length = None;
+    for value in field_values(headers, "content-length").flat_map(|v| v.split(',')) {
+        let value = value.trim();
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(RequestError::Malformed("Content-Length"));
+        }
+        // Anything too long to parse is certainly too large
+        let parsed = value.parse::<usize>().unwrap_or(usize::MAX);
+        if length.is_some_and(|l| l != parsed) {
+            return Err(RequestError::Malformed("conflicting Content-Length"));
+        }
+        length = Some(parsed);
+    }
+
+    let length = length.unwrap_or(0);
+    if length > MAX_BODY_SIZE {
+        return Err(RequestError::BodyTooLarge);
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(body)
+}
+
+/// The values of every header called `name`, ignoring case.
+fn field_values<'a>(
+    headers: &'a [(String, String)],
+    name: &'a str,
+) -> impl Iterator<Item = &'a str> {
+    headers
+        .iter()
+        .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Reads a chunked body, discarding any chunk extensions and trailers.
+fn read_chunked(reader: &mut impl BufRead) -> Result<Vec<u8>, RequestError> {
+    let mut body = Vec::new();
+    loop {
+        let mut budget = MAX_HEAD_SIZE;
+        let line = read_line(reader, &mut budget)?.ok_or_else(truncated)?;
+        let size = line.split(';').next().unwrap_or("").trim();
+        if size.is_empty() || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(RequestError::Malformed("chunk size"));
+        }
+        let size = usize::from_str_radix(size, 16).unwrap_or(usize::MAX);
+
+        if size == 0 {
+            read_fields(reader, &mut budget)?;
+            return Ok(body);
+        }
+        if size > MAX_BODY_SIZE - body.len() {
+            return Err(RequestError::BodyTooLarge);
+        }
+
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+
+        let mut crlf = [0; 2];
+        reader.read_exact(&mut crlf)?;
+        if &crlf != b"\r\n" {
+            return Err(RequestError::Malformed("chunk not followed by CRLF"));
+        }
+    }
+}
+
+/// Reads one line without its line ending, or `None` at a clean end of stream.
+///
+/// Lines count against `budget` and must be UTF-8; a bare `\n` is accepted as a line ending.
+fn read_line(
+    reader: &mut impl BufRead,
+    budget: &mut usize,
+) -> Result<Option<String>, RequestError> {
+    let mut line = Vec::new();
+    let read = reader.take(*budget as u64).read_until(b'\n', &mut line)?;
+    *budget -= read;
+
+    if read == 0 {
+        return if *budget == 0 {
+            Err(RequestError::HeadTooLarge)
+        } else {
+            Ok(None)
+        };
+    }
+    if !line.ends_with(b"\n") {
+        return Err(if *budget == 0 {
+            RequestError::HeadTooLarge
+        } else {
+            truncated()
+        });
+    }
+
+    line.pop();
+    if line.ends_with(b"\r") {
+        line.pop();
+    }
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|_| RequestError::Malformed("not UTF-8"))
+}
+
+/// The error for a request cut off by the end of the stream.
+fn truncated() -> RequestError {
+    RequestError::Io(io::ErrorKind::UnexpectedEof.into())
+}
+
+/// Whether `s` is a non-empty RFC 9110 token, as methods and header names must be.
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(raw: &str) -> Result<Request, RequestError> {
+        read_request(&mut io::Cursor::new(raw.as_bytes()))
+    }
+
+    fn status(raw: &str) -> Option<u16> {
+        parse(raw).err().and_then(|e| e.status())
+    }
+
+    #[test]
+    fn reads_content_length_body() {
+        let request =
+            parse("POST /a?b=c HTTP/1.1\r\nHost: x\r\nContent-Length: 5\r\n\r\nhello").unwrap();
+
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.target, "/a?b=c");
+        assert_eq!(request.header("host"), Some("x"));
+        assert_eq!(request.body, b"hello");
+    }
+
+    #[test]
+    fn reads_chunked_body_with_extensions_and_trailers() {
+        let request = parse(
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+             5;name=value\r\nhello\r\n1\r\n!\r\n0\r\nX-Trailer: yes\r\n\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(request.body, b"hello!");
+    }
+
+    #[test]
+    fn empty_input_is_a_clean_close() {
+        assert!(matches!(parse(""), Err(RequestError::Closed)));
+    }
+
+    #[test]
+    fn truncated_requests_are_io_errors() {
+        for raw in [
+            "GET / HTTP/1.1",
+            "GET / HTTP/1.1\r\nHost: x\r\n",
+            "POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhel",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n",
+        ] {
+            assert!(matches!(parse(raw), Err(RequestError::Io(_))), "{raw:?}");
+        }
+    }
+
+    #[test]
+    fn malformed_requests_are_bad_requests() {
+        for raw in [
+            "GET\r\n\r\n",
+            "GET /\r\n\r\n",
+            "GET / HTTP/1.1 extra\r\n\r\n",
+            "G(T / HTTP/1.1\r\n\r\n",
+            "GET / FTP/1.0\r\n\r\n",
+            "GET / HTTP/1.1\r\nno colon here\r\n\r\n",
+            "GET / HTTP/1.1\r\nBad Name: x\r\n\r\n",
+            "GET / HTTP/1.1\r\nA: b\r\n  folded\r\n\r\n",
+            "GET / HTTP/1.1\r\nContent-Length: abc\r\n\r\n",
+            "GET / HTTP/1.1\r\nContent-Length: -1\r\n\r\n",
+            "GET / HTTP/1.1\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\nab",
+            "POST / HTTP/1.1\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nabcd\r\n",
+            "GET /\u{0} HTTP/1.1\r\n\r\n",
+        ] {
+            assert_eq!(status(raw), Some(400), "{raw:?}");
+        }
+        assert_eq!(
+            read_request(&mut io::Cursor::new(b"GET /\xff HTTP/1.1\r\n\r\n"))
+                .err()
+                .and_then(|e| e.status()),
+            Some(400)
+        );
+    }
+
+    #[test]
+    fn agreeing_content_lengths_are_accepted() {
+        let request = parse("POST / HTTP/1.1\r\nContent-Length: 2, 2\r\n\r\nab").unwrap();
+        assert_eq!(request.body, b"ab");
+    }
+
+    #[test]
+    fn limits_and_versions_map_to_statuses() {
+        let big_header = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(MAX_HEAD_SIZE));
+        assert_eq!(status(&big_header), Some(431));
+        assert_eq!(
+            status("POST / HTTP/1.1\r\nContent-Length: 99999999999999999999999\r\n\r\n"),
+            Some(413)
+        );
+        assert_eq!(
+            status("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nFFFFFFFFFF\r\n"),
+            Some(413)
+        );
+        assert_eq!(status("GET / HTTP/2.0\r\n\r\n"), Some(505));
+        assert_eq!(
+            status("POST / HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n"),
+            Some(501)
+        );
+    }
+
+    #[test]
+    fn connection_close_is_detected() {
+        let request = parse("GET / HTTP/1.1\r\nConnection: keep-alive, Close\r\n\r\n").unwrap();
+        assert!(request.wants_close());
+        assert!(!parse("GET / HTTP/1.1\r\n\r\n").unwrap().wants_close());
+    }
+
+    #[test]
+    fn http_1_0_closes_unless_keep_alive_is_asked_for() {
+        assert!(parse("GET / HTTP/1.0\r\n\r\n").unwrap().wants_close());
+        let request = parse("GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n").unwrap();
+        assert!(!request.wants_close());
+    }
+}
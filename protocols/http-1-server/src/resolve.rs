@@ -0,0 +1,151 @@
+//! Mapping request paths onto files below the static root without ever leaving it.
+//!
+//! Paths are percent-decoded once, normalised segment by segment (so `..` can never climb
+//! above the root), and the result is canonicalised and checked against the canonical root,
+//! which also catches symlinks that point outside it.
+
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use percent_encoding::percent_decode_str;
+
+/// File served when a directory is requested.
+const INDEX_FILE: &str = "index.html";
+
+/// Why a request path could not be mapped to a file.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ResolveError {
+    /// Not valid percent-encoded UTF-8, or contains a NUL byte or backslash.
+    BadRequest,
+    /// Climbs above the root or resolves (through a symlink) to somewhere outside it.
+    Forbidden,
+    /// Nothing exists at the resolved location.
+    NotFound,
+}
+
+impl ResolveError {
+    /// The HTTP status this error is answered with.
+    pub fn status(&self) -> u16 {
+        match self {
+            ResolveError::BadRequest => 400,
+            ResolveError::Forbidden => 403,
+            ResolveError::NotFound => 404,
+        }
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::BadRequest => write!(f, "malformed path"),
+            ResolveError::Forbidden => write!(f, "path leaves the static root"),
+            ResolveError::NotFound => write!(f, "no such file"),
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// Turns a request target into a relative path with no `.` or `..` segments.
+///
+/// The query string and fragment are dropped, the rest is percent-decoded exactly once,
+/// `.` segments and empty segments are skipped and `..` removes the previous segment.
+/// A `..` with nothing left to remove is refused rather than ignored.
+pub fn normalize(target: &str) -> Result<PathBuf, ResolveError> {
+    let path = target.split(['?', '#']).next().unwrap_or("");
+    let decoded = percent_decode_str(path)
+        .decode_utf8()
+        .map_err(|_| ResolveError::BadRequest)?;
+
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop().ok_or(ResolveError::Forbidden)?;
+            }
+            // Backslashes are separators on Windows and NUL truncates paths in C APIs
+            s if s.contains(['\\', '\0']) => return Err(ResolveError::BadRequest),
+            // A drive prefix like `C:` would make the join absolute on Windows
+            s if cfg!(windows) && s.contains(':') => return Err(ResolveError::BadRequest),
+            s => segments.push(s),
+        }
+    }
+
+    Ok(segments.iter().collect())
+}
+
+/// Finds the existing file under `root` that `target` names, serving `index.html` for directories.
+///
+/// The returned path is canonical and guaranteed to lie inside the canonical `root`.
+pub fn resolve(root: &Path, target: &str) -> Result<PathBuf, ResolveError> {
+    let root = canonicalize(root)?;
+    let mut path = canonicalize(&root.join(normalize(target)?))?;
+    ensure_inside(&root, &path)?;
+
+    if path.is_dir() {
+        path = canonicalize(&path.join(INDEX_FILE))?;
+        ensure_inside(&root, &path)?;
+    }
+
+    if !path.is_file() {
+        return Err(ResolveError::NotFound);
+    }
+    Ok(path)
+}
+
+/// Finds where under `root` a file named by `target` may be written.
+///
+/// The file itself need not exist, but whatever part of the path already does (including a
+/// symlink at the final name) must canonicalise to somewhere inside `root`.
+pub fn resolve_for_write(root: &Path, target: &str) -> Result<PathBuf, ResolveError> {
+    let relative = normalize(target)?;
+    if relative.as_os_str().is_empty() {
+        return Err(ResolveError::BadRequest);
+    }
+
+    let root = canonicalize(root)?;
+    let path = root.join(&relative);
+
+    // Walk up to the deepest component that exists and check where it really points
+    let mut existing = path.as_path();
+    while existing.symlink_metadata().is_err() {
+        existing = existing.parent().ok_or(ResolveError::Forbidden)?;
+    }
+    ensure_inside(&root, &canonicalize(existing)?)?;
+
+    Ok(path)
+}
+
+/// Finds the file next to `file` with `extension` appended to its name, as in `app.js.gz`.
+///
+/// `file` should come from [`resolve`]. The sibling must be a regular file that canonicalises
+/// to somewhere inside `root`, just like a file requested directly.
+pub fn sibling(root: &Path, file: &Path, extension: &str) -> Option<PathBuf> {
+    let mut name = file.file_name()?.to_owned();
+    name.push(".");
+    name.push(extension);
+
+    let root = canonicalize(root).ok()?;
+    let path = canonicalize(&file.with_file_name(name)).ok()?;
+    (ensure_inside(&root, &path).is_ok() && path.is_file()).then_some(path)
+}
+
+/// Resolves symlinks and `..`; a missing file (or a file used as a directory) is not found.
+fn canonicalize(path: &Path) -> Result<PathBuf, ResolveError> {
+    path.canonicalize().map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound | io::ErrorKind::NotADirectory => ResolveError::NotFound,
+        _ => ResolveError::Forbidden,
+    })
+}
+
+/// Both paths must already be canonical for the prefix check to mean anything.
+fn ensure_inside(root: &Path, path: &Path) -> Result<(), ResolveError> {
+    if path.starts_with(root) {
+        Ok(())
+    } else {
+        Err(ResolveError::Forbidden)
+    }
+}
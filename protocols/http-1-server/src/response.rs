@@ -0,0 +1,300 @@
+//! HTTP/1.1 responses: a small builder plus the wire encoding for each kind of body.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Read, Write},
+};
+
+/// Size of the buffer used when copying file and stream bodies to the socket.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Where the bytes of a response body come from.
+pub enum Body {
+    /// No body at all.
+    Empty,
+    /// A body held in memory.
+    Bytes(Vec<u8>),
+    /// A file sent with its length known up front, copied without buffering it whole.
+    File { file: File, len: u64 },
+    /// A body of unknown length, sent with chunked transfer encoding.
+    Stream(Box<dyn Read + Send>),
+}
+
+/// An HTTP/1.1 response built up with chained calls and written with [`Response::write_to`].
+pub struct Response {
+    pub(crate) status: u16,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: Body,
+    pub(crate) omit_body: bool,
+}
+
+impl Response {
+    /// Creates an empty response with the given status code.
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Body::Empty,
+            omit_body: false,
+        }
+    }
+
+    /// Adds a header. Framing headers (`Content-Length`, `Transfer-Encoding`) are set from the
+    /// body, so any given here are ignored.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let name = name.into();
+        if !is_framing(&name) {
+            self.headers.push((name, value.into()));
+        }
+        self
+    }
+
+    /// Uses `bytes` as the body.
+    pub fn body(mut self, bytes: impl Into<Vec<u8>>) -> Self {
+        self.body = Body::Bytes(bytes.into());
+        self
+    }
+
+    /// Uses the contents of `file` as the body, streamed from disk.
+    pub fn file(mut self, file: File) -> io::Result<Self> {
+        let len = file.metadata()?.len();
+        self.body = Body::File { file, len };
+        Ok(self)
+    }
+
+    /// Uses everything `reader` yields as the body, sent as chunks.
+    pub fn stream(mut self, reader: impl Read + Send + 'static) -> Self {
+        self.body = Body::Stream(Box::new(reader));
+        self
+    }
+
+    /// Keeps the headers, framing included, but leaves the body out, as an answer to HEAD.
+    pub fn without_body(mut self) -> Self {
+        self.omit_body = true;
+        self
+    }
+
+    /// The status code.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// The value of the first header called `name`, ignoring case.
+    pub fn header_value(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Writes the status line, headers and body to `out`.
+    pub fn write_to(self, out: &mut impl Write) -> io::Result<()> {
+        // Buffer so the head and a small body leave in one segment, not a write per piece
+        let out = &mut BufWriter::with_capacity(CHUNK_SIZE, out);
+
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\n",
+            self.status,
+            reason_phrase(self.status)
+        );
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        // These statuses never have content, so they are not framed either (RFC 9110 section 6.4.1)
+        let bodiless = matches!(self.status, 100..=199 | 204 | 304);
+        match &self.body {
+            _ if bodiless => {}
+            Body::Empty => head.push_str("Content-Length: 0\r\n"),
+            Body::Bytes(bytes) => head.push_str(&format!("Content-Length: {}\r\n", bytes.len())),
+            Body::File { len, .. } => head.push_str(&format!("Content-Length: {len}\r\n")),
+            Body::Stream(_) => head.push_str("Transfer-Encoding: chunked\r\n"),
+        }
+        head.push_str("\r\n");
+        out.write_all(head.as_bytes())?;
+
+        match self.body {
+            _ if bodiless || self.omit_body => {}
+            Body::Empty => {}
+            Body::Bytes(bytes) => out.write_all(&bytes)?,
+            Body::File { file, len } => {
+                // Copy by hand: io::copy into a BufWriter flushes the head on its own first
+                let copied = copy_buffered(&mut file.take(len), out)?;
+                if copied < len {
+                    // The file shrank after we announced its length; the response is unusable
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "file shorter than its Content-Length",
+                    ));
+                }
+            }
+            Body::Stream(mut reader) => write_chunked(&mut reader, out)?,
+        }
+
+        out.flush()
+    }
+}
+
+/// Whether `name` is one of the headers that frame the body, which the body itself decides.
+fn is_framing(name: &str) -> bool {
+    name.eq_ignore_ascii_case("content-length") || name.eq_ignore_ascii_case("transfer-encoding")
+}
+
+/// Copies `reader` to `out` through a fixed buffer, returning the number of bytes copied.
+fn copy_buffered(reader: &mut impl Read, out: &mut impl Write) -> io::Result<u64> {
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut copied = 0;
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok(copied),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        out.write_all(&buf[..n])?;
+        copied += n as u64;
+    }
+}
+
+/// Copies `reader` to `out` as chunks, ending with the zero-length last chunk.
+fn write_chunked(reader: &mut dyn Read, out: &mut impl Write) -> io::Result<()> {
+    let mut buf = vec![0; CHUNK_SIZE];
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        write!(out, "{n:X}\r\n")?;
+        out.write_all(&buf[..n])?;
+        out.write_all(b"\r\n")?;
+    }
+    out.write_all(b"0\r\n\r\n")
+}
+
+/// The standard reason phrase for the status codes this server sends.
+pub fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        411 => "Length Required",
+        413 => "Content Too Large",
+        414 => "URI Too Long",
+        415 => "Unsupported Media Type",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        505 => "HTTP Version Not Supported",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(response: Response) -> String {
+        let mut out = Vec::new();
+        response.write_to(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn bytes_body_sets_content_length() {
+        let response = Response::new(200)
+            .header("Content-Type", "text/plain")
+            .body("hello");
+
+        assert_eq!(
+            render(response),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nhello"
+        );
+    }
+
+    #[test]
+    fn empty_body_has_zero_length() {
+        assert_eq!(
+            render(Response::new(200)),
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn responses_without_content_are_not_framed() {
+        for (status, reason) in [
+            (100, "Continue"),
+            (204, "No Content"),
+            (304, "Not Modified"),
+        ] {
+            assert_eq!(
+                render(Response::new(status).body("ignored")),
+                format!("HTTP/1.1 {status} {reason}\r\n\r\n")
+            );
+        }
+    }
+
+    #[test]
+    fn framing_headers_come_from_the_body() {
+        let response = Response::new(200)
+            .header("Content-Length", "99")
+            .header("transfer-encoding", "chunked")
+            .body("hello");
+
+        assert_eq!(
+            render(response),
+            "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello"
+        );
+    }
+
+    #[test]
+    fn stream_body_is_chunked() {
+        // A reader that hands out its data in two pieces
+        let reader =
+            io::Cursor::new(b"Hello, ".to_vec()).chain(io::Cursor::new(b"chunks!".to_vec()));
+
+        assert_eq!(
+            render(Response::new(200).stream(reader)),
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n7\r\nHello, \r\n7\r\nchunks!\r\n0\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn file_body_uses_file_length() {
+        let path = std::env::temp_dir().join(format!("response-file-{}", std::process::id()));
+        std::fs::write(&path, vec![b'x'; 20_000]).unwrap();
+
+        let response = Response::new(200).file(File::open(&path).unwrap()).unwrap();
+        let rendered = render(response);
+
+        assert!(rendered.starts_with("HTTP/1.1 200 OK\r\nContent-Length: 20000\r\n\r\n"));
+        assert_eq!(
+            rendered.len() - rendered.find("\r\n\r\n").unwrap() - 4,
+            20_000
+        );
+    }
+
+    #[test]
+    fn without_body_keeps_the_framing() {
+        let response = Response::new(200).body("hello").without_body();
+
+        assert_eq!(
+            render(response),
+            "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n"
+        );
+    }
+}
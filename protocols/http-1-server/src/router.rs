@@ -0,0 +1,102 @@
+//! Routes that send requests for particular methods and paths to handlers.
+
+use std::{fs, path::Path};
+
+use serde_json::{Map, Value, json};
+
+use crate::{Request, Response, error_response, resolve};
+
+/// What to do with a request that matched a route.
+pub enum Handler {
+    /// Store the request body as a file under the static root, at the request path.
+    Upload,
+    /// Reply with a JSON description of the request, including its body.
+    EchoJson,
+    /// Call a registered function.
+    Custom(Box<dyn Fn(&Request) -> Response + Send + Sync>),
+}
+
+impl Handler {
+    /// Wraps a closure as a handler.
+    pub fn custom(f: impl Fn(&Request) -> Response + Send + Sync + 'static) -> Self {
+        Handler::Custom(Box::new(f))
+    }
+
+    pub(crate) fn call(&self, request: &Request, root: &Path) -> Response {
+        match self {
+            Handler::Upload => upload(request, root),
+            Handler::EchoJson => echo_json(request),
+            Handler::Custom(f) => f(request),
+        }
+    }
+}
+
+/// A method plus a path pattern. A pattern ending in `*` matches every path with that prefix.
+pub(crate) struct Route {
+    pub(crate) method: String,
+    pattern: String,
+    pub(crate) handler: Handler,
+}
+
+impl Route {
+    pub(crate) fn new(method: &str, pattern: &str, handler: Handler) -> Self {
+        Self {
+            method: method.to_ascii_uppercase(),
+            pattern: pattern.to_string(),
+            handler,
+        }
+    }
+
+    pub(crate) fn matches(&self, path: &str) -> bool {
+        match self.pattern.strip_suffix('*') {
+            Some(prefix) => path.starts_with(prefix),
+            None => path == self.pattern,
+        }
+    }
+}
+
+/// Writes the body to the file named by the request path, creating directories as needed.
+fn upload(request: &Request, root: &Path) -> Response {
+    let target = match resolve::resolve_for_write(root, request.path()) {
+        Ok(target) => target,
+        Err(e) => return error_response(e.status()),
+    };
+    let existed = target.is_file();
+
+    let written = match target.parent() {
+        Some(parent) => fs::create_dir_all(parent).and_then(|()| fs::write(&target, &request.body)),
+        None => fs::write(&target, &request.body),
+    };
+
+    match written {
+        Ok(()) if existed => Response::new(204),
+        Ok(()) => Response::new(201).header("Location", request.path()),
+        Err(e) => {
+            eprintln!("Upload to {} failed: {e}", target.display());
+            error_response(500)
+        }
+    }
+}
+
+/// Describes the request as JSON. A body that is itself JSON is embedded as a value.
+fn echo_json(request: &Request) -> Response {
+    let headers: Map<String, Value> = request
+        .headers
+        .iter()
+        .map(|(name, value)| (name.to_ascii_lowercase(), Value::from(value.as_str())))
+        .collect();
+    let body = serde_json::from_slice::<Value>(&request.body)
+        .unwrap_or_else(|_| Value::from(String::from_utf8_lossy(&request.body)));
+
+    let echo = json!({
+        "method": request.method,
+        "path": request.path(),
+        "query": request.query(),
+        "headers": headers,
+        "body": body,
+    });
+
+    Response::new(200)
+        .header("Content-Type", "application/json")
+        .body(echo.to_string())
+}
@@ -0,0 +1,100 @@
+//! HTTPS support built on rustls, enabled with the `tls` cargo feature.
+
+use std::{
+    io,
+    net::{TcpListener, TcpStream},
+    path::Path,
+    sync::Arc,
+    thread,
+};
+
+use rustls::{
+    ServerConfig, ServerConnection, StreamOwned,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+};
+
+use crate::{Server, linger, prepare};
+
+/// Builds a rustls server config from a PEM certificate chain and a PEM private key.
+pub fn load_config(cert_path: &Path, key_path: &Path) -> io::Result<Arc<ServerConfig>> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| {
+            invalid(format!(
+                "cannot read certificates from {}: {e}",
+                cert_path.display()
+            ))
+        })?;
+    if certs.is_empty() {
+        return Err(invalid(format!(
+            "no certificates found in {}",
+            cert_path.display()
+        )));
+    }
+
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|e| {
+        invalid(format!(
+            "cannot read private key from {}: {e}",
+            key_path.display()
+        ))
+    })?;
+
+    let config =
+        ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|e| invalid(format!("unusable certificate or key: {e}")))?;
+
+    Ok(Arc::new(config))
+}
+
+impl Server {
+    /// Accepts TLS connections forever, one thread per connection.
+    pub fn serve_tls(
+        self: Arc<Self>,
+        listener: TcpListener,
+        config: Arc<ServerConfig>,
+    ) -> io::Result<()> {
+        for stream in listener.incoming().flatten() {
+            let server = Arc::clone(&self);
+            let config = Arc::clone(&config);
+            thread::spawn(move || {
+                let peer = match prepare(&stream) {
+                    Ok(peer) => peer,
+                    Err(e) => return eprintln!("Dropping connection: {e}"),
+                };
+                match accept(stream, config) {
+                    Ok(mut stream) => {
+                        server.handle_connection(&mut stream, peer);
+
+                        // Tell the client the response is complete rather than truncated
+                        stream.conn.send_close_notify();
+                        let _ = stream.conn.complete_io(&mut stream.sock);
+                        linger(&stream.sock);
+                    }
+                    Err(e) => eprintln!("TLS handshake with {peer} failed: {e}"),
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Runs the TLS handshake so failures are reported here instead of mid-request.
+fn accept(
+    stream: TcpStream,
+    config: Arc<ServerConfig>,
+) -> io::Result<StreamOwned<ServerConnection, TcpStream>> {
+    let conn = ServerConnection::new(config).map_err(io::Error::other)?;
+    let mut stream = StreamOwned::new(conn, stream);
+    while stream.conn.is_handshaking() {
+        stream.conn.complete_io(&mut stream.sock)?;
+    }
+    Ok(stream)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
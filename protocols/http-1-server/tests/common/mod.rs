@@ -0,0 +1,32 @@
+//! Fixtures shared by the integration tests.
+
+use std::{
+    fs,
+    net::{SocketAddr, TcpListener},
+    path::PathBuf,
+    sync::Arc,
+    thread,
+};
+
+use http_1_server::Server;
+
+/// Creates a fresh static root for the test `name`, holding `index.html` and `test.txt`.
+pub fn static_root(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("http-1-tests-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join("index.html"), b"<h1>index</h1>").unwrap();
+    fs::write(root.join("test.txt"), b"Hello from test!").unwrap();
+    root
+}
+
+/// Serves `server` on a random port.
+pub fn start_server(server: Server) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = Arc::new(server);
+
+    thread::spawn(move || server.serve(listener));
+
+    addr
+}
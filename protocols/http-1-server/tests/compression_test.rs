@@ -0,0 +1,215 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpStream},
+    path::PathBuf,
+};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use http_1_server::Server;
+
+mod common;
+use common::{start_server, static_root};
+
+fn page() -> String {
+    "<p>Compress me, I repeat myself.</p>\n".repeat(100)
+}
+
+// A static root with a compressible page, a file too small to compress and an image
+fn fixture(name: &str) -> PathBuf {
+    let root = static_root(name);
+    fs::write(root.join("page.html"), page()).unwrap();
+    fs::write(root.join("tiny.txt"), "too small to bother").unwrap();
+    fs::write(root.join("photo.png"), vec![0x89; 4096]).unwrap();
+    root
+}
+
+struct Reply {
+    status: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+// GET `path` with the given Accept-Encoding and decode the framing (not the content coding)
+fn get(addr: SocketAddr, path: &str, accept_encoding: Option<&str>) -> Reply {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let mut request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n");
+    if let Some(value) = accept_encoding {
+        request.push_str(&format!("Accept-Encoding: {value}\r\n"));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).unwrap();
+
+    let mut reader = BufReader::new(stream);
+    let mut status = String::new();
+    reader.read_line(&mut status).unwrap();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':').unwrap();
+        headers.push((name.to_string(), value.trim().to_string()));
+    }
+
+    let mut reply = Reply {
+        status: status.trim_end().to_string(),
+        headers,
+        body: Vec::new(),
+    };
+    if reply.header("Transfer-Encoding") == Some("chunked") {
+        loop {
+            let mut size = String::new();
+            reader.read_line(&mut size).unwrap();
+            let size = usize::from_str_radix(size.trim(), 16).unwrap();
+            let mut chunk = vec![0; size + 2];
+            reader.read_exact(&mut chunk).unwrap();
+            if size == 0 {
+                break;
+            }
+            reply.body.extend_from_slice(&chunk[..size]);
+        }
+    } else {
+        reader.read_to_end(&mut reply.body).unwrap();
+    }
+    reply
+}
+
+fn gunzip(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    GzDecoder::new(bytes).read_to_string(&mut out).unwrap();
+    out
+}
+
+fn unbrotli(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    brotli::Decompressor::new(bytes, 4096)
+        .read_to_string(&mut out)
+        .unwrap();
+    out
+}
+
+#[test]
+fn gzip_on_the_fly() {
+    let addr = start_server(Server::new(fixture("gzip")));
+
+    let reply = get(addr, "/page.html", Some("gzip"));
+
+    assert_eq!(reply.status, "HTTP/1.1 200 OK");
+    assert_eq!(reply.header("Content-Encoding"), Some("gzip"));
+    assert_eq!(reply.header("Vary"), Some("Accept-Encoding"));
+    assert_eq!(reply.header("Content-Type"), Some("text/html"));
+    assert!(reply.body.len() < page().len());
+    assert_eq!(gunzip(&reply.body), page());
+}
+
+#[test]
+fn brotli_preferred_over_gzip() {
+    let addr = start_server(Server::new(fixture("brotli")));
+
+    let reply = get(addr, "/page.html", Some("gzip, deflate, br"));
+
+    assert_eq!(reply.header("Content-Encoding"), Some("br"));
+    assert_eq!(unbrotli(&reply.body), page());
+}
+
+#[test]
+fn client_weights_are_respected() {
+    let addr = start_server(Server::new(fixture("weights")));
+
+    let reply = get(addr, "/page.html", Some("br;q=0.1, gzip;q=0.8"));
+    assert_eq!(reply.header("Content-Encoding"), Some("gzip"));
+
+    let reply = get(addr, "/page.html", Some("br;q=0, gzip;q=0"));
+    assert_eq!(reply.header("Content-Encoding"), None);
+    assert_eq!(reply.body, page().as_bytes());
+}
+
+#[test]
+fn identity_without_accept_encoding() {
+    let addr = start_server(Server::new(fixture("identity")));
+
+    let reply = get(addr, "/page.html", None);
+
+    assert_eq!(reply.header("Content-Encoding"), None);
+    assert_eq!(
+        reply.header("Content-Length"),
+        Some(&*page().len().to_string())
+    );
+    assert_eq!(reply.header("Vary"), Some("Accept-Encoding"));
+    assert_eq!(reply.body, page().as_bytes());
+}
+
+#[test]
+fn incompressible_and_tiny_files_are_sent_as_is() {
+    let addr = start_server(Server::new(fixture("as-is")));
+
+    let reply = get(addr, "/photo.png", Some("gzip, br"));
+    assert_eq!(reply.header("Content-Encoding"), None);
+    assert_eq!(reply.header("Vary"), None);
+    assert_eq!(reply.body.len(), 4096);
+
+    let reply = get(addr, "/tiny.txt", Some("gzip, br"));
+    assert_eq!(reply.header("Content-Encoding"), None);
+    assert_eq!(reply.body, b"too small to bother");
+}
+
+#[test]
+fn precompressed_siblings_are_served() {
+    let root = fixture("precompressed");
+    // Distinct contents prove the sibling was sent rather than the page compressed again
+    let mut gz = GzEncoder::new(Vec::new(), Compression::best());
+    gz.write_all(b"from the .gz file").unwrap();
+    fs::write(root.join("page.html.gz"), gz.finish().unwrap()).unwrap();
+    fs::write(root.join("photo.png.gz"), b"not really gzip").unwrap();
+    let addr = start_server(Server::new(&root));
+
+    let reply = get(addr, "/page.html", Some("gzip"));
+    assert_eq!(reply.header("Content-Encoding"), Some("gzip"));
+    assert_eq!(reply.header("Content-Type"), Some("text/html"));
+    assert!(reply.header("Content-Length").is_some());
+    assert_eq!(gunzip(&reply.body), "from the .gz file");
+
+    // Without a .br sibling brotli is still produced on the fly
+    let reply = get(addr, "/page.html", Some("br"));
+    assert_eq!(reply.header("Content-Encoding"), Some("br"));
+    assert_eq!(unbrotli(&reply.body), page());
+
+    // A sibling makes even an incompressible type vary by encoding
+    let reply = get(addr, "/photo.png", Some("gzip"));
+    assert_eq!(reply.header("Content-Encoding"), Some("gzip"));
+    assert_eq!(reply.header("Vary"), Some("Accept-Encoding"));
+    assert_eq!(reply.header("Content-Type"), Some("image/png"));
+    assert_eq!(reply.body, b"not really gzip");
+
+    let reply = get(addr, "/photo.png", None);
+    assert_eq!(reply.header("Content-Encoding"), None);
+    assert_eq!(reply.header("Vary"), Some("Accept-Encoding"));
+}
+
+#[cfg(unix)]
+#[test]
+fn sibling_symlinks_out_of_root_are_ignored() {
+    let root = fixture("sibling-escape");
+    let outside = root.with_extension("secret.gz");
+    fs::write(&outside, b"outside the root").unwrap();
+    std::os::unix::fs::symlink(&outside, root.join("page.html.gz")).unwrap();
+    let addr = start_server(Server::new(&root));
+
+    let reply = get(addr, "/page.html", Some("gzip"));
+
+    assert_eq!(gunzip(&reply.body), page());
+}
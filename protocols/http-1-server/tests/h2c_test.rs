@@ -0,0 +1,341 @@
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    thread,
+    time::Duration,
+};
+
+use http_1_server::{Handler, Response, Server};
+use http_2_server::ConnectionOptions;
+use toy_http2::{
+    Frame, PREFACE,
+    hpack::{Decoder, Encoder},
+    settings::{self, MAX_FRAME_SIZE},
+};
+
+mod common;
+use common::{start_server, static_root};
+
+// Start a server on a random port, answering `/describe/*` with each request's method, path
+// and body, and serving static files otherwise
+fn start(name: &str, http2: bool) -> SocketAddr {
+    let describe = || {
+        Handler::custom(|request| {
+            let mut body = format!("{} {} ", request.method, request.target).into_bytes();
+            body.extend(&request.body);
+            Response::new(200).body(body)
+        })
+    };
+    let mut server = Server::new(static_root(name))
+        .route("GET", "/describe/*", describe())
+        .route("POST", "/describe/*", describe());
+    if http2 {
+        server = server.http2(ConnectionOptions::new());
+    }
+    start_server(server)
+}
+
+fn send(stream: &mut TcpStream, frame: Frame) {
+    frame.write_to(stream).unwrap();
+}
+
+fn settings_frame() -> Frame {
+    Frame::Settings {
+        ack: false,
+        parameters: Vec::new(),
+    }
+}
+
+// Reads whole frames as they arrive, or None once the server has closed
+fn read_frame(stream: &mut TcpStream, buf: &mut Vec<u8>) -> Option<Frame> {
+    loop {
+        if let Some((frame, len)) = Frame::parse(buf, 1 << 24).unwrap() {
+            buf.drain(..len);
+            return Some(frame);
+        }
+        let mut chunk = [0u8; 4096];
+        match stream.read(&mut chunk) {
+            Ok(0) | Err(_) => return None,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+}
+
+// Reads the response on `stream_id`: its :status and body
+fn read_response(stream: &mut TcpStream, buf: &mut Vec<u8>, stream_id: u32) -> (String, Vec<u8>) {
+    let (headers, body) = read_fields(stream, buf, &mut Decoder::default(), stream_id);
+    (headers[0].1.clone(), body)
+}
+
+// Reads the response on `stream_id`: all of its fields and its body. Header blocks share
+// `decoder`'s dynamic table, so one decoder must read every response on a connection
+fn read_fields(
+    stream: &mut TcpStream,
+    buf: &mut Vec<u8>,
+    decoder: &mut Decoder,
+    stream_id: u32,
+) -> (Vec<(String, String)>, Vec<u8>) {
+    let mut fields = Vec::new();
+    let mut body = Vec::new();
+    loop {
+        match read_frame(stream, buf).expect("closed") {
+            Frame::Headers {
+                stream_id: id,
+                block,
+                end_stream,
+                ..
+            } => {
+                let headers = decoder.decode(&block).unwrap();
+                if id == stream_id {
+                    fields = headers;
+                    if end_stream {
+                        return (fields, body);
+                    }
+                }
+            }
+            Frame::Data {
+                stream_id: id,
+                data,
+                end_stream,
+                ..
+            } if id == stream_id => {
+                body.extend(data);
+                if end_stream {
+                    return (fields, body);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Reads the HTTP/1.1 response head, leaving anything after it in `buf`
+fn read_head(stream: &mut TcpStream, buf: &mut Vec<u8>) -> String {
+    loop {
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = String::from_utf8(buf[..end + 4].to_vec()).unwrap();
+            buf.drain(..end + 4);
+            return head;
+        }
+        let mut chunk = [0u8; 4096];
+        let n = stream.read(&mut chunk).unwrap();
+        assert!(n > 0, "closed before the head ended");
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+#[test]
+fn prior_knowledge_clients_get_http2() {
+    let addr = start("prior", true);
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let mut buf = Vec::new();
+
+    stream.write_all(PREFACE).unwrap();
+    send(&mut stream, settings_frame());
+    let block = Encoder::default().encode([
+        (":method", "GET"),
+        (":scheme", "http"),
+        (":path", "/describe/direct"),
+    ]);
+    send(
+        &mut stream,
+        Frame::Headers {
+            stream_id: 1,
+            block,
+            priority: None,
+            end_stream: true,
+            end_headers: true,
+            padding: None,
+        },
+    );
+
+    let (status, body) = read_response(&mut stream, &mut buf, 1);
+    assert_eq!(status, "200");
+    assert_eq!(body, b"GET /describe/direct ");
+}
+
+#[test]
+fn a_preface_split_across_writes_is_still_recognised() {
+    let addr = start("split", true);
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.set_nodelay(true).unwrap();
+    let mut buf = Vec::new();
+
+    stream.write_all(&PREFACE[..2]).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    stream.write_all(&PREFACE[2..]).unwrap();
+    send(&mut stream, settings_frame());
+    let block = Encoder::default().encode([
+        (":method", "GET"),
+        (":scheme", "http"),
+        (":path", "/describe/split"),
+    ]);
+    send(
+        &mut stream,
+        Frame::Headers {
+            stream_id: 1,
+            block,
+            priority: None,
+            end_stream: true,
+            end_headers: true,
+            padding: None,
+        },
+    );
+
+    let (status, body) = read_response(&mut stream, &mut buf, 1);
+    assert_eq!(status, "200");
+    assert_eq!(body, b"GET /describe/split ");
+}
+
+#[test]
+fn upgrade_requests_are_answered_on_stream_1() {
+    let addr = start("upgrade", true);
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let mut buf = Vec::new();
+
+    // Settings sent in the upgrade apply without an acknowledgement
+    let request = format!(
+        "POST /describe/upload HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade, HTTP2-Settings\r\n\
+         Upgrade: h2c\r\nHTTP2-Settings: {}\r\nContent-Length: 5\r\n\r\nhello",
+        settings::to_header(&[(MAX_FRAME_SIZE, 1 << 20)])
+    );
+    stream.write_all(request.as_bytes()).unwrap();
+    let head = read_head(&mut stream, &mut buf);
+    assert!(
+        head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"),
+        "{head}"
+    );
+    assert!(head.contains("\r\nUpgrade: h2c\r\n"));
+
+    stream.write_all(PREFACE).unwrap();
+    send(&mut stream, settings_frame());
+    assert!(matches!(
+        read_frame(&mut stream, &mut buf),
+        Some(Frame::Settings { ack: false, .. })
+    ));
+    let (status, body) = read_response(&mut stream, &mut buf, 1);
+    assert_eq!(status, "200");
+    assert_eq!(body, b"POST /describe/upload hello");
+
+    // Later requests go on new streams as usual
+    let block = Encoder::default().encode([
+        (":method", "GET"),
+        (":scheme", "http"),
+        (":path", "/describe/next"),
+    ]);
+    send(
+        &mut stream,
+        Frame::Headers {
+            stream_id: 3,
+            block,
+            priority: None,
+            end_stream: true,
+            end_headers: true,
+            padding: None,
+        },
+    );
+    let (status, body) = read_response(&mut stream, &mut buf, 3);
+    assert_eq!(status, "200");
+    assert_eq!(body, b"GET /describe/next ");
+}
+
+#[test]
+fn static_files_are_served_over_http2() {
+    let addr = start("static", true);
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let mut buf = Vec::new();
+
+    let request = format!(
+        "GET /test.txt HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade, HTTP2-Settings\r\n\
+         Upgrade: h2c\r\nHTTP2-Settings: {}\r\n\r\n",
+        settings::to_header(&[])
+    );
+    stream.write_all(request.as_bytes()).unwrap();
+    let head = read_head(&mut stream, &mut buf);
+    assert!(
+        head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"),
+        "{head}"
+    );
+
+    stream.write_all(PREFACE).unwrap();
+    send(&mut stream, settings_frame());
+    let mut decoder = Decoder::default();
+    let (fields, body) = read_fields(&mut stream, &mut buf, &mut decoder, 1);
+    assert_eq!(body, b"Hello from test!");
+    for field in [(":status", "200"), ("content-type", "text/plain")] {
+        assert!(
+            fields.contains(&(field.0.to_string(), field.1.to_string())),
+            "{fields:?}"
+        );
+    }
+    assert!(
+        !fields.iter().any(|(name, _)| name == "connection"),
+        "{fields:?}"
+    );
+
+    // HEAD tells the length without the body, and missing files are still not found
+    let mut encoder = Encoder::default();
+    for (stream_id, method, path) in [(3, "HEAD", "/test.txt"), (5, "GET", "/missing.txt")] {
+        let block = encoder.encode([(":method", method), (":scheme", "http"), (":path", path)]);
+        send(
+            &mut stream,
+            Frame::Headers {
+                stream_id,
+                block,
+                priority: None,
+                end_stream: true,
+                end_headers: true,
+                padding: None,
+            },
+        );
+    }
+    let (fields, body) = read_fields(&mut stream, &mut buf, &mut decoder, 3);
+    assert!(body.is_empty());
+    assert!(
+        fields.contains(&("content-length".to_string(), "16".to_string())),
+        "{fields:?}"
+    );
+    let (fields, _) = read_fields(&mut stream, &mut buf, &mut decoder, 5);
+    assert_eq!(fields[0], (":status".to_string(), "404".to_string()));
+}
+
+#[test]
+fn http1_still_works_on_the_same_port() {
+    let addr = start("http1", true);
+
+    // Including a request whose method starts like the preface
+    for request in [
+        "GET /test.txt HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        "PUT /test.txt HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+    ] {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 "), "{response}");
+        assert!(!response.starts_with("HTTP/1.1 101"));
+    }
+}
+
+#[test]
+fn upgrades_are_ignored_when_incomplete_or_not_offered() {
+    let bad = "GET /test.txt HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade\r\n\
+               Upgrade: h2c\r\n\r\n";
+    let good = format!(
+        "GET /test.txt HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade, HTTP2-Settings\r\n\
+         Upgrade: h2c\r\nHTTP2-Settings: {}\r\n\r\n",
+        settings::to_header(&[])
+    );
+
+    for (addr, request) in [
+        (start("no-settings", true), bad.to_string()),
+        (start("no-http2", false), good),
+    ] {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let mut buf = Vec::new();
+        stream.write_all(request.as_bytes()).unwrap();
+
+        let head = read_head(&mut stream, &mut buf);
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{head}");
+    }
+}
@@ -0,0 +1,203 @@
+use std::{
+    fs,
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+};
+
+use http_1_server::{Handler, Response, Server};
+
+mod common;
+use common::{start_server, static_root};
+
+// Send one request with `Connection: close` and return the raw response
+fn send(addr: SocketAddr, request: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+fn body(response: &str) -> &str {
+    response.split_once("\r\n\r\n").unwrap().1
+}
+
+#[test]
+fn put_stores_upload_under_static_root() {
+    let root = static_root("upload");
+    let addr = start_server(Server::new(&root).route("PUT", "/uploads/*", Handler::Upload));
+
+    let response = send(
+        addr,
+        "PUT /uploads/notes/a.txt HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+    );
+    assert!(response.starts_with("HTTP/1.1 201 Created"));
+    assert!(response.contains("Location: /uploads/notes/a.txt"));
+    assert_eq!(
+        fs::read(root.join("uploads/notes/a.txt")).unwrap(),
+        b"hello"
+    );
+
+    // Replacing an existing file answers 204, and the new content is served back
+    let response = send(
+        addr,
+        "PUT /uploads/notes/a.txt HTTP/1.1\r\nContent-Length: 3\r\nConnection: close\r\n\r\nbye",
+    );
+    assert!(response.starts_with("HTTP/1.1 204 No Content"));
+
+    let response = send(
+        addr,
+        "GET /uploads/notes/a.txt HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+    assert_eq!(body(&response), "bye");
+}
+
+#[test]
+fn chunked_upload_is_reassembled() {
+    let root = static_root("chunked");
+    let addr = start_server(Server::new(&root).route("POST", "/uploads/*", Handler::Upload));
+
+    let response = send(
+        addr,
+        "POST /uploads/c.txt HTTP/1.1\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+         4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n",
+    );
+
+    assert!(response.starts_with("HTTP/1.1 201 Created"));
+    assert_eq!(fs::read(root.join("uploads/c.txt")).unwrap(), b"Wikipedia");
+}
+
+#[test]
+fn upload_refuses_parent_directories() {
+    let root = static_root("escape");
+    fs::create_dir_all(root.join("inner")).unwrap();
+    let addr = start_server(Server::new(root.join("inner")).route("PUT", "/*", Handler::Upload));
+
+    let response = send(
+        addr,
+        "PUT /%2e%2e/escaped.txt HTTP/1.1\r\nContent-Length: 1\r\nConnection: close\r\n\r\nx",
+    );
+
+    assert!(response.starts_with("HTTP/1.1 403 Forbidden"));
+    assert!(response.contains("Content-Type: text/plain\r\n"));
+    assert_eq!(body(&response), "403 Forbidden");
+    assert!(!root.join("escaped.txt").exists());
+}
+
+#[test]
+fn echo_json_describes_request() {
+    let addr =
+        start_server(Server::new(static_root("echo")).route("POST", "/echo", Handler::EchoJson));
+
+    let response = send(
+        addr,
+        "POST /echo?x=1 HTTP/1.1\r\nX-Test: yes\r\nContent-Type: application/json\r\nContent-Length: 13\r\nConnection: close\r\n\r\n{\"answer\":42}",
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("Content-Type: application/json"));
+
+    let echo: serde_json::Value = serde_json::from_str(body(&response)).unwrap();
+    assert_eq!(echo["method"], "POST");
+    assert_eq!(echo["path"], "/echo");
+    assert_eq!(echo["query"], "x=1");
+    assert_eq!(echo["headers"]["x-test"], "yes");
+    assert_eq!(echo["body"]["answer"], 42);
+}
+
+#[test]
+fn custom_handler_is_called() {
+    let server = Server::new(static_root("custom")).route(
+        "POST",
+        "/reverse",
+        Handler::custom(|request| {
+            let mut body = request.body.clone();
+            body.reverse();
+            Response::new(200).body(body)
+        }),
+    );
+    let addr = start_server(server);
+
+    let response = send(
+        addr,
+        "POST /reverse HTTP/1.1\r\nContent-Length: 3\r\nConnection: close\r\n\r\nabc",
+    );
+
+    assert_eq!(body(&response), "cba");
+}
+
+#[test]
+fn unsupported_method_is_405_with_allow() {
+    let server = Server::new(static_root("405")).route("POST", "/echo", Handler::EchoJson);
+    let addr = start_server(server);
+
+    let response = send(
+        addr,
+        "DELETE /index.html HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed"));
+    assert!(response.contains("Allow: GET, HEAD\r\n"));
+
+    let response = send(
+        addr,
+        "PUT /echo HTTP/1.1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed"));
+    assert!(response.contains("Allow: GET, HEAD, POST\r\n"));
+
+    // A POST without any route is refused rather than served the static file
+    let response = send(
+        addr,
+        "POST /index.html HTTP/1.1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed"));
+}
+
+#[test]
+fn head_is_answered_like_get_without_the_body() {
+    let server = Server::new(static_root("head")).route(
+        "GET",
+        "/hello",
+        Handler::custom(|_| Response::new(200).body("hello")),
+    );
+    let addr = start_server(server);
+
+    let response = send(
+        addr,
+        "HEAD /index.html HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+    assert!(response.contains("Content-Length: 14\r\n"), "{response}");
+    assert!(response.contains("Content-Type: text/html"), "{response}");
+    assert_eq!(body(&response), "");
+
+    // GET routes answer HEAD too
+    let response = send(addr, "HEAD /hello HTTP/1.1\r\nConnection: close\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+    assert!(response.contains("Content-Length: 5\r\n"), "{response}");
+    assert_eq!(body(&response), "");
+
+    // Errors keep their headers and lose their body the same way
+    let response = send(
+        addr,
+        "HEAD /missing.html HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 404 Not Found"), "{response}");
+    assert_eq!(body(&response), "");
+}
+
+#[test]
+fn body_is_consumed_on_keep_alive() {
+    let server = Server::new(static_root("keep-alive")).route("POST", "/echo", Handler::EchoJson);
+    let addr = start_server(server);
+
+    // Two pipelined requests: the first body must not be mistaken for the second request
+    let response = send(
+        addr,
+        "POST /echo HTTP/1.1\r\nContent-Length: 4\r\n\r\nping\
+         GET /index.html HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+
+    assert!(response.contains("\"body\":\"ping\""));
+    assert!(response.ends_with("<h1>index</h1>"));
+}
@@ -2,40 +2,18 @@ use std::{
     fs,
     io::{Read, Write},
     net::TcpStream,
-    process::{Command, Stdio},
-    thread,
-    time::Duration,
 };
 
-fn wait_for_server() {
-    for _ in 0..10 {
-        if TcpStream::connect("127.0.0.1:8080").is_ok() {
-            return;
-        }
-        thread::sleep(Duration::from_millis(200));
-    }
-    panic!("Server did not start in time");
-}
+use http_1_server::Server;
+
+mod common;
+use common::{start_server, static_root};
 
 #[test]
 fn test_static_file_serving() {
-    // Ensure static file exists
-    fs::create_dir_all("static").unwrap();
-    fs::write("static/test.txt", b"Hello from test!").unwrap();
-
-    // Start server in a separate thread
-    thread::spawn(|| {
-        let _ = Command::new("cargo")
-            .args(&["run"])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-            .expect("Failed to run server");
-    });
-
-    wait_for_server();
-
-    let mut stream = TcpStream::connect("127.0.0.1:8080").unwrap();
+    let addr = start_server(Server::new(static_root("static")));
+
+    let mut stream = TcpStream::connect(addr).unwrap();
     let request = "GET /test.txt HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
     stream.write_all(request.as_bytes()).unwrap();
 
@@ -49,9 +27,9 @@ fn test_static_file_serving() {
 
 #[test]
 fn test_404_response() {
-    wait_for_server();
+    let addr = start_server(Server::new(static_root("404")));
 
-    let mut stream = TcpStream::connect("127.0.0.1:8080").unwrap();
+    let mut stream = TcpStream::connect(addr).unwrap();
     let request = "GET /nonexistent.html HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
     stream.write_all(request.as_bytes()).unwrap();
 
@@ -64,9 +42,9 @@ fn test_404_response() {
 
 #[test]
 fn test_keep_alive() {
-    wait_for_server();
+    let addr = start_server(Server::new(static_root("keep-alive")));
 
-    let mut stream = TcpStream::connect("127.0.0.1:8080").unwrap();
+    let mut stream = TcpStream::connect(addr).unwrap();
     let request = "GET /test.txt HTTP/1.1\r\nHost: localhost\r\nConnection: keep-alive\r\n\r\n";
     stream.write_all(request.as_bytes()).unwrap();
 
@@ -84,3 +62,49 @@ fn test_keep_alive() {
 
     assert!(response2.contains("HTTP/1.1 200 OK"));
 }
+
+#[test]
+fn test_http_1_0_closes_unless_keep_alive_is_asked_for() {
+    let addr = start_server(Server::new(static_root("http-1-0")));
+
+    // Without keep-alive the server closes after the response, ending the read
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(b"GET /test.txt HTTP/1.0\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.contains("200 OK"), "{response}");
+    assert!(response.contains("Connection: close"), "{response}");
+
+    // With it, a second request is answered on the same connection
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let request = "GET /test.txt HTTP/1.0\r\nConnection: keep-alive\r\n\r\n";
+    for _ in 0..2 {
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut buffer = [0u8; 1024];
+        let size = stream.read(&mut buffer).unwrap();
+        let response = String::from_utf8_lossy(&buffer[..size]);
+        assert!(response.contains("Connection: keep-alive"), "{response}");
+    }
+}
+
+#[test]
+fn test_large_file_is_sent_whole() {
+    let root = static_root("large");
+    let contents: Vec<u8> = (0..5_000_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(root.join("large.bin"), &contents).unwrap();
+
+    let addr = start_server(Server::new(root));
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let request = "GET /large.bin HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+    stream.write_all(request.as_bytes()).unwrap();
+
+    let mut buffer = Vec::new();
+    stream.read_to_end(&mut buffer).unwrap();
+    let split = buffer.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    let head = String::from_utf8_lossy(&buffer[..split]);
+
+    assert!(head.contains("Content-Length: 5000000"));
+    assert!(head.contains("Content-Type: application/octet-stream"));
+    assert!(buffer[split..] == contents[..]);
+}
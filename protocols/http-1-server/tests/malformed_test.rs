@@ -0,0 +1,148 @@
+use std::{
+    io::{Read, Write},
+    net::{Shutdown, SocketAddr, TcpStream},
+    time::Duration,
+};
+
+use http_1_server::{Handler, Response, Server};
+
+mod common;
+use common::{start_server, static_root};
+
+// Send raw bytes, stop writing, and return whatever the server answers before closing
+fn send(addr: SocketAddr, request: &[u8]) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(request).unwrap();
+    stream.shutdown(Shutdown::Write).unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+fn assert_served(addr: SocketAddr) {
+    let response = send(
+        addr,
+        b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+}
+
+#[test]
+fn malformed_requests_get_an_error_status_and_close() {
+    let addr = start_server(Server::new(static_root("status")));
+
+    for (request, status) in [
+        ("garbage\r\n\r\n", "400 Bad Request"),
+        ("GET / HTTP/1.1\r\nno colon\r\n\r\n", "400 Bad Request"),
+        (
+            "POST / HTTP/1.1\r\nContent-Length: ten\r\n\r\n",
+            "400 Bad Request",
+        ),
+        (
+            "POST / HTTP/1.1\r\nContent-Length: 1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n",
+            "400 Bad Request",
+        ),
+        (
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nnope\r\n",
+            "400 Bad Request",
+        ),
+        (
+            "POST / HTTP/1.1\r\nContent-Length: 999999999999\r\n\r\n",
+            "413 Content Too Large",
+        ),
+        ("GET / HTTP/3.0\r\n\r\n", "505 HTTP Version Not Supported"),
+        (
+            "POST / HTTP/1.1\r\nTransfer-Encoding: br\r\n\r\n",
+            "501 Not Implemented",
+        ),
+    ] {
+        let response = send(addr, request.as_bytes());
+        assert!(
+            response.starts_with(&format!("HTTP/1.1 {status}\r\n")),
+            "{request:?}: {response}"
+        );
+        assert!(response.contains("Connection: close\r\n"), "{request:?}");
+    }
+
+    let huge = format!("GET / HTTP/1.1\r\nX-Big: {}\r\n\r\n", "a".repeat(16 * 1024));
+    let response = send(addr, huge.as_bytes());
+    assert!(response.starts_with("HTTP/1.1 431 Request Header Fields Too Large"));
+}
+
+#[test]
+fn invalid_utf8_in_head_is_a_bad_request() {
+    let addr = start_server(Server::new(static_root("utf8")));
+
+    let response = send(addr, b"GET /\xff\xfe HTTP/1.1\r\n\r\n");
+
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+}
+
+#[test]
+fn truncated_requests_are_closed_without_a_response() {
+    let addr = start_server(Server::new(static_root("truncated")));
+
+    for request in [
+        "GET / HTTP/1.1",
+        "GET / HTTP/1.1\r\nHost: localhost\r\n",
+        "POST / HTTP/1.1\r\nContent-Length: 100\r\n\r\nonly a little",
+        "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n10\r\nabc",
+    ] {
+        assert_eq!(send(addr, request.as_bytes()), "", "{request:?}");
+    }
+
+    // The server is still up after all of that
+    assert_served(addr);
+}
+
+#[test]
+fn client_reset_mid_request_does_not_take_the_server_down() {
+    let addr = start_server(Server::new(static_root("reset")));
+
+    for _ in 0..5 {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"POST / HTTP/1.1\r\nContent-Length: 1000\r\n\r\npartial")
+            .unwrap();
+        drop(stream);
+    }
+
+    assert_served(addr);
+}
+
+#[test]
+fn malformed_request_after_a_good_one_on_keep_alive() {
+    let addr = start_server(Server::new(static_root("keep-alive")));
+
+    let response = send(
+        addr,
+        b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\nBROKEN REQUEST LINE HERE\r\n\r\n",
+    );
+
+    let (first, second) = response.split_once("<h1>index</h1>").unwrap();
+    assert!(first.starts_with("HTTP/1.1 200 OK"));
+    assert!(second.starts_with("HTTP/1.1 400 Bad Request"));
+}
+
+#[test]
+fn panicking_handler_answers_500() {
+    let addr = start_server(Server::new(static_root("panic")).route(
+        "GET",
+        "/boom",
+        Handler::custom(|_| -> Response { panic!("boom") }),
+    ));
+
+    let response = send(
+        addr,
+        b"GET /boom HTTP/1.1\r\nHost: localhost\r\n\r\nGET / HTTP/1.1\r\nConnection: close\r\n\r\n",
+    );
+
+    // The connection survives the panic and serves the next request too
+    let (first, second) = response.split_once("\r\n\r\n").unwrap();
+    assert!(first.starts_with("HTTP/1.1 500 Internal Server Error"));
+    assert!(second.contains("HTTP/1.1 200 OK"));
+}
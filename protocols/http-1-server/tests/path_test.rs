@@ -0,0 +1,303 @@
+use std::{
+    fs,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::Arc,
+    thread,
+};
+
+use http_1_server::{
+    Handler, Server,
+    resolve::{ResolveError, resolve, resolve_for_write},
+};
+
+const SECRET: &str = "top secret, outside the static root";
+
+// base/secret.txt sits next to base/static, which is the served root
+fn fixture(name: &str) -> (PathBuf, PathBuf) {
+    let base = std::env::temp_dir().join(format!("http-1-paths-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&base);
+    let root = base.join("static");
+    fs::create_dir_all(root.join("dir")).unwrap();
+
+    fs::write(base.join("secret.txt"), SECRET).unwrap();
+    fs::write(root.join("index.html"), "root index").unwrap();
+    fs::write(root.join("a..b.txt"), "dots in the name").unwrap();
+    fs::write(root.join("dir/index.html"), "dir index").unwrap();
+    fs::write(root.join("dir/file.txt"), "file in dir").unwrap();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::symlink;
+        symlink(base.join("secret.txt"), root.join("link_out")).unwrap();
+        symlink(&base, root.join("dir_out")).unwrap();
+        symlink(root.join("dir/file.txt"), root.join("link_in")).unwrap();
+    }
+
+    (base, root)
+}
+
+fn contents(result: Result<PathBuf, ResolveError>) -> String {
+    fs::read_to_string(result.unwrap()).unwrap()
+}
+
+#[test]
+fn legitimate_paths_resolve() {
+    let (_base, root) = fixture("legit");
+
+    assert_eq!(contents(resolve(&root, "/")), "root index");
+    assert_eq!(contents(resolve(&root, "/index.html")), "root index");
+    assert_eq!(contents(resolve(&root, "/dir/")), "dir index");
+    assert_eq!(contents(resolve(&root, "/dir")), "dir index");
+    assert_eq!(contents(resolve(&root, "/dir/file.txt")), "file in dir");
+    assert_eq!(contents(resolve(&root, "/dir/./file.txt")), "file in dir");
+    assert_eq!(contents(resolve(&root, "/dir/../index.html")), "root index");
+    assert_eq!(contents(resolve(&root, "//dir//file.txt")), "file in dir");
+    assert_eq!(contents(resolve(&root, "/%64ir/file.txt")), "file in dir");
+}
+
+#[test]
+fn names_containing_dots_are_kept() {
+    let (_base, root) = fixture("dots");
+
+    assert_eq!(contents(resolve(&root, "/a..b.txt")), "dots in the name");
+    assert_eq!(
+        contents(resolve(&root, "/a%2e%2eb.txt")),
+        "dots in the name"
+    );
+}
+
+#[test]
+fn query_and_fragment_are_ignored() {
+    let (_base, root) = fixture("query");
+
+    assert_eq!(contents(resolve(&root, "/index.html?x=1")), "root index");
+    assert_eq!(
+        contents(resolve(&root, "/index.html?f=../../secret.txt")),
+        "root index"
+    );
+    assert_eq!(
+        contents(resolve(&root, "/index.html#../../secret.txt")),
+        "root index"
+    );
+    assert_eq!(contents(resolve(&root, "/dir/?a=b#c")), "dir index");
+}
+
+#[test]
+fn traversal_is_forbidden() {
+    let (_base, root) = fixture("traversal");
+
+    for target in [
+        "/../secret.txt",
+        "/../../../../etc/passwd",
+        "/dir/../../secret.txt",
+        "/%2e%2e/secret.txt",
+        "/%2E%2E/secret.txt",
+        "/%2e%2e%2fsecret.txt",
+        "/..%2fsecret.txt",
+        "/.%2e/secret.txt",
+        "/dir/%2e%2e/%2e%2e/secret.txt",
+        "..",
+        "../secret.txt",
+    ] {
+        assert_eq!(
+            resolve(&root, target),
+            Err(ResolveError::Forbidden),
+            "{target}"
+        );
+    }
+}
+
+#[test]
+fn filter_bypass_patterns_stay_inside_root() {
+    let (_base, root) = fixture("bypass");
+
+    // Each of these defeats a naive `.replace("..", "")`; here they are just odd names
+    for target in [
+        "/....//secret.txt",
+        "/....//....//secret.txt",
+        "/..../secret.txt",
+        "/.../.../secret.txt",
+        "/%252e%252e/secret.txt",
+        "/%252e%252e%252fsecret.txt",
+        "/...%2f/secret.txt",
+    ] {
+        assert_eq!(
+            resolve(&root, target),
+            Err(ResolveError::NotFound),
+            "{target}"
+        );
+    }
+}
+
+#[test]
+fn malformed_paths_are_bad_requests() {
+    let (_base, root) = fixture("malformed");
+
+    for target in [
+        "/..\\secret.txt",
+        "/..%5csecret.txt",
+        "/dir%5c..%5c..%5csecret.txt",
+        "/secret.txt%00.html",
+        "/%00",
+        "/%ff%fe",
+        "/%c0%ae%c0%ae/secret.txt",
+    ] {
+        assert_eq!(
+            resolve(&root, target),
+            Err(ResolveError::BadRequest),
+            "{target}"
+        );
+    }
+}
+
+#[test]
+fn missing_files_are_not_found() {
+    let (_base, root) = fixture("missing");
+
+    assert_eq!(resolve(&root, "/nope.txt"), Err(ResolveError::NotFound));
+    assert_eq!(
+        resolve(&root, "/index.html/nope"),
+        Err(ResolveError::NotFound)
+    );
+    assert_eq!(
+        resolve(&root.join("absent"), "/index.html"),
+        Err(ResolveError::NotFound)
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_out_of_root_are_forbidden() {
+    let (_base, root) = fixture("symlinks");
+
+    assert_eq!(resolve(&root, "/link_out"), Err(ResolveError::Forbidden));
+    assert_eq!(
+        resolve(&root, "/dir_out/secret.txt"),
+        Err(ResolveError::Forbidden)
+    );
+
+    // Only where a path finally lands matters, so these stay inside the root
+    assert_eq!(contents(resolve(&root, "/link_in")), "file in dir");
+    assert_eq!(
+        contents(resolve(&root, "/dir_out/static/index.html")),
+        "root index"
+    );
+}
+
+#[test]
+fn write_targets_stay_inside_root() {
+    let (_base, root) = fixture("write");
+    let canonical_root = root.canonicalize().unwrap();
+
+    assert_eq!(
+        resolve_for_write(&root, "/new/sub/file.txt").unwrap(),
+        canonical_root.join("new/sub/file.txt")
+    );
+    assert_eq!(
+        resolve_for_write(&root, "/../escape.txt"),
+        Err(ResolveError::Forbidden)
+    );
+    assert_eq!(resolve_for_write(&root, "/"), Err(ResolveError::BadRequest));
+    assert_eq!(
+        resolve_for_write(&root, "/a%5cb"),
+        Err(ResolveError::BadRequest)
+    );
+
+    #[cfg(unix)]
+    {
+        assert_eq!(
+            resolve_for_write(&root, "/link_out"),
+            Err(ResolveError::Forbidden)
+        );
+        assert_eq!(
+            resolve_for_write(&root, "/dir_out/new.txt"),
+            Err(ResolveError::Forbidden)
+        );
+    }
+}
+
+#[test]
+fn server_never_leaks_files_outside_root() {
+    let (_base, root) = fixture("server");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = Arc::new(Server::new(root));
+    thread::spawn(move || server.serve(listener));
+
+    for (target, status) in [
+        ("/../secret.txt", "403 Forbidden"),
+        ("/%2e%2e/secret.txt", "403 Forbidden"),
+        ("/....//secret.txt", "404 Not Found"),
+        ("/..%5csecret.txt", "400 Bad Request"),
+        (
+            "/link_out",
+            if cfg!(unix) {
+                "403 Forbidden"
+            } else {
+                "404 Not Found"
+            },
+        ),
+        ("/a..b.txt?x=../secret.txt", "200 OK"),
+    ] {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let request =
+            format!("GET {target} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        stream.write_all(request.as_bytes()).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(
+            response.starts_with(&format!("HTTP/1.1 {status}")),
+            "{target}: {response}"
+        );
+        assert!(!response.contains(SECRET), "{target} leaked the secret");
+    }
+}
+
+#[test]
+fn uploads_cannot_escape_their_prefix() {
+    let (base, root) = fixture("upload");
+    fs::create_dir_all(root.join("uploads")).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = Arc::new(Server::new(&root).route("PUT", "/uploads/*", Handler::Upload));
+    thread::spawn(move || server.serve(listener));
+
+    for target in [
+        "/uploads/../index.html",
+        "/uploads/%2e%2e/index.html",
+        "/uploads/..%2findex.html",
+        "/uploads/./../../secret.txt",
+    ] {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let request = format!(
+            "PUT {target} HTTP/1.1\r\nHost: localhost\r\nContent-Length: 6\r\nConnection: close\r\n\r\npwned!"
+        );
+        stream.write_all(request.as_bytes()).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(!response.starts_with("HTTP/1.1 2"), "{target}: {response}");
+    }
+    assert_eq!(
+        fs::read_to_string(root.join("index.html")).unwrap(),
+        "root index"
+    );
+    assert_eq!(fs::read_to_string(base.join("secret.txt")).unwrap(), SECRET);
+
+    // Paths that stay inside the prefix are still uploads
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let request = "PUT /uploads/./sub/../new.txt HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok";
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 2"), "{response}");
+    assert_eq!(
+        fs::read_to_string(root.join("uploads/new.txt")).unwrap(),
+        "ok"
+    );
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "tls")]
+
+use std::{
+    fs,
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
+    sync::Arc,
+    thread,
+};
+
+use http_1_server::{Server, tls};
+use rustls::{
+    ClientConfig, ClientConnection, RootCertStore, StreamOwned, pki_types::CertificateDer,
+};
+
+// A directory holding a static file plus a freshly generated self-signed certificate
+struct Fixture {
+    dir: PathBuf,
+    cert_der: CertificateDer<'static>,
+}
+
+fn fixture(name: &str) -> Fixture {
+    let dir =
+        std::env::temp_dir().join(format!("http-1-server-tls-{}-{}", name, std::process::id()));
+    fs::create_dir_all(dir.join("static")).unwrap();
+    fs::write(dir.join("static/test.txt"), b"Hello over TLS!").unwrap();
+
+    let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    fs::write(dir.join("cert.pem"), certified.cert.pem()).unwrap();
+    fs::write(dir.join("key.pem"), certified.key_pair.serialize_pem()).unwrap();
+
+    Fixture {
+        dir,
+        cert_der: certified.cert.der().clone(),
+    }
+}
+
+fn start_tls_server(fixture: &Fixture) -> SocketAddr {
+    let config =
+        tls::load_config(&fixture.dir.join("cert.pem"), &fixture.dir.join("key.pem")).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = Arc::new(Server::new(fixture.dir.join("static")));
+
+    thread::spawn(move || server.serve_tls(listener, config));
+
+    addr
+}
+
+// Connect with a client that trusts only the fixture's certificate
+fn connect(fixture: &Fixture, addr: SocketAddr) -> StreamOwned<ClientConnection, TcpStream> {
+    let mut roots = RootCertStore::empty();
+    roots.add(fixture.cert_der.clone()).unwrap();
+
+    let config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+    let conn = ClientConnection::new(Arc::new(config), "localhost".try_into().unwrap()).unwrap();
+
+    StreamOwned::new(conn, TcpStream::connect(addr).unwrap())
+}
+
+#[test]
+fn serves_static_file_over_tls() {
+    let fixture = fixture("serve");
+    let addr = start_tls_server(&fixture);
+
+    let mut stream = connect(&fixture, addr);
+    let request = "GET /test.txt HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+    stream.write_all(request.as_bytes()).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("Hello over TLS!"));
+}
+
+#[test]
+fn plaintext_client_is_rejected() {
+    let fixture = fixture("plaintext");
+    let addr = start_tls_server(&fixture);
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .write_all(b"GET /test.txt HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response);
+
+    assert!(!String::from_utf8_lossy(&response).contains("Hello over TLS!"));
+}
+
+#[test]
+fn missing_certificate_is_reported() {
+    let fixture = fixture("missing");
+
+    let err =
+        tls::load_config(&fixture.dir.join("nope.pem"), &fixture.dir.join("key.pem")).unwrap_err();
+
+    assert!(err.to_string().contains("nope.pem"));
+}
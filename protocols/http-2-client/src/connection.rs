@@ -0,0 +1,593 @@
+//! One connection carrying many requests at once: each goes out on a stream of its own,
+//! and a reader thread routes the frames that come back to whoever is waiting.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use toy_http2::hpack::{DEFAULT_TABLE_SIZE, Decoder, Encoder};
+use toy_http2::settings::ENABLE_PUSH;
+use toy_http2::{ErrorCode, Frame, PREFACE, Settings, Window};
+
+use crate::reader::FrameReader;
+use crate::{Error, Http2Response, Request};
+
+/// Largest stream id there is; a connection that has used it can take no more requests.
+const MAX_STREAM_ID: u32 = (1 << 31) - 1;
+
+/// A client connection to one server, shareable between threads.
+///
+/// Dropping it closes the connection, failing any response still outstanding.
+pub struct Connection {
+    inner: Arc<Mutex<Inner>>,
+    /// Used to shut the socket down, which ends the reader thread
+    socket: TcpStream,
+    authority: String,
+}
+
+/// A response still to come on one stream.
+pub struct ResponseHandle {
+    stream_id: u32,
+    rx: Receiver<Result<Http2Response, Error>>,
+}
+
+/// What the requesting threads and the reader thread share.
+struct Inner {
+    writer: TcpStream,
+    /// One per connection, as it carries a dynamic table across header blocks
+    encoder: Encoder,
+    /// Client streams have odd ids, each higher than the last
+    next_stream_id: u32,
+    /// What the server announced, applied as each SETTINGS frame arrives
+    remote: Settings,
+    /// How much more DATA the server may send across all streams
+    recv_window: Window,
+    /// How much more DATA we may send across all streams
+    send_window: Window,
+    streams: HashMap<u32, Stream>,
+    /// Set once no more requests can be made
+    closed: bool,
+}
+
+/// A request awaiting its response.
+struct Stream {
+    tx: Sender<Result<Http2Response, Error>>,
+    /// The fields of the response's header block, pseudo-headers included, once it has come
+    headers: Option<Vec<(String, String)>>,
+    body: Vec<u8>,
+    trailers: Vec<(String, String)>,
+    /// How much more DATA the server may send on this stream
+    recv_window: Window,
+    /// How much more DATA we may send on this stream
+    send_window: Window,
+    /// What is left of the request once its HEADERS are out
+    upload: Option<Upload>,
+}
+
+/// A request body and trailers, sent as windows allow.
+struct Upload {
+    body: Vec<u8>,
+    /// How much of the body has gone out
+    sent: usize,
+    trailers: Vec<(String, String)>,
+}
+
+/// A header block spread over CONTINUATION frames, until END_HEADERS.
+struct PartialBlock {
+    stream_id: u32,
+    end_stream: bool,
+    block: Vec<u8>,
+}
+
+impl Connection {
+    /// Connects and sends the preface, leaving a thread to read what the server sends.
+    pub fn connect(host: &str, port: u16) -> Result<Self, Error> {
+        let mut socket = TcpStream::connect((host, port))?;
+
+        // Apart from turning push off, the defaults are all this client needs
+        socket.write_all(PREFACE)?;
+        Frame::Settings {
+            ack: false,
+            parameters: vec![(ENABLE_PUSH, 0)],
+        }
+        .write_to(&mut socket)?;
+
+        let inner = Arc::new(Mutex::new(Inner {
+            writer: socket.try_clone()?,
+            encoder: Encoder::default(),
+            next_stream_id: 1,
+            remote: Settings::default(),
+            // The connection windows start at 65,535 whatever the SETTINGS say
+            recv_window: Window::new(Settings::default().initial_window_size),
+            send_window: Window::new(Settings::default().initial_window_size),
+            streams: HashMap::new(),
+            closed: false,
+        }));
+        let reader = FrameReader::new(socket.try_clone()?);
+        let shared = Arc::clone(&inner);
+        thread::spawn(move || read_frames(shared, reader));
+
+        let authority = match port {
+            80 => host.to_string(),
+            _ => format!("{host}:{port}"),
+        };
+        Ok(Self {
+            inner,
+            socket,
+            authority,
+        })
+    }
+
+    /// Sends a GET for `path` on a new stream, without waiting for the response.
+    pub fn get(&self, path: &str) -> Result<ResponseHandle, Error> {
+        self.send(Request::get(path))
+    }
+
+    /// Sends `request` on a new stream, without waiting for the response.
+    ///
+    /// Its body goes out as the server's flow-control windows allow, so this may return
+    /// while some of it is still to be sent.
+    pub fn send(&self, request: Request) -> Result<ResponseHandle, Error> {
+        let mut fields = vec![
+            (":method", request.method.as_str()),
+            (":scheme", "http"),
+            (":authority", self.authority.as_str()),
+            (":path", request.path.as_str()),
+        ];
+        if !request.headers.iter().any(|(name, _)| name == "user-agent") {
+            fields.push(("user-agent", "toy-client/0.1"));
+        }
+        fields.extend(
+            request
+                .headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        );
+
+        let mut inner = self.inner.lock().unwrap();
+        if inner.closed || inner.next_stream_id > MAX_STREAM_ID {
+            return Err(Error::Closed);
+        }
+        let stream_id = inner.next_stream_id;
+        inner.next_stream_id += 2;
+
+        // Without a body or trailers the HEADERS frame is the whole request
+        let end_stream = request.body.is_empty() && request.trailers.is_empty();
+        let upload = (!end_stream).then(|| Upload {
+            body: request.body.clone(),
+            sent: 0,
+            trailers: request.trailers.clone(),
+        });
+
+        // Registered first, as the response can come back before the lock is let go
+        let (tx, rx) = mpsc::channel();
+        let stream = Stream {
+            tx,
+            headers: None,
+            body: Vec::new(),
+            trailers: Vec::new(),
+            recv_window: Window::new(Settings::default().initial_window_size),
+            send_window: Window::new(inner.remote.initial_window_size),
+            upload,
+        };
+        inner.streams.insert(stream_id, stream);
+
+        // Blocks must be sent in the order they are encoded, so both happen under the lock
+        let block = inner.encoder.encode(fields);
+        let sent = inner
+            .send(Frame::Headers {
+                stream_id,
+                block,
+                priority: None,
+                end_stream,
+                end_headers: true,
+                padding: None,
+            })
+            .and_then(|()| inner.flush());
+        if let Err(e) = sent {
+            inner.streams.remove(&stream_id);
+            return Err(e.into());
+        }
+        Ok(ResponseHandle { stream_id, rx })
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.socket.shutdown(Shutdown::Both);
+    }
+}
+
+impl ResponseHandle {
+    pub fn stream_id(&self) -> u32 {
+        self.stream_id
+    }
+
+    /// Blocks until the whole response is in.
+    pub fn wait(self) -> Result<Http2Response, Error> {
+        self.rx.recv().unwrap_or(Err(Error::Closed))
+    }
+}
+
+/// Runs on the reader thread until the connection ends, then fails whatever is left.
+fn read_frames(inner: Arc<Mutex<Inner>>, mut reader: FrameReader<TcpStream>) {
+    let mut decoder = Decoder::default();
+    let mut partial = None;
+
+    let error = loop {
+        let frame = match reader.read_frame() {
+            Ok(Some(frame)) => frame,
+            Ok(None) => break Error::Closed,
+            Err(e) => break e,
+        };
+        let mut inner = inner.lock().unwrap();
+        if let Err(e) = inner.on_frame(frame, &mut decoder, &mut partial) {
+            break e;
+        }
+        // Whatever arrived may have opened windows
+        if let Err(e) = inner.flush() {
+            break e.into();
+        }
+    };
+
+    let mut inner = inner.lock().unwrap();
+    inner.closed = true;
+    for (_, stream) in inner.streams.drain() {
+        let _ = stream.tx.send(Err(duplicate(&error)));
+    }
+}
+
+/// A copy of `error` for each of the requests it fails.
+fn duplicate(error: &Error) -> Error {
+    match error {
+        Error::Io(e) => Error::Io(io::Error::new(e.kind(), e.to_string())),
+        Error::Closed => Error::Closed,
+        Error::Protocol(code, reason) => Error::Protocol(*code, reason),
+        Error::Reset(code) => Error::Reset(*code),
+        Error::GoAway(code) => Error::GoAway(*code),
+    }
+}
+
+/// Whether a response header block carries a 1xx status, which a final response follows.
+fn is_informational(headers: &[(String, String)]) -> bool {
+    headers
+        .iter()
+        .any(|(name, value)| name == ":status" && value.starts_with('1'))
+}
+
+impl Inner {
+    fn send(&mut self, frame: Frame) -> io::Result<()> {
+        frame.write_to(&mut self.writer)
+    }
+
+    /// Handles one frame; an error ends the whole connection.
+    fn on_frame(
+        &mut self,
+        frame: Frame,
+        decoder: &mut Decoder,
+        partial: &mut Option<PartialBlock>,
+    ) -> Result<(), Error> {
+        // A header block has to be finished before anything else is sent
+        if let Some(block) = partial
+            && !matches!(frame, Frame::Continuation { stream_id, .. } if stream_id == block.stream_id)
+        {
+            return Err(Error::Protocol(
+                ErrorCode::ProtocolError,
+                "header block interrupted before END_HEADERS",
+            ));
+        }
+
+        match frame {
+            Frame::Headers {
+                stream_id,
+                block,
+                end_stream,
+                end_headers,
+                ..
+            } => {
+                let block = PartialBlock {
+                    stream_id,
+                    end_stream,
+                    block,
+                };
+                self.on_block(block, end_headers, decoder, partial)?;
+            }
+            Frame::Continuation {
+                block, end_headers, ..
+            } => {
+                let Some(mut block_so_far) = partial.take() else {
+                    return Err(Error::Protocol(
+                        ErrorCode::ProtocolError,
+                        "CONTINUATION without HEADERS",
+                    ));
+                };
+                block_so_far.block.extend(block);
+                self.on_block(block_so_far, end_headers, decoder, partial)?;
+            }
+            Frame::Data {
+                stream_id,
+                data,
+                end_stream,
+                padding,
+            } => {
+                // Padding counts against the windows too
+                let len = data.len() + padding.map_or(0, |pad_len| 1 + pad_len as usize);
+                self.on_data(stream_id, data, len, end_stream)?;
+            }
+            Frame::Settings {
+                ack: false,
+                parameters,
+            } => self.on_settings(&parameters)?,
+            Frame::WindowUpdate {
+                stream_id,
+                increment,
+            } => self.on_window_update(stream_id, increment)?,
+            Frame::Ping { ack: false, data } => self.send(Frame::Ping { ack: true, data })?,
+            Frame::RstStream { stream_id, code } => {
+                if let Some(stream) = self.streams.remove(&stream_id) {
+                    let _ = stream.tx.send(Err(Error::Reset(code)));
+                }
+            }
+            Frame::GoAway {
+                last_stream_id,
+                code,
+                ..
+            } => {
+                // Streams up to `last_stream_id` may still be answered; later ones never will
+                self.closed = true;
+                let abandoned: Vec<u32> = self
+                    .streams
+                    .keys()
+                    .copied()
+                    .filter(|&id| id > last_stream_id)
+                    .collect();
+                for stream_id in abandoned {
+                    if let Some(stream) = self.streams.remove(&stream_id) {
+                        let _ = stream.tx.send(Err(Error::GoAway(code)));
+                    }
+                }
+            }
+            Frame::PushPromise { .. } => {
+                return Err(Error::Protocol(
+                    ErrorCode::ProtocolError,
+                    "PUSH_PROMISE with push disabled",
+                ));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Takes on the server's parameters, adjusting what depends on them, and acknowledges.
+    fn on_settings(&mut self, parameters: &[(u16, u32)]) -> Result<(), Error> {
+        let previous = self.remote;
+        self.remote
+            .apply(parameters)
+            .map_err(|code| Error::Protocol(code, "invalid SETTINGS"))?;
+        if self.remote.header_table_size != previous.header_table_size {
+            // We may use less than the server allows, which bounds our memory
+            let size = (self.remote.header_table_size as usize).min(DEFAULT_TABLE_SIZE);
+            self.encoder.set_max_table_size(size);
+        }
+        // Changes every stream's send window by the difference, which may overflow one
+        let delta = self.remote.initial_window_size as i64 - previous.initial_window_size as i64;
+        for stream in self.streams.values_mut() {
+            stream.send_window.grow(delta).map_err(|code| {
+                Error::Protocol(code, "SETTINGS_INITIAL_WINDOW_SIZE overflows a window")
+            })?;
+        }
+
+        self.send(Frame::Settings {
+            ack: true,
+            parameters: Vec::new(),
+        })?;
+        Ok(())
+    }
+
+    fn on_window_update(&mut self, stream_id: u32, increment: u32) -> Result<(), Error> {
+        if stream_id == 0 {
+            return self
+                .send_window
+                .grow(increment as i64)
+                .map_err(|code| Error::Protocol(code, "connection window overflow"));
+        }
+        // Updates can cross with the end of a stream, so ones for unknown streams are fine
+        let Some(stream) = self.streams.get_mut(&stream_id) else {
+            return Ok(());
+        };
+        if let Err(code) = stream.send_window.grow(increment as i64) {
+            if let Some(stream) = self.streams.remove(&stream_id) {
+                let _ = stream
+                    .tx
+                    .send(Err(Error::Protocol(code, "stream window overflow")));
+            }
+            self.send(Frame::RstStream { stream_id, code })?;
+        }
+        Ok(())
+    }
+
+    /// Holds on to a header block until END_HEADERS, then adds its fields to the response.
+    fn on_block(
+        &mut self,
+        block: PartialBlock,
+        end_headers: bool,
+        decoder: &mut Decoder,
+        partial: &mut Option<PartialBlock>,
+    ) -> Result<(), Error> {
+        if !end_headers {
+            *partial = Some(block);
+            return Ok(());
+        }
+
+        // Blocks for streams no one waits on still have to keep the tables in step
+        let fields = decoder.decode(&block.block).map_err(|_| {
+            Error::Protocol(ErrorCode::CompressionError, "undecodable header block")
+        })?;
+        if let Some(stream) = self.streams.get_mut(&block.stream_id) {
+            match &stream.headers {
+                // An informational (1xx) response is followed by the real one
+                Some(headers) if !is_informational(headers) => stream.trailers = fields,
+                _ => stream.headers = Some(fields),
+            }
+            if block.end_stream {
+                self.complete(block.stream_id)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn on_data(
+        &mut self,
+        stream_id: u32,
+        data: Vec<u8>,
+        len: usize,
+        end_stream: bool,
+    ) -> Result<(), Error> {
+        // DATA counts against the connection even when no one waits on the stream
+        let target = Settings::default().initial_window_size;
+        self.recv_window
+            .consume(len)
+            .map_err(|code| Error::Protocol(code, "DATA beyond the connection window"))?;
+        // Reopen the windows as bodies are taken in, so large ones keep coming
+        if let Some(increment) = self.recv_window.replenish(target) {
+            self.send(Frame::WindowUpdate {
+                stream_id: 0,
+                increment,
+            })?;
+        }
+
+        let Some(stream) = self.streams.get_mut(&stream_id) else {
+            return Ok(());
+        };
+        if let Err(code) = stream.recv_window.consume(len) {
+            // Only this stream is broken, so only its request fails
+            if let Some(stream) = self.streams.remove(&stream_id) {
+                let _ = stream
+                    .tx
+                    .send(Err(Error::Protocol(code, "DATA beyond the stream window")));
+            }
+            self.send(Frame::RstStream { stream_id, code })?;
+            return Ok(());
+        }
+        stream.body.extend(data);
+
+        if end_stream {
+            self.complete(stream_id)?;
+        } else if let Some(increment) = stream.recv_window.replenish(target) {
+            self.send(Frame::WindowUpdate {
+                stream_id,
+                increment,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Hands a finished response to whoever is waiting for it.
+    fn complete(&mut self, stream_id: u32) -> io::Result<()> {
+        let Some(mut stream) = self.streams.remove(&stream_id) else {
+            return Ok(());
+        };
+        let (pseudo, headers): (Vec<_>, Vec<_>) = stream
+            .headers
+            .take()
+            .unwrap_or_default()
+            .into_iter()
+            .partition(|(name, _)| name.starts_with(':'));
+        let status = pseudo
+            .into_iter()
+            .find(|(name, _)| name == ":status")
+            .and_then(|(_, status)| status.parse().ok());
+        let response = match status {
+            Some(status) => Ok(Http2Response {
+                status,
+                headers,
+                body: stream.body,
+                trailers: stream.trailers,
+            }),
+            None => Err(Error::Protocol(
+                ErrorCode::ProtocolError,
+                "response without a valid :status",
+            )),
+        };
+        let _ = stream.tx.send(response);
+
+        // The server answered before taking the whole request, so the rest is not needed
+        if stream.upload.is_some() {
+            self.send(Frame::RstStream {
+                stream_id,
+                code: ErrorCode::Cancel,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Sends request bodies a frame per stream at a time, so uploads share the connection,
+    /// followed by their trailers, until they are done or out of window.
+    fn flush(&mut self) -> io::Result<()> {
+        loop {
+            let mut progress = false;
+
+            for (&stream_id, stream) in &mut self.streams {
+                let Some(upload) = &mut stream.upload else {
+                    continue;
+                };
+                let left = upload.body.len() - upload.sent;
+                if left > 0 {
+                    let room = self
+                        .send_window
+                        .available()
+                        .min(stream.send_window.available())
+                        .min(self.remote.max_frame_size as usize);
+                    if room == 0 {
+                        continue;
+                    }
+
+                    let len = room.min(left);
+                    // Both hold at least `room`, so these cannot fail
+                    let _ = self.send_window.consume(len);
+                    let _ = stream.send_window.consume(len);
+                    let data = upload.body[upload.sent..upload.sent + len].to_vec();
+                    upload.sent += len;
+                    let done = upload.sent == upload.body.len();
+                    let frame = Frame::Data {
+                        stream_id,
+                        data,
+                        end_stream: done && upload.trailers.is_empty(),
+                        padding: None,
+                    };
+                    frame.write_to(&mut self.writer)?;
+                    progress = true;
+                    if !done {
+                        continue;
+                    }
+                }
+
+                // The body is all out, so the trailers end the stream if there are any
+                if !upload.trailers.is_empty() {
+                    let fields = upload
+                        .trailers
+                        .iter()
+                        .map(|(name, value)| (name.as_str(), value.as_str()));
+                    let frame = Frame::Headers {
+                        stream_id,
+                        block: self.encoder.encode(fields),
+                        priority: None,
+                        end_stream: true,
+                        end_headers: true,
+                        padding: None,
+                    };
+                    frame.write_to(&mut self.writer)?;
+                }
+                stream.upload = None;
+            }
+
+            if !progress {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+//! What can go wrong fetching a response over HTTP/2.
+
+use std::{fmt, io};
+
+use toy_http2::{ErrorCode, FrameError};
+
+/// Why a request did not produce a response.
+#[derive(Debug)]
+pub enum Error {
+    /// Connecting, sending or receiving failed.
+    Io(io::Error),
+    /// The server closed the connection before the response was complete.
+    Closed,
+    /// The server broke the protocol, so nothing more on the connection can be trusted.
+    Protocol(ErrorCode, &'static str),
+    /// The server reset the request's stream.
+    Reset(ErrorCode),
+    /// The server closed the connection with a GOAWAY before answering.
+    GoAway(ErrorCode),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Closed => write!(f, "connection closed before the response ended"),
+            Error::Protocol(code, reason) => write!(f, "protocol error ({code}): {reason}"),
+            Error::Reset(code) => write!(f, "stream reset by the server: {code}"),
+            Error::GoAway(code) => write!(f, "connection closed by the server: {code}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<FrameError> for Error {
+    fn from(e: FrameError) -> Self {
+        match e {
+            FrameError::Connection(code, reason) => Error::Protocol(code, reason),
+            FrameError::Stream(_, code) => Error::Protocol(code, "invalid frame on a stream"),
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! A toy HTTP/2 client: a [`Connection`] carries any number of requests at once, each
+//! answered through its own [`ResponseHandle`].
+
+mod connection;
+pub mod error;
+mod reader;
+pub mod request;
+
+pub use connection::{Connection, ResponseHandle};
+pub use error::Error;
+pub use request::Request;
+
+/// A complete response.
+pub struct Http2Response {
+    pub status: u16,
+    /// Header fields in the order they arrived, repeats included, without the
+    /// pseudo-headers.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Fields sent after the body, if the server sent any.
+    pub trailers: Vec<(String, String)>,
+}
+
+impl Http2Response {
+    /// The first value of the header `name`, which HTTP/2 sends in lowercase.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Sends `request`, or a GET for a path, over a connection of its own.
+pub fn send_http2_request(
+    host: &str,
+    port: u16,
+    request: impl Into<Request>,
+) -> Result<Http2Response, Error> {
+    Connection::connect(host, port)?
+        .send(request.into())?
+        .wait()
+}
@@ -0,0 +1,105 @@
+//! Frames read off a connection whichever way the reads happen to split them.
+
+use std::io::Read;
+
+use toy_http2::{Frame, Settings};
+
+use crate::Error;
+
+/// Buffers what has been read until it holds a whole frame, growing as large frames need.
+pub(crate) struct FrameReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    /// The largest frame we accept, as announced in our SETTINGS
+    max_frame_size: u32,
+}
+
+impl<R: Read> FrameReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(4096),
+            max_frame_size: Settings::default().max_frame_size,
+        }
+    }
+
+    /// Reads the next frame, or `None` if the server closed the connection between frames.
+    pub(crate) fn read_frame(&mut self) -> Result<Option<Frame>, Error> {
+        loop {
+            if let Some((frame, len)) = Frame::parse(&self.buf, self.max_frame_size)? {
+                // Drop what has been used, so the buffer only ever holds one frame and a bit
+                self.buf.drain(..len);
+                return Ok(Some(frame));
+            }
+
+            let mut chunk = [0u8; 4096];
+            let n = self.inner.read(&mut chunk)?;
+            if n == 0 {
+                return match self.buf.is_empty() {
+                    true => Ok(None),
+                    false => Err(Error::Closed),
+                };
+            }
+            self.buf.extend_from_slice(&chunk[..n]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hands out what it holds a few bytes at a time.
+    struct Trickle<'a>(&'a [u8], usize);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.1.min(self.0.len()).min(out.len());
+            out[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    fn data(stream_id: u32, len: usize) -> Frame {
+        Frame::Data {
+            stream_id,
+            data: vec![b'x'; len],
+            end_stream: false,
+            padding: None,
+        }
+    }
+
+    #[test]
+    fn frames_split_across_reads_are_put_back_together() {
+        let frames = [data(1, 10_000), data(3, 0), data(1, 16_384)];
+        let wire: Vec<u8> = frames.iter().flat_map(Frame::encode).collect();
+
+        for step in [1, 7, 4096] {
+            let mut reader = FrameReader::new(Trickle(&wire, step));
+            for frame in &frames {
+                assert_eq!(reader.read_frame().unwrap().as_ref(), Some(frame));
+            }
+            assert!(reader.read_frame().unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn closing_mid_frame_is_an_error() {
+        let wire = data(1, 100).encode();
+        let mut reader = FrameReader::new(Trickle(&wire[..50], 4096));
+
+        assert!(matches!(reader.read_frame(), Err(Error::Closed)));
+    }
+
+    #[test]
+    fn frames_beyond_the_limit_are_refused() {
+        let wire = data(1, 16_385).encode();
+        let mut reader = FrameReader::new(Trickle(&wire, 4096));
+
+        assert!(matches!(
+            reader.read_frame(),
+            Err(Error::Protocol(toy_http2::ErrorCode::FrameSizeError, _))
+        ));
+    }
+}
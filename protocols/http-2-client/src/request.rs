@@ -0,0 +1,68 @@
+//! Requests built up with chained calls.
+
+/// An HTTP/2 request to send with [`Connection::send`](crate::Connection::send).
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub(crate) method: String,
+    pub(crate) path: String,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: Vec<u8>,
+    pub(crate) trailers: Vec<(String, String)>,
+}
+
+impl Request {
+    /// A request with no headers or body for `path`. Methods are case-sensitive, so
+    /// `method` is sent as given.
+    pub fn new(method: &str, path: impl Into<String>) -> Self {
+        Self {
+            method: method.to_string(),
+            path: path.into(),
+            headers: Vec::new(),
+            body: Vec::new(),
+            trailers: Vec::new(),
+        }
+    }
+
+    /// A GET request for `path`.
+    pub fn get(path: impl Into<String>) -> Self {
+        Self::new("GET", path)
+    }
+
+    /// Adds a header; the name is sent lowercase, as HTTP/2 requires. A `user-agent` is
+    /// filled in unless set here.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers
+            .push((name.into().to_ascii_lowercase(), value.into()));
+        self
+    }
+
+    /// Uses `bytes` as the body, sent as DATA frames as the server's windows allow.
+    pub fn body(mut self, bytes: impl Into<Vec<u8>>) -> Self {
+        self.body = bytes.into();
+        self
+    }
+
+    /// Adds a trailer, sent in a header block of its own after the body.
+    pub fn trailer(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.trailers
+            .push((name.into().to_ascii_lowercase(), value.into()));
+        self
+    }
+
+    /// The method, sent as `:method`.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// The path and query, sent as `:path`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl From<&str> for Request {
+    /// A GET request for the path.
+    fn from(path: &str) -> Self {
+        Self::get(path)
+    }
+}
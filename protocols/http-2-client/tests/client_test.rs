@@ -1,16 +1,22 @@
-use std::{net::TcpListener, thread, time::Duration};
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    sync::Arc,
+    thread,
+    time::Duration,
+};
 
-use toy_http2::{client, handle_connection};
+use http_2_client::send_http2_request;
+use http_2_server::{Response, Server, handle_connection};
+use toy_http2::{Frame, PREFACE, hpack::Encoder};
 
 fn start_server() -> u16 {
     let listener = TcpListener::bind("127.0.0.1:0").unwrap(); // random port
     let port = listener.local_addr().unwrap().port();
 
     thread::spawn(move || {
-        for stream in listener.incoming() {
-            if let Ok(mut stream) = stream {
-                toy_http2::handle_connection(&mut stream);
-            }
+        for stream in listener.incoming().flatten() {
+            handle_connection(&stream);
         }
     });
 
@@ -22,9 +28,108 @@ fn test_client_receives_headers_and_body() {
     let port = start_server();
     std::thread::sleep(Duration::from_millis(100));
 
-    let response = client::send_http2_request("127.0.0.1", port, "/").unwrap();
+    let response = send_http2_request("127.0.0.1", port, "/").unwrap();
+
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("content-type").unwrap(), "text/plain");
+    assert_eq!(response.body, b"Hello from toy HTTP/2 server!");
+}
+
+#[test]
+fn large_binary_bodies_arrive_whole() {
+    // Every byte value, repeated well past the windows and the frame size
+    let body: Vec<u8> = (0..=255u8).cycle().take(300_000).collect();
+    let expected = body.clone();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = Arc::new(Server::new(move |_| Response::new(200).body(body.clone())));
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let server = Arc::clone(&server);
+            thread::spawn(move || server.handle_connection(&stream));
+        }
+    });
+
+    let response = send_http2_request("127.0.0.1", port, "/big").unwrap();
+
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body.len(), expected.len());
+    assert!(response.body == expected);
+}
+
+#[test]
+fn repeated_fields_and_trailers_are_kept_apart() {
+    // The toy server sends neither, so this one writes the frames itself
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        // Answer once the request's HEADERS are in, so the client is waiting for stream 1
+        let mut buf = Vec::new();
+        let mut at = PREFACE.len();
+        'request: loop {
+            let mut chunk = [0; 4096];
+            let n = stream.read(&mut chunk).unwrap();
+            buf.extend_from_slice(&chunk[..n]);
+            while buf.len() > at {
+                let Some((frame, len)) = Frame::parse(&buf[at..], 1 << 14).unwrap() else {
+                    break;
+                };
+                at += len;
+                if matches!(frame, Frame::Headers { .. }) {
+                    break 'request;
+                }
+            }
+        }
+
+        let mut encoder = Encoder::default();
+        let headers = |block, end_stream| Frame::Headers {
+            stream_id: 1,
+            block,
+            priority: None,
+            end_stream,
+            end_headers: true,
+            padding: None,
+        };
+        let frames = [
+            Frame::Settings {
+                ack: false,
+                parameters: Vec::new(),
+            },
+            headers(
+                encoder.encode([
+                    (":status", "200"),
+                    ("set-cookie", "a=1"),
+                    ("set-cookie", "b=2"),
+                ]),
+                false,
+            ),
+            Frame::Data {
+                stream_id: 1,
+                data: b"hi".to_vec(),
+                end_stream: false,
+                padding: None,
+            },
+            headers(encoder.encode([("x-checksum", "abc")]), true),
+        ];
+        for frame in frames {
+            frame.write_to(&mut stream).unwrap();
+        }
+        stream.flush().unwrap();
+        // Unread bytes would turn the close into a reset
+        while stream.read(&mut [0; 1024]).is_ok_and(|n| n > 0) {}
+    });
+
+    let response = send_http2_request("127.0.0.1", port, "/").unwrap();
 
-    assert_eq!(response.headers.get(":status").unwrap(), "200");
-    assert_eq!(response.headers.get("content-type").unwrap(), "text/plain");
-    assert_eq!(response.body, "Hello from toy HTTP/2 server!");
+    assert_eq!(response.status, 200);
+    let field = |name: &str, value: &str| (name.to_string(), value.to_string());
+    assert_eq!(
+        response.headers,
+        [field("set-cookie", "a=1"), field("set-cookie", "b=2")]
+    );
+    assert_eq!(response.header("set-cookie"), Some("a=1"));
+    assert_eq!(response.body, b"hi");
+    assert_eq!(response.trailers, [field("x-checksum", "abc")]);
 }
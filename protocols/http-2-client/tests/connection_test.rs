@@ -0,0 +1,136 @@
+use std::{
+    io::Read,
+    net::TcpListener,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use http_2_client::{Connection, Error};
+use http_2_server::{Request, Response, Server};
+use toy_http2::ErrorCode;
+
+const SLOW: Duration = Duration::from_millis(300);
+
+// "/slow" takes a while; everything is answered with its path
+fn handler(request: &Request) -> Response {
+    if request.path == "/slow" {
+        thread::sleep(SLOW);
+    }
+    Response::new(200).body(request.path.clone())
+}
+
+fn start_server(server: Server) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap(); // random port
+    let port = listener.local_addr().unwrap().port();
+    let server = Arc::new(server);
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let server = Arc::clone(&server);
+            thread::spawn(move || server.handle_connection(&stream));
+        }
+    });
+
+    port
+}
+
+#[test]
+fn requests_use_rising_odd_stream_ids() {
+    let port = start_server(Server::new(handler));
+    let connection = Connection::connect("127.0.0.1", port).unwrap();
+
+    for (path, stream_id) in [("/a", 1), ("/b", 3), ("/c", 5)] {
+        let handle = connection.get(path).unwrap();
+        assert_eq!(handle.stream_id(), stream_id);
+        assert_eq!(handle.wait().unwrap().body, path.as_bytes());
+    }
+}
+
+#[test]
+fn requests_in_flight_together_are_answered_separately() {
+    let port = start_server(Server::new(handler));
+    let connection = Connection::connect("127.0.0.1", port).unwrap();
+
+    // All of them wait at once, so together they take about as long as one
+    let started = Instant::now();
+    let handles: Vec<_> = (0..10)
+        .map(|i| {
+            let path = if i % 2 == 0 { "/slow" } else { "/fast" };
+            (path, connection.get(path).unwrap())
+        })
+        .collect();
+    for (path, handle) in handles {
+        let response = handle.wait().unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, path.as_bytes());
+    }
+    assert!(started.elapsed() < SLOW * 3, "{:?}", started.elapsed());
+}
+
+#[test]
+fn fast_responses_overtake_slow_ones() {
+    let port = start_server(Server::new(handler));
+    let connection = Connection::connect("127.0.0.1", port).unwrap();
+
+    let slow = connection.get("/slow").unwrap();
+    let fast = connection.get("/fast").unwrap();
+
+    let started = Instant::now();
+    assert_eq!(fast.wait().unwrap().body, b"/fast");
+    assert!(started.elapsed() < SLOW);
+    assert_eq!(slow.wait().unwrap().body, b"/slow");
+}
+
+#[test]
+fn threads_can_share_a_connection() {
+    let port = start_server(Server::new(handler));
+    let connection = Connection::connect("127.0.0.1", port).unwrap();
+
+    thread::scope(|scope| {
+        for i in 0..8 {
+            let connection = &connection;
+            scope.spawn(move || {
+                let path = format!("/thread/{i}");
+                let response = connection.get(&path).unwrap().wait().unwrap();
+                assert_eq!(response.body, path.as_bytes());
+            });
+        }
+    });
+}
+
+#[test]
+fn refused_streams_fail_only_their_request() {
+    let port = start_server(Server::new(handler).max_concurrent_streams(1));
+    let connection = Connection::connect("127.0.0.1", port).unwrap();
+
+    let slow = connection.get("/slow").unwrap();
+    let refused = connection.get("/fast").unwrap();
+
+    assert!(matches!(
+        refused.wait(),
+        Err(Error::Reset(ErrorCode::RefusedStream))
+    ));
+    assert_eq!(slow.wait().unwrap().body, b"/slow");
+}
+
+#[test]
+fn outstanding_requests_fail_when_the_server_goes() {
+    // A server that takes the preface and then hangs up
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        // Unread bytes would turn the close into a reset
+        stream
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        while stream.read(&mut [0; 1024]).is_ok_and(|n| n > 0) {}
+    });
+
+    let connection = Connection::connect("127.0.0.1", port).unwrap();
+    let handle = connection.get("/").unwrap();
+
+    assert!(matches!(handle.wait(), Err(Error::Closed)));
+    assert!(matches!(connection.get("/"), Err(Error::Closed)));
+}
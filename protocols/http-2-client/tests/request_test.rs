@@ -0,0 +1,135 @@
+use std::{net::TcpListener, sync::Arc, thread};
+
+use http_2_client::{Connection, Request, send_http2_request};
+use http_2_server::{Response, Server};
+
+// "/echo" sends the body back, "/missing" is not found, and anything else describes the
+// request: its method, user agent, custom header and trailers, a line each
+fn handler(request: &http_2_server::Request) -> Response {
+    let header = |name: &str| request.header(name).unwrap_or("-").to_string();
+    match request.path.as_str() {
+        "/echo" => Response::new(200).body(request.body.clone()),
+        "/missing" => Response::new(404),
+        _ => {
+            let trailers: Vec<String> = request
+                .trailers
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect();
+            let description = [
+                request.method.clone(),
+                header("user-agent"),
+                header("x-custom"),
+                trailers.join(","),
+                request.body.len().to_string(),
+            ];
+            Response::new(200).body(description.join("\n"))
+        }
+    }
+}
+
+fn start_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap(); // random port
+    let port = listener.local_addr().unwrap().port();
+    let server = Arc::new(Server::new(handler));
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let server = Arc::clone(&server);
+            thread::spawn(move || server.handle_connection(&stream));
+        }
+    });
+
+    port
+}
+
+fn describe(connection: &Connection, request: Request) -> Vec<String> {
+    let response = connection.send(request).unwrap().wait().unwrap();
+    assert_eq!(response.status, 200);
+    String::from_utf8(response.body)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn any_method_and_headers_can_be_sent() {
+    let port = start_server();
+    let connection = Connection::connect("127.0.0.1", port).unwrap();
+
+    // Methods are case-sensitive, so they go out exactly as given
+    let request = Request::new("delete", "/item").header("X-Custom", "yes");
+    assert_eq!(
+        describe(&connection, request),
+        ["delete", "toy-client/0.1", "yes", "", "0"]
+    );
+
+    // A user agent given by the caller replaces the default one
+    let request = Request::new("OPTIONS", "/").header("User-Agent", "tester/2");
+    assert_eq!(
+        describe(&connection, request),
+        ["OPTIONS", "tester/2", "-", "", "0"]
+    );
+}
+
+#[test]
+fn status_is_typed() {
+    let port = start_server();
+
+    let response = send_http2_request("127.0.0.1", port, "/missing").unwrap();
+    assert_eq!(response.status, 404);
+    assert!(!response.headers.iter().any(|(name, _)| name == ":status"));
+
+    let response = send_http2_request("127.0.0.1", port, Request::get("/echo")).unwrap();
+    assert_eq!(response.status, 200);
+}
+
+#[test]
+fn bodies_larger_than_the_windows_are_sent_whole() {
+    let port = start_server();
+    let connection = Connection::connect("127.0.0.1", port).unwrap();
+
+    // Several times the 65,535-byte windows, in frames no larger than 16 KB
+    let body: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+    let handles: Vec<_> = (0..3)
+        .map(|_| {
+            let request = Request::new("POST", "/echo").body(body.clone());
+            connection.send(request).unwrap()
+        })
+        .collect();
+
+    for handle in handles {
+        let response = handle.wait().unwrap();
+        assert_eq!(response.status, 200);
+        assert!(response.body == body, "body differs");
+    }
+}
+
+#[test]
+fn trailers_follow_the_body() {
+    let port = start_server();
+    let connection = Connection::connect("127.0.0.1", port).unwrap();
+
+    let request = Request::new("PUT", "/upload")
+        .body(vec![b'x'; 70_000])
+        .trailer("X-Checksum", "abc")
+        .trailer("x-count", "1");
+    assert_eq!(
+        describe(&connection, request),
+        [
+            "PUT",
+            "toy-client/0.1",
+            "-",
+            "x-checksum=abc,x-count=1",
+            "70000"
+        ]
+    );
+
+    // Without a body the trailers come straight after the headers
+    let request = Request::new("POST", "/").trailer("x-only", "trailers");
+    assert_eq!(
+        describe(&connection, request),
+        ["POST", "toy-client/0.1", "-", "x-only=trailers", "0"]
+    );
+}
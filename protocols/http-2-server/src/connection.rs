@@ -0,0 +1,1006 @@
+//! One HTTP/2 connection: frames are read on the caller's thread, each complete request
+//! goes to the handler on a thread of its own, and all state lives behind one lock that
+//! every frame is written under.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::iter;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::{fmt, thread};
+
+use bytes::{Buf, BytesMut};
+use toy_http2::frame::FRAME_HEADER_LEN;
+use toy_http2::hpack::{DEFAULT_TABLE_SIZE, Decoder, Encoder};
+use toy_http2::{ErrorCode, Frame, FrameError, PREFACE, Settings, Window};
+
+use crate::response::Body;
+use crate::{ConnectionOptions, Request, Response, Server, Transport, Upgrade};
+
+/// Stream ids are 31 bits.
+const MAX_STREAM_ID: u32 = (1 << 31) - 1;
+
+/// Why a connection ended early, or a stream did.
+#[derive(Debug)]
+enum Error {
+    Io(io::Error),
+    /// The client broke the protocol; answered with a GOAWAY carrying the code.
+    Connection(ErrorCode, &'static str),
+    /// The client broke the protocol on one stream; answered with a RST_STREAM.
+    Stream(u32, ErrorCode),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Connection(code, reason) => write!(f, "{code}: {reason}"),
+            Error::Stream(stream_id, code) => write!(f, "{code} on stream {stream_id}"),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<FrameError> for Error {
+    fn from(e: FrameError) -> Self {
+        match e {
+            FrameError::Connection(code, reason) => Error::Connection(code, reason),
+            FrameError::Stream(stream_id, code) => Error::Stream(stream_id, code),
+        }
+    }
+}
+
+/// A header block still waiting for CONTINUATION frames.
+struct PartialBlock {
+    stream_id: u32,
+    end_stream: bool,
+    block: Vec<u8>,
+}
+
+/// Where a stream is in its lifecycle (RFC 9113 section 5.1).
+///
+/// Streams above the highest id the client has used, or that we have promised, are idle,
+/// and those below it that are no longer tracked are closed. Pushed streams skip open:
+/// they are half-closed (remote) from their PUSH_PROMISE on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamState {
+    Open,
+    /// The client has sent END_STREAM; the response is still going out.
+    HalfClosedRemote,
+}
+
+struct Stream {
+    state: StreamState,
+    /// The request so far, until it is complete and goes to the handler
+    request: Option<Request>,
+    /// How much more DATA the client may send on this stream
+    recv_window: Window,
+    /// How much more DATA we may send on this stream
+    send_window: Window,
+    /// Response body still to send, once the response has started
+    pending: Option<Body>,
+}
+
+/// Everything the reading thread and the handler threads share.
+struct Connection<'a, S> {
+    writer: &'a S,
+    options: &'a ConnectionOptions,
+    /// What we announced in our SETTINGS frame
+    local: Settings,
+    /// Largest request body a handler will be given
+    max_body_size: usize,
+    /// What the client announced, applied as each SETTINGS frame arrives
+    remote: Settings,
+    // One of each per connection, as both carry a dynamic table across header blocks
+    decoder: Decoder,
+    encoder: Encoder,
+    /// How much more DATA the client may send across all streams
+    recv_window: Window,
+    /// How much more DATA we may send across all streams
+    send_window: Window,
+    /// Highest stream id the client has opened, reported in GOAWAY
+    last_stream_id: u32,
+    /// Id for the next pushed stream, which are even
+    next_push_id: u32,
+    /// When our unacknowledged PING went out, and what it carried
+    ping_sent: Option<(Instant, [u8; 8])>,
+    /// Until it is complete no other frame may arrive
+    partial_block: Option<PartialBlock>,
+    /// Streams that are open on at least one side, in id order
+    streams: BTreeMap<u32, Stream>,
+    /// Set once the connection is ending, after which nothing more is written
+    closed: bool,
+}
+
+/// The reading half: the caller's thread owns it and never holds the lock while reading.
+struct Reader<'a, S> {
+    stream: &'a S,
+    buf: BytesMut,
+    max_frame_size: u32,
+}
+
+pub(crate) fn serve<S>(
+    server: &Server,
+    options: &ConnectionOptions,
+    stream: &S,
+    buffered: &[u8],
+    upgrade: Option<Upgrade>,
+) where
+    S: Transport,
+    for<'a> &'a S: Read + Write,
+{
+    let local = Settings {
+        max_concurrent_streams: Some(server.max_concurrent_streams),
+        initial_window_size: server.initial_window_size,
+        max_frame_size: server.max_frame_size,
+        ..Settings::default()
+    };
+    let connection = Mutex::new(Connection {
+        writer: stream,
+        options,
+        local,
+        max_body_size: server.max_body_size,
+        remote: Settings::default(),
+        decoder: Decoder::default(),
+        encoder: Encoder::default(),
+        // The connection windows start at 65,535 whatever the SETTINGS say
+        recv_window: Window::new(Settings::default().initial_window_size),
+        send_window: Window::new(Settings::default().initial_window_size),
+        last_stream_id: 0,
+        next_push_id: 2,
+        ping_sent: None,
+        partial_block: None,
+        streams: BTreeMap::new(),
+        closed: false,
+    });
+    let mut reader = Reader {
+        stream,
+        buf: BytesMut::with_capacity(4096),
+        max_frame_size: local.max_frame_size,
+    };
+    reader.buf.extend_from_slice(buffered);
+
+    // A client that stops reading would otherwise hold the connection lock in a write
+    // forever, where the PINGs cannot get past it
+    if let Some((_, timeout)) = options.keepalive {
+        stream.set_write_timeout(timeout);
+    }
+
+    // Handler threads borrow the connection, so it outlives all of them
+    thread::scope(|scope| {
+        let pinger = options.keepalive.map(|(interval, timeout)| {
+            let connection = &connection;
+            scope.spawn(move || keep_alive(connection, stream, interval, timeout))
+        });
+        let result = run(&connection, &mut reader, upgrade, |stream_id, request| {
+            let connection = &connection;
+            scope.spawn(move || respond(server, connection, stream_id, request));
+        });
+
+        let mut connection = connection.lock().unwrap();
+        if let Err(e) = result {
+            println!("Connection error: {}", e);
+            if let Error::Connection(code, reason) = e {
+                let _ = connection.go_away(code, reason);
+            }
+        }
+        connection.closed = true;
+        if let Some(pinger) = pinger {
+            pinger.thread().unpark();
+        }
+    });
+}
+
+/// Reads and handles frames until the client leaves, calling `spawn` for every request
+/// that is complete.
+fn run<S>(
+    connection: &Mutex<Connection<'_, S>>,
+    reader: &mut Reader<'_, S>,
+    upgrade: Option<Upgrade>,
+    mut spawn: impl FnMut(u32, Request),
+) -> Result<(), Error>
+where
+    for<'a> &'a S: Read + Write,
+{
+    if !reader.read_preface()? {
+        return Ok(());
+    }
+    connection.lock().unwrap().send_settings()?;
+
+    if let Some(upgrade) = upgrade {
+        // The 101 response already acknowledged the settings, and the request came whole
+        let mut connection = connection.lock().unwrap();
+        connection.apply_settings(&upgrade.settings)?;
+        connection.open_upgraded();
+        spawn(1, upgrade.request);
+    }
+
+    // The preface is only complete with the client's SETTINGS frame
+    let mut first = true;
+    loop {
+        let frame = match reader.read_frame() {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return Ok(()),
+            // A frame that is wrong for its stream alone still leaves the others readable
+            Err(Error::Stream(stream_id, code)) => {
+                connection.lock().unwrap().reset(stream_id, code)?;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        if first && !matches!(frame, Frame::Settings { ack: false, .. }) {
+            return Err(Error::Connection(
+                ErrorCode::ProtocolError,
+                "expected SETTINGS after the preface",
+            ));
+        }
+        first = false;
+        if let Frame::GoAway { code, .. } = frame {
+            println!("Client is going away: {}", code);
+            return Ok(());
+        }
+
+        let mut connection = connection.lock().unwrap();
+        match connection.on_frame(frame) {
+            Ok(Some((stream_id, request))) => spawn(stream_id, request),
+            Ok(None) => {}
+            Err(Error::Stream(stream_id, code)) => connection.reset(stream_id, code)?,
+            Err(e) => return Err(e),
+        }
+        // Whatever arrived may have opened windows
+        connection.flush()?;
+    }
+}
+
+/// Runs the handler for one request, then queues the response and sends what it can,
+/// followed by any responses it pushed.
+fn respond<S>(
+    server: &Server,
+    connection: &Mutex<Connection<'_, S>>,
+    stream_id: u32,
+    request: Request,
+) where
+    S: Transport,
+    for<'a> &'a S: Write,
+{
+    let mut response = call_handler(server, stream_id, &request);
+    let pushes = std::mem::take(&mut response.pushes);
+
+    let promised = {
+        let mut connection = connection.lock().unwrap();
+        // Promises go first, so the client does not request what the response refers to
+        let sent = connection
+            .promise(stream_id, &request, &pushes)
+            .and_then(|promised| {
+                connection.start_response(stream_id, response)?;
+                connection.flush()?;
+                Ok(promised)
+            });
+        match sent {
+            Ok(promised) => promised,
+            Err(e) => {
+                println!("[Stream {}] Failed to respond: {}", stream_id, e);
+                connection.abandon();
+                return;
+            }
+        }
+    };
+
+    for (promised_id, request) in promised {
+        // Pushed responses cannot push in turn, as only the client's streams carry promises
+        let response = call_handler(server, promised_id, &request);
+        let mut connection = connection.lock().unwrap();
+        let sent = connection
+            .start_response(promised_id, response)
+            .and_then(|()| connection.flush());
+        if let Err(e) = sent {
+            println!("[Stream {}] Failed to respond: {}", promised_id, e);
+            connection.abandon();
+            return;
+        }
+    }
+}
+
+/// Calls the handler, turning a panic into a 500.
+fn call_handler(server: &Server, stream_id: u32, request: &Request) -> Response {
+    panic::catch_unwind(AssertUnwindSafe(|| (server.handler)(request))).unwrap_or_else(|_| {
+        println!("[Stream {}] Handler panicked", stream_id);
+        Response::new(500)
+    })
+}
+
+/// PINGs the client every `interval`, and cuts the connection off if an acknowledgement
+/// takes longer than `timeout`; runs until the connection ends and unparks it.
+fn keep_alive<S>(
+    connection: &Mutex<Connection<'_, S>>,
+    stream: &S,
+    interval: Duration,
+    timeout: Duration,
+) where
+    S: Transport,
+    for<'a> &'a S: Write,
+{
+    let mut next_ping = Instant::now() + interval;
+    let mut count = 0u64;
+    loop {
+        let now = Instant::now();
+        let mut connection = connection.lock().unwrap();
+        if connection.closed {
+            return;
+        }
+
+        let wake = match connection.ping_sent {
+            Some((sent, _)) if now >= sent + timeout => {
+                // Not even GOAWAY, as writing to a client that is gone may block
+                println!("Connection dead: no PING acknowledgement in {:?}", timeout);
+                connection.closed = true;
+                stream.shutdown();
+                return;
+            }
+            // Only one PING is outstanding at a time
+            Some((sent, _)) => (sent + timeout).min(next_ping.max(now)),
+            None if now >= next_ping => {
+                count += 1;
+                let data = count.to_be_bytes();
+                if connection.send(Frame::Ping { ack: false, data }).is_err() {
+                    connection.abandon();
+                    return;
+                }
+                connection.ping_sent = Some((now, data));
+                next_ping = now + interval;
+                now + timeout.min(interval)
+            }
+            None => next_ping,
+        };
+        drop(connection);
+        thread::park_timeout(wake.saturating_duration_since(Instant::now()));
+    }
+}
+
+impl<S> Reader<'_, S>
+where
+    for<'a> &'a S: Read,
+{
+    /// Reads the client preface, returning false if the client left before sending it.
+    fn read_preface(&mut self) -> Result<bool, Error> {
+        loop {
+            let seen = self.buf.len().min(PREFACE.len());
+            // Fail as soon as it differs, e.g. on an HTTP/1.1 request line
+            if self.buf[..seen] != PREFACE[..seen] {
+                return Err(Error::Connection(
+                    ErrorCode::ProtocolError,
+                    "invalid connection preface",
+                ));
+            }
+            if seen == PREFACE.len() {
+                self.buf.advance(seen);
+                return Ok(true);
+            }
+            if !self.fill()? {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Reads the next frame, or `None` once the client closes the connection.
+    fn read_frame(&mut self) -> Result<Option<Frame>, Error> {
+        loop {
+            match Frame::parse(&self.buf, self.max_frame_size) {
+                Ok(Some((frame, len))) => {
+                    self.buf.advance(len);
+                    return Ok(Some(frame));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    // Skip the frame, as the connection carries on after a stream error
+                    let len = ((self.buf[0] as usize) << 16)
+                        | ((self.buf[1] as usize) << 8)
+                        | (self.buf[2] as usize);
+                    self.buf
+                        .advance((FRAME_HEADER_LEN + len).min(self.buf.len()));
+                    return Err(e.into());
+                }
+            }
+            if !self.fill()? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Reads more bytes into the buffer, returning false once the client has closed.
+    fn fill(&mut self) -> Result<bool, Error> {
+        let mut tmp = [0u8; 4096];
+        let bytes_read = (&mut self.stream).read(&mut tmp)?;
+        self.buf.extend_from_slice(&tmp[..bytes_read]);
+        Ok(bytes_read > 0)
+    }
+}
+
+impl<S: Transport> Connection<'_, S> {
+    /// Gives up on the connection after a failed write, which may have left part of a frame
+    /// behind. Cutting it off also stops the thread reading from it.
+    fn abandon(&mut self) {
+        self.closed = true;
+        self.writer.shutdown();
+    }
+}
+
+impl<S> Connection<'_, S>
+where
+    for<'a> &'a S: Write,
+{
+    fn send(&mut self, frame: Frame) -> io::Result<()> {
+        frame.write_to(&mut self.writer)
+    }
+
+    fn send_settings(&mut self) -> io::Result<()> {
+        let parameters = self.local.parameters();
+        self.send(Frame::Settings {
+            ack: false,
+            parameters,
+        })
+    }
+
+    /// Handles one frame, returning the request it completed, if any.
+    fn on_frame(&mut self, frame: Frame) -> Result<Option<(u32, Request)>, Error> {
+        // A header block has to be finished before anything else is sent
+        if let Some(partial) = &self.partial_block
+            && !matches!(frame, Frame::Continuation { stream_id, .. } if stream_id == partial.stream_id)
+        {
+            return Err(Error::Connection(
+                ErrorCode::ProtocolError,
+                "header block interrupted before END_HEADERS",
+            ));
+        }
+
+        match frame {
+            Frame::Settings { ack, parameters } => self.on_settings(ack, &parameters)?,
+            Frame::Headers {
+                stream_id,
+                block,
+                end_stream,
+                end_headers,
+                ..
+            } => {
+                let partial = PartialBlock {
+                    stream_id,
+                    end_stream,
+                    block,
+                };
+                return self.on_block(partial, end_headers);
+            }
+            Frame::Continuation {
+                block, end_headers, ..
+            } => {
+                let Some(mut partial) = self.partial_block.take() else {
+                    return Err(Error::Connection(
+                        ErrorCode::ProtocolError,
+                        "CONTINUATION without HEADERS",
+                    ));
+                };
+                partial.block.extend(block);
+                return self.on_block(partial, end_headers);
+            }
+            Frame::Data {
+                stream_id,
+                data,
+                end_stream,
+                padding,
+            } => {
+                // Padding counts against the windows too
+                let len = data.len() + padding.map_or(0, |pad_len| 1 + pad_len as usize);
+                return self.on_data(stream_id, &data, len, end_stream);
+            }
+            Frame::WindowUpdate {
+                stream_id,
+                increment,
+            } => self.on_window_update(stream_id, increment)?,
+            Frame::RstStream { stream_id, code } => self.on_rst_stream(stream_id, code)?,
+            Frame::Ping { ack: false, data } => self.send(Frame::Ping { ack: true, data })?,
+            Frame::Ping { ack: true, data } => self.on_ping_ack(data),
+            Frame::PushPromise { .. } => {
+                return Err(Error::Connection(
+                    ErrorCode::ProtocolError,
+                    "PUSH_PROMISE from a client",
+                ));
+            }
+            // Priorities are advisory
+            Frame::Priority { .. } => {}
+            // Handled before the lock is taken
+            Frame::GoAway { .. } => {}
+            Frame::Unknown {
+                frame_type,
+                stream_id,
+                ..
+            } => {
+                // Unknown frame types must be ignored
+                println!("[Stream {}] Unknown frame type: {}", stream_id, frame_type);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Measures the round trip of our outstanding PING, if this acknowledges it.
+    fn on_ping_ack(&mut self, data: [u8; 8]) {
+        let Some((sent, payload)) = self.ping_sent else {
+            return;
+        };
+        if payload != data {
+            return;
+        }
+        self.ping_sent = None;
+        let rtt = sent.elapsed();
+        println!("PING round trip: {:?}", rtt);
+        if let Some(on_rtt) = &self.options.on_rtt {
+            on_rtt(rtt);
+        }
+    }
+
+    fn on_settings(&mut self, ack: bool, parameters: &[(u16, u32)]) -> Result<(), Error> {
+        if ack {
+            // The client now holds to what we announced
+            self.decoder
+                .set_max_table_size(self.local.header_table_size as usize);
+            return Ok(());
+        }
+
+        self.apply_settings(parameters)?;
+        self.send(Frame::Settings {
+            ack: true,
+            parameters: Vec::new(),
+        })?;
+        Ok(())
+    }
+
+    /// Takes on the client's parameters, adjusting what depends on them.
+    fn apply_settings(&mut self, parameters: &[(u16, u32)]) -> Result<(), Error> {
+        let previous = self.remote;
+        self.remote
+            .apply(parameters)
+            .map_err(|code| Error::Connection(code, "invalid SETTINGS"))?;
+        if self.remote.header_table_size != previous.header_table_size {
+            // We may use less than the client allows, which bounds our memory
+            let size = (self.remote.header_table_size as usize).min(DEFAULT_TABLE_SIZE);
+            self.encoder.set_max_table_size(size);
+        }
+        // Changes every stream's send window by the difference, which may overflow one
+        let delta = self.remote.initial_window_size as i64 - previous.initial_window_size as i64;
+        for stream in self.streams.values_mut() {
+            stream.send_window.grow(delta).map_err(|code| {
+                Error::Connection(code, "SETTINGS_INITIAL_WINDOW_SIZE overflows a window")
+            })?;
+        }
+        println!("Client SETTINGS: {:?}", self.remote);
+        Ok(())
+    }
+
+    /// Opens stream 1 for a request upgraded from HTTP/1.1, which needs only its response.
+    fn open_upgraded(&mut self) {
+        self.last_stream_id = 1;
+        self.streams.insert(
+            1,
+            Stream {
+                state: StreamState::HalfClosedRemote,
+                request: None,
+                recv_window: Window::new(self.local.initial_window_size),
+                send_window: Window::new(self.remote.initial_window_size),
+                pending: None,
+            },
+        );
+    }
+
+    /// Holds on to a header block until END_HEADERS, then handles it as HEADERS would be.
+    fn on_block(
+        &mut self,
+        partial: PartialBlock,
+        end_headers: bool,
+    ) -> Result<Option<(u32, Request)>, Error> {
+        if !end_headers {
+            self.partial_block = Some(partial);
+            return Ok(None);
+        }
+        let PartialBlock {
+            stream_id,
+            end_stream,
+            block,
+        } = partial;
+
+        // The tables are out of step after a bad block, so nothing later can be decoded
+        let headers = self.decoder.decode(&block).map_err(|_| {
+            Error::Connection(ErrorCode::CompressionError, "undecodable header block")
+        })?;
+
+        if let Some(stream) = self.streams.get_mut(&stream_id) {
+            // A second block carries the request trailers and must end the stream
+            if stream.state == StreamState::HalfClosedRemote {
+                return Err(Error::Stream(stream_id, ErrorCode::StreamClosed));
+            }
+            let request = stream.request.as_mut().filter(|_| end_stream);
+            let Some(Ok(())) = request.map(|request| request.add_trailers(headers)) else {
+                return Err(Error::Stream(stream_id, ErrorCode::ProtocolError));
+            };
+            println!("[Stream {}] Received trailers", stream_id);
+            return Ok(self.end_remote(stream_id));
+        }
+        if stream_id.is_multiple_of(2) {
+            return Err(Error::Connection(
+                ErrorCode::ProtocolError,
+                "HEADERS on a stream the client cannot open",
+            ));
+        }
+        if stream_id <= self.last_stream_id {
+            return Err(Error::Connection(
+                ErrorCode::StreamClosed,
+                "HEADERS on a closed stream",
+            ));
+        }
+
+        // Opening a stream closes every idle one below it
+        println!("[Stream {}] Received HEADERS: {:?}", stream_id, headers);
+        self.last_stream_id = stream_id;
+        // Our limit is on the streams the client opens; pushes count against the client's
+        let max = self.local.max_concurrent_streams.unwrap_or(u32::MAX);
+        let open = self
+            .streams
+            .keys()
+            .filter(|id| !id.is_multiple_of(2))
+            .count();
+        if open >= max as usize {
+            return Err(Error::Stream(stream_id, ErrorCode::RefusedStream));
+        }
+        let request = Request::from_headers(headers).map_err(|reason| {
+            println!("[Stream {}] Malformed request: {}", stream_id, reason);
+            Error::Stream(stream_id, ErrorCode::ProtocolError)
+        })?;
+
+        self.streams.insert(
+            stream_id,
+            Stream {
+                state: StreamState::Open,
+                request: Some(request),
+                recv_window: Window::new(self.local.initial_window_size),
+                send_window: Window::new(self.remote.initial_window_size),
+                pending: None,
+            },
+        );
+        if end_stream {
+            return Ok(self.end_remote(stream_id));
+        }
+        Ok(None)
+    }
+
+    /// Takes DATA whose frame payload, padding included, was `len` bytes.
+    fn on_data(
+        &mut self,
+        stream_id: u32,
+        data: &[u8],
+        len: usize,
+        end_stream: bool,
+    ) -> Result<Option<(u32, Request)>, Error> {
+        println!("[Stream {}] Received DATA ({} bytes)", stream_id, len);
+
+        // DATA counts against the connection even when the stream is gone
+        self.recv_window
+            .consume(len)
+            .map_err(|code| Error::Connection(code, "DATA beyond the connection window"))?;
+        if let Some(increment) = self
+            .recv_window
+            .replenish(Settings::default().initial_window_size)
+        {
+            self.send(Frame::WindowUpdate {
+                stream_id: 0,
+                increment,
+            })?;
+        }
+
+        let idle = self.is_idle(stream_id);
+        let stream = match self.streams.get_mut(&stream_id) {
+            Some(stream) if stream.state != StreamState::HalfClosedRemote => stream,
+            Some(_) => return Err(Error::Stream(stream_id, ErrorCode::StreamClosed)),
+            None if idle => {
+                return Err(Error::Connection(
+                    ErrorCode::ProtocolError,
+                    "DATA on an idle stream",
+                ));
+            }
+            None => return Err(Error::Stream(stream_id, ErrorCode::StreamClosed)),
+        };
+        stream
+            .recv_window
+            .consume(len)
+            .map_err(|code| Error::Stream(stream_id, code))?;
+        let too_large = stream
+            .request
+            .as_ref()
+            .is_some_and(|request| request.body.len() + data.len() > self.max_body_size);
+        if too_large {
+            // Answer as HTTP/1.1 would, then ask the client to stop sending the rest
+            println!("[Stream {}] Request body too large", stream_id);
+            self.start_response(stream_id, Response::new(413))?;
+            self.reset(stream_id, ErrorCode::NoError)?;
+            return Ok(None);
+        }
+        if let Some(request) = &mut stream.request {
+            request.body.extend_from_slice(data);
+        }
+
+        if end_stream {
+            return Ok(self.end_remote(stream_id));
+        }
+        // Let the client carry on once it has used half of what it may send
+        if let Some(increment) = stream.recv_window.replenish(self.local.initial_window_size) {
+            self.send(Frame::WindowUpdate {
+                stream_id,
+                increment,
+            })?;
+        }
+        Ok(None)
+    }
+
+    fn on_window_update(&mut self, stream_id: u32, increment: u32) -> Result<(), Error> {
+        if stream_id == 0 {
+            return self
+                .send_window
+                .grow(increment as i64)
+                .map_err(|code| Error::Connection(code, "connection window overflow"));
+        }
+        let idle = self.is_idle(stream_id);
+        match self.streams.get_mut(&stream_id) {
+            Some(stream) => stream
+                .send_window
+                .grow(increment as i64)
+                .map_err(|code| Error::Stream(stream_id, code)),
+            None if idle => Err(Error::Connection(
+                ErrorCode::ProtocolError,
+                "WINDOW_UPDATE on an idle stream",
+            )),
+            // Updates can cross with the end of a stream, so late ones are fine
+            None => Ok(()),
+        }
+    }
+
+    fn on_rst_stream(&mut self, stream_id: u32, code: ErrorCode) -> Result<(), Error> {
+        if self.is_idle(stream_id) {
+            return Err(Error::Connection(
+                ErrorCode::ProtocolError,
+                "RST_STREAM on an idle stream",
+            ));
+        }
+        // Whatever the handler produces for it now goes nowhere
+        println!("[Stream {}] Cancelled by the client: {}", stream_id, code);
+        self.streams.remove(&stream_id);
+        Ok(())
+    }
+
+    /// Whether `stream_id` has yet to be opened, by the client or by a push.
+    fn is_idle(&self, stream_id: u32) -> bool {
+        if stream_id.is_multiple_of(2) {
+            stream_id >= self.next_push_id
+        } else {
+            stream_id > self.last_stream_id
+        }
+    }
+
+    /// Records the client's END_STREAM, returning the request if it is now complete.
+    fn end_remote(&mut self, stream_id: u32) -> Option<(u32, Request)> {
+        let stream = self.streams.get_mut(&stream_id)?;
+        let request = stream.request.take();
+        match stream.state {
+            StreamState::Open => stream.state = StreamState::HalfClosedRemote,
+            _ => {
+                self.streams.remove(&stream_id);
+            }
+        }
+        request.map(|request| (stream_id, request))
+    }
+
+    /// Records our END_STREAM, which closes the stream: responses only start once the
+    /// client has ended its side.
+    fn end_local(&mut self, stream_id: u32) {
+        self.streams.remove(&stream_id);
+    }
+
+    /// Sends a PUSH_PROMISE on `stream_id` for each of `paths`, as GETs alongside
+    /// `request`, returning the requests to answer on the promised streams.
+    fn promise(
+        &mut self,
+        stream_id: u32,
+        request: &Request,
+        paths: &[String],
+    ) -> io::Result<Vec<(u32, Request)>> {
+        let mut promised = Vec::new();
+        // Promises need the stream they refer to, and a client that wants them
+        if !self.options.push || !self.remote.enable_push || !self.streams.contains_key(&stream_id)
+        {
+            return Ok(promised);
+        }
+
+        for path in paths {
+            let pushed = self
+                .streams
+                .keys()
+                .filter(|id| id.is_multiple_of(2))
+                .count() as u32;
+            if self
+                .remote
+                .max_concurrent_streams
+                .is_some_and(|max| pushed >= max)
+                || self.next_push_id > MAX_STREAM_ID
+            {
+                println!("[Stream {}] No room to push {}", stream_id, path);
+                break;
+            }
+            let promised_id = self.next_push_id;
+            self.next_push_id += 2;
+
+            let pushed_request = Request {
+                method: "GET".to_string(),
+                scheme: request.scheme.clone(),
+                path: path.clone(),
+                authority: request.authority.clone(),
+                headers: Vec::new(),
+                body: Vec::new(),
+                trailers: Vec::new(),
+            };
+            let mut fields = vec![
+                (":method", "GET"),
+                (":scheme", pushed_request.scheme.as_str()),
+                (":path", pushed_request.path.as_str()),
+            ];
+            if let Some(authority) = &pushed_request.authority {
+                fields.push((":authority", authority.as_str()));
+            }
+            let block = self.encoder.encode(fields);
+            println!(
+                "[Stream {}] Promising {} on stream {}",
+                stream_id, path, promised_id
+            );
+            self.send(Frame::PushPromise {
+                stream_id,
+                promised_id,
+                block,
+                end_headers: true,
+                padding: None,
+            })?;
+
+            self.streams.insert(
+                promised_id,
+                Stream {
+                    state: StreamState::HalfClosedRemote,
+                    request: None,
+                    recv_window: Window::new(self.local.initial_window_size),
+                    send_window: Window::new(self.remote.initial_window_size),
+                    pending: None,
+                },
+            );
+            promised.push((promised_id, pushed_request));
+        }
+        Ok(promised)
+    }
+
+    /// Sends the response head and queues its body for [`Connection::flush`].
+    fn start_response(&mut self, stream_id: u32, response: Response) -> io::Result<()> {
+        // The stream may have been reset while the handler ran
+        if self.closed || !self.streams.contains_key(&stream_id) {
+            return Ok(());
+        }
+        println!("[Stream {}] Responding {}", stream_id, response.status);
+
+        let status = response.status.to_string();
+        let fields = iter::once((":status", status.as_str())).chain(
+            response
+                .headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        );
+        let block = self.encoder.encode(fields);
+        let end_stream = matches!(&response.body, Body::Bytes(bytes) if bytes.is_empty());
+        self.send(Frame::Headers {
+            stream_id,
+            block,
+            priority: None,
+            end_stream,
+            end_headers: true,
+            padding: None,
+        })?;
+        if end_stream {
+            self.end_local(stream_id);
+        } else if let Some(stream) = self.streams.get_mut(&stream_id) {
+            stream.pending = Some(response.body);
+        }
+        Ok(())
+    }
+
+    /// Sends pending response bodies a frame per stream at a time, so responses share the
+    /// connection, until they are done or out of window.
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.closed {
+            let mut progress = false;
+            let mut finished = Vec::new();
+            let mut failed = Vec::new();
+
+            for (&stream_id, stream) in &mut self.streams {
+                let Some(pending) = &mut stream.pending else {
+                    continue;
+                };
+                let room = self
+                    .send_window
+                    .available()
+                    .min(stream.send_window.available())
+                    .min(self.remote.max_frame_size as usize);
+                if room == 0 {
+                    continue;
+                }
+
+                let (data, done) = match pending {
+                    Body::Bytes(bytes) => {
+                        let chunk = bytes.split_to(room.min(bytes.len()));
+                        (chunk.to_vec(), bytes.is_empty())
+                    }
+                    // Falling short of `room` means the reader has run out
+                    Body::Reader(reader) => {
+                        let mut data = Vec::with_capacity(room);
+                        match reader.take(room as u64).read_to_end(&mut data) {
+                            Ok(n) => (data, n < room),
+                            Err(e) => {
+                                println!("[Stream {}] Body read failed: {}", stream_id, e);
+                                failed.push(stream_id);
+                                continue;
+                            }
+                        }
+                    }
+                };
+                // Both hold at least `room`, so these cannot fail
+                let _ = self.send_window.consume(data.len());
+                let _ = stream.send_window.consume(data.len());
+                let frame = Frame::Data {
+                    stream_id,
+                    data,
+                    end_stream: done,
+                    padding: None,
+                };
+                frame.write_to(&mut self.writer)?;
+                progress = true;
+
+                if done {
+                    stream.pending = None;
+                    finished.push(stream_id);
+                }
+            }
+
+            for stream_id in finished {
+                self.end_local(stream_id);
+            }
+            // The response has begun, so all that is left is to abandon the stream
+            for stream_id in failed {
+                self.reset(stream_id, ErrorCode::InternalError)?;
+            }
+            if !progress {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Abandons one stream, leaving the rest of the connection running.
+    fn reset(&mut self, stream_id: u32, code: ErrorCode) -> io::Result<()> {
+        println!("[Stream {}] Reset: {}", stream_id, code);
+        self.streams.remove(&stream_id);
+        self.send(Frame::RstStream { stream_id, code })
+    }
+
+    /// Tells the client which streams were processed and why the connection is closing.
+    fn go_away(&mut self, code: ErrorCode, reason: &str) -> io::Result<()> {
+        self.send(Frame::GoAway {
+            last_stream_id: self.last_stream_id,
+            code,
+            debug_data: reason.as_bytes().to_vec(),
+        })
+    }
+}
@@ -0,0 +1,208 @@
+//! A toy HTTP/2 server: every request goes to a handler on a thread of its own, and the
+//! responses share the connection as flow control allows.
+
+use std::io::{self, Read, Write};
+use std::mem;
+use std::net::{Shutdown, TcpStream};
+use std::time::Duration;
+
+use toy_http2::{
+    Settings,
+    settings::{MAX_MAX_FRAME_SIZE, MAX_WINDOW_SIZE, MIN_MAX_FRAME_SIZE},
+};
+
+mod connection;
+pub mod options;
+pub mod request;
+pub mod response;
+
+pub use options::ConnectionOptions;
+pub use request::Request;
+pub use response::Response;
+
+pub use toy_http2::frame::{
+    ACK, CONTINUATION, DATA, END_HEADERS, END_STREAM, FRAME_HEADER_LEN, GOAWAY, HEADERS, PING,
+    PRIORITY, PUSH_PROMISE, RST_STREAM, SETTINGS, WINDOW_UPDATE,
+};
+
+/// Streams a client may have open at once unless the server is told otherwise.
+pub const DEFAULT_MAX_CONCURRENT_STREAMS: u32 = 100;
+
+/// Largest request body accepted unless the server is told otherwise, as for HTTP/1.1.
+pub const DEFAULT_MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// An HTTP/2 server that answers every request with one handler.
+pub struct Server {
+    handler: Box<dyn Fn(&Request) -> Response + Send + Sync>,
+    max_concurrent_streams: u32,
+    initial_window_size: u32,
+    max_frame_size: u32,
+    max_body_size: usize,
+    options: ConnectionOptions,
+}
+
+impl Default for Server {
+    /// Echoes request bodies back, and greets requests without one.
+    fn default() -> Self {
+        Self::new(|request| {
+            let body = if request.body.is_empty() {
+                b"Hello from toy HTTP/2 server!".to_vec()
+            } else {
+                request.body.clone()
+            };
+            Response::new(200)
+                .header("content-type", "text/plain")
+                .body(body)
+        })
+    }
+}
+
+impl Server {
+    /// Creates a server that answers with `handler`, called on a thread per request.
+    pub fn new(handler: impl Fn(&Request) -> Response + Send + Sync + 'static) -> Self {
+        Self {
+            handler: Box::new(handler),
+            max_concurrent_streams: DEFAULT_MAX_CONCURRENT_STREAMS,
+            initial_window_size: Settings::default().initial_window_size,
+            max_frame_size: Settings::default().max_frame_size,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            options: ConnectionOptions::default(),
+        }
+    }
+
+    /// Refuses new streams with REFUSED_STREAM while `max` are still open.
+    pub fn max_concurrent_streams(mut self, max: u32) -> Self {
+        self.max_concurrent_streams = max;
+        self
+    }
+
+    /// Lets clients send `size` bytes of DATA on each stream before a WINDOW_UPDATE, up to
+    /// 2^31 - 1. The connection as a whole still starts at 65,535.
+    pub fn initial_window_size(mut self, size: u32) -> Self {
+        self.initial_window_size = size.min(MAX_WINDOW_SIZE);
+        self
+    }
+
+    /// Accepts frames with payloads of up to `size` bytes, from 16,384 to 2^24 - 1.
+    pub fn max_frame_size(mut self, size: u32) -> Self {
+        self.max_frame_size = size.clamp(MIN_MAX_FRAME_SIZE, MAX_MAX_FRAME_SIZE);
+        self
+    }
+
+    /// Answers requests whose bodies grow past `size` bytes with 413 and a RST_STREAM
+    /// (NO_ERROR), without calling the handler.
+    pub fn max_body_size(mut self, size: usize) -> Self {
+        self.max_body_size = size;
+        self
+    }
+
+    /// Uses `options` for every connection not given its own.
+    pub fn options(mut self, options: ConnectionOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Serves one connection until the client closes it or breaks the protocol.
+    ///
+    /// The stream is read on the calling thread while handler threads write responses to
+    /// it, which `&TcpStream` (among others) allows.
+    pub fn handle_connection<S>(&self, stream: &S)
+    where
+        S: Transport,
+        for<'a> &'a S: Read + Write,
+    {
+        connection::serve(self, &self.options, stream, &[], None);
+    }
+
+    /// Serves one connection whose first bytes, `buffered`, were already read off it, as
+    /// when telling a client that opens with the preface from an HTTP/1.1 one.
+    pub fn handle_buffered_connection<S>(&self, stream: &S, buffered: &[u8])
+    where
+        S: Transport,
+        for<'a> &'a S: Read + Write,
+    {
+        connection::serve(self, &self.options, stream, buffered, None);
+    }
+
+    /// Serves one connection with `options` in place of the server's own.
+    pub fn handle_connection_with<S>(&self, stream: &S, options: &ConnectionOptions)
+    where
+        S: Transport,
+        for<'a> &'a S: Read + Write,
+    {
+        connection::serve(self, options, stream, &[], None);
+    }
+
+    /// Serves a connection switched over from HTTP/1.1, once the `101 Switching Protocols`
+    /// response has gone out, answering the upgrade request on stream 1.
+    pub fn upgrade_connection<S>(&self, stream: &S, mut upgrade: Upgrade)
+    where
+        S: Transport,
+        for<'a> &'a S: Read + Write,
+    {
+        let buffered = mem::take(&mut upgrade.buffered);
+        connection::serve(self, &self.options, stream, &buffered, Some(upgrade));
+    }
+}
+
+/// A connection the server can be handed: besides reading and writing through shared
+/// references, it must be possible to cut off a client that stopped answering PINGs.
+pub trait Transport: Sync {
+    /// Makes reads and writes fail from now on; does nothing unless implemented.
+    fn shutdown(&self) {}
+
+    /// Makes a write the client leaves unread for `timeout` fail; does nothing unless
+    /// implemented.
+    fn set_write_timeout(&self, _timeout: Duration) {}
+}
+
+impl Transport for TcpStream {
+    fn shutdown(&self) {
+        let _ = TcpStream::shutdown(self, Shutdown::Both);
+    }
+
+    fn set_write_timeout(&self, timeout: Duration) {
+        let _ = TcpStream::set_write_timeout(self, Some(timeout));
+    }
+}
+
+/// An HTTP/1.1 request that asked to continue in HTTP/2 with `Upgrade: h2c`.
+pub struct Upgrade {
+    /// The parameters from the request's HTTP2-Settings header.
+    pub settings: Vec<(u16, u32)>,
+    /// The request itself, body included.
+    pub request: Request,
+    /// What was read from the connection past the request, the start of the client preface.
+    pub buffered: Vec<u8>,
+}
+
+/// Serves one connection with the default [`Server`].
+pub fn handle_connection<S>(stream: &S)
+where
+    S: Transport,
+    for<'a> &'a S: Read + Write,
+{
+    Server::default().handle_connection(stream);
+}
+
+pub fn send_frame(
+    stream: &mut impl Write,
+    frame_type: u8,
+    flags: u8,
+    stream_id: u32,
+    payload: &[u8],
+) -> io::Result<()> {
+    let len = payload.len();
+    let mut header = Vec::with_capacity(FRAME_HEADER_LEN);
+
+    header.push(((len >> 16) & 0xFF) as u8);
+    header.push(((len >> 8) & 0xFF) as u8);
+    header.push((len & 0xFF) as u8);
+    header.push(frame_type);
+    header.push(flags);
+    header.extend_from_slice(&(stream_id & 0x7FFFFFFF).to_be_bytes());
+
+    stream.write_all(&header)?;
+    stream.write_all(payload)?;
+    stream.flush()
+}
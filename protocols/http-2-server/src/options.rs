@@ -0,0 +1,61 @@
+//! What a connection does beyond answering requests: pushing responses and checking that
+//! the client is still there.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Settings for one connection, or for every connection with [`Server::options`].
+///
+/// [`Server::options`]: crate::Server::options
+#[derive(Clone)]
+pub struct ConnectionOptions {
+    pub(crate) push: bool,
+    /// How often to PING, and how long to wait for the acknowledgement
+    pub(crate) keepalive: Option<(Duration, Duration)>,
+    pub(crate) on_rtt: Option<Arc<dyn Fn(Duration) + Send + Sync>>,
+}
+
+impl Default for ConnectionOptions {
+    /// Pushes what handlers ask for and never PINGs.
+    fn default() -> Self {
+        Self {
+            push: true,
+            keepalive: None,
+            on_rtt: None,
+        }
+    }
+}
+
+impl ConnectionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether responses pushed with [`Response::push`] go out. Clients can also turn
+    /// pushes off with SETTINGS_ENABLE_PUSH, which wins over this.
+    ///
+    /// [`Response::push`]: crate::Response::push
+    pub fn push(mut self, enabled: bool) -> Self {
+        self.push = enabled;
+        self
+    }
+
+    /// Sends a PING every `interval`, and closes the connection if one is not acknowledged
+    /// within `timeout`.
+    pub fn keepalive(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.keepalive = Some((interval, timeout));
+        self
+    }
+
+    /// Whether connections are PINGed, as set with [`keepalive`](Self::keepalive).
+    pub fn pings(&self) -> bool {
+        self.keepalive.is_some()
+    }
+
+    /// Calls `f` with the round trip time of every acknowledged PING, on the thread reading
+    /// the connection.
+    pub fn on_rtt(mut self, f: impl Fn(Duration) + Send + Sync + 'static) -> Self {
+        self.on_rtt = Some(Arc::new(f));
+        self
+    }
+}
@@ -0,0 +1,166 @@
+//! Requests as handed to handlers, assembled from a stream's header blocks and DATA.
+
+/// Header fields that only mean something to an HTTP/1.1 connection (RFC 9113 section 8.2.2).
+const CONNECTION_SPECIFIC: [&str; 5] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// A complete request: its pseudo-headers, fields, body and any trailers.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub scheme: String,
+    /// The request target, including any query string.
+    pub path: String,
+    pub authority: Option<String>,
+    /// Regular fields, without the pseudo-headers.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub trailers: Vec<(String, String)>,
+}
+
+impl Request {
+    /// Splits a decoded header block into pseudo-headers and fields, or says why the
+    /// request is malformed.
+    pub(crate) fn from_headers(headers: Vec<(String, String)>) -> Result<Self, &'static str> {
+        let mut request = Request {
+            method: String::new(),
+            scheme: String::new(),
+            path: String::new(),
+            authority: None,
+            headers: Vec::new(),
+            body: Vec::new(),
+            trailers: Vec::new(),
+        };
+
+        for (name, value) in headers {
+            let Some(pseudo) = name.strip_prefix(':') else {
+                check_field(&name, &value)?;
+                request.headers.push((name, value));
+                continue;
+            };
+            if !request.headers.is_empty() {
+                return Err("pseudo-header after a regular field");
+            }
+            let slot = match pseudo {
+                "method" => &mut request.method,
+                "scheme" => &mut request.scheme,
+                "path" => &mut request.path,
+                "authority" => request.authority.get_or_insert_with(String::new),
+                _ => return Err("unknown pseudo-header"),
+            };
+            if !slot.is_empty() {
+                return Err("repeated pseudo-header");
+            }
+            *slot = value;
+        }
+
+        if request.method.is_empty() || request.scheme.is_empty() || request.path.is_empty() {
+            return Err("missing :method, :scheme or :path");
+        }
+        Ok(request)
+    }
+
+    /// Checks the fields of a trailing header block before adding them.
+    pub(crate) fn add_trailers(
+        &mut self,
+        trailers: Vec<(String, String)>,
+    ) -> Result<(), &'static str> {
+        for (name, value) in &trailers {
+            if name.starts_with(':') {
+                return Err("pseudo-header in trailers");
+            }
+            check_field(name, value)?;
+        }
+        self.trailers = trailers;
+        Ok(())
+    }
+
+    /// The value of the first field called `name`; names are always lowercase.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+fn check_field(name: &str, value: &str) -> Result<(), &'static str> {
+    if name.bytes().any(|b| b.is_ascii_uppercase()) {
+        return Err("uppercase field name");
+    }
+    if CONNECTION_SPECIFIC.contains(&name) || (name == "te" && value != "trailers") {
+        return Err("connection-specific field");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|&(n, v)| (n.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn pseudo_headers_are_split_out() {
+        let request = Request::from_headers(fields(&[
+            (":method", "GET"),
+            (":scheme", "http"),
+            (":authority", "example.com"),
+            (":path", "/a?b=c"),
+            ("accept", "*/*"),
+            ("te", "trailers"),
+        ]))
+        .unwrap();
+
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/a?b=c");
+        assert_eq!(request.authority.as_deref(), Some("example.com"));
+        assert_eq!(request.header("accept"), Some("*/*"));
+        assert_eq!(request.headers.len(), 2);
+    }
+
+    #[test]
+    fn malformed_blocks_are_rejected() {
+        let base = [(":method", "GET"), (":scheme", "http"), (":path", "/")];
+        for extra in [
+            [("accept", "*/*"), (":authority", "late")],
+            [(":method", "POST"), ("accept", "*/*")],
+            [(":protocol", "x"), ("accept", "*/*")],
+            [("Accept", "*/*"), ("accept", "*/*")],
+            [("connection", "keep-alive"), ("accept", "*/*")],
+            [("te", "gzip"), ("accept", "*/*")],
+        ] {
+            let mut block = fields(&base);
+            block.extend(fields(&extra));
+            assert!(Request::from_headers(block).is_err(), "{extra:?}");
+        }
+
+        assert!(Request::from_headers(fields(&[(":method", "GET"), (":path", "/")])).is_err());
+    }
+
+    #[test]
+    fn trailers_cannot_hold_pseudo_headers() {
+        let mut request = Request::from_headers(fields(&[
+            (":method", "POST"),
+            (":scheme", "http"),
+            (":path", "/"),
+        ]))
+        .unwrap();
+
+        assert!(request.add_trailers(fields(&[(":status", "200")])).is_err());
+        request
+            .add_trailers(fields(&[("x-checksum", "abc")]))
+            .unwrap();
+        assert_eq!(request.trailers, fields(&[("x-checksum", "abc")]));
+    }
+}
@@ -0,0 +1,75 @@
+//! Responses built by handlers.
+
+use std::{fmt, io::Read};
+
+use bytes::Bytes;
+
+/// What the DATA frames of a response carry.
+pub(crate) enum Body {
+    Bytes(Bytes),
+    /// Read a frame at a time, as the windows allow
+    Reader(Box<dyn Read + Send>),
+}
+
+/// A response built up with chained calls; sent as HEADERS then DATA as windows allow.
+pub struct Response {
+    pub(crate) status: u16,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: Body,
+    /// Paths whose responses are pushed alongside this one
+    pub(crate) pushes: Vec<String>,
+}
+
+impl Response {
+    /// Creates an empty response with the given status code.
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Body::Bytes(Bytes::new()),
+            pushes: Vec::new(),
+        }
+    }
+
+    /// Adds a header; the name is sent lowercase, as HTTP/2 requires.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers
+            .push((name.into().to_ascii_lowercase(), value.into()));
+        self
+    }
+
+    /// Uses `bytes` as the body.
+    pub fn body(mut self, bytes: impl Into<Vec<u8>>) -> Self {
+        self.body = Body::Bytes(Bytes::from(bytes.into()));
+        self
+    }
+
+    /// Sends what `reader` yields as the body, read only as the client's windows let it go
+    /// out, so it never has to be held whole.
+    pub fn stream(mut self, reader: impl Read + Send + 'static) -> Self {
+        self.body = Body::Reader(Box::new(reader));
+        self
+    }
+
+    /// Pushes the response to a GET of `path`, from the same handler, before the client
+    /// asks for it. Only responses to the client's own requests can push.
+    pub fn push(mut self, path: impl Into<String>) -> Self {
+        self.pushes.push(path.into());
+        self
+    }
+
+    /// The status code.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+}
+
+impl fmt::Debug for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Response")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .field("pushes", &self.pushes)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,120 @@
+//! In-memory connections, for tests that must not depend on how a socket behaves.
+
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind, Read, Write},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use http_2_server::{Server, Transport};
+
+use super::Client;
+
+/// One direction of an in-memory connection: the bytes in flight, and whether it is closed
+#[derive(Default)]
+struct Pipe {
+    state: Mutex<(VecDeque<u8>, bool)>,
+    changed: Condvar,
+}
+
+impl Pipe {
+    fn close(&self) {
+        self.state.lock().unwrap().1 = true;
+        self.changed.notify_all();
+    }
+}
+
+/// One end of an in-memory connection, shareable between threads like a `TcpStream`
+pub struct End {
+    incoming: Arc<Pipe>,
+    outgoing: Arc<Pipe>,
+    read_timeout: Option<Duration>,
+}
+
+fn pair() -> (End, End) {
+    let (a, b) = (Arc::new(Pipe::default()), Arc::new(Pipe::default()));
+    let client = End {
+        incoming: Arc::clone(&a),
+        outgoing: Arc::clone(&b),
+        // A missing answer fails the test rather than hanging it
+        read_timeout: Some(Duration::from_secs(2)),
+    };
+    let server = End {
+        incoming: b,
+        outgoing: a,
+        read_timeout: None,
+    };
+    (client, server)
+}
+
+impl Read for &End {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let deadline = self.read_timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.incoming.state.lock().unwrap();
+        while state.0.is_empty() && !state.1 {
+            state = match deadline {
+                None => self.incoming.changed.wait(state).unwrap(),
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    if left.is_zero() {
+                        return Err(ErrorKind::TimedOut.into());
+                    }
+                    self.incoming.changed.wait_timeout(state, left).unwrap().0
+                }
+            };
+        }
+        // Nothing left once closed reads as the end of the stream
+        let n = buf.len().min(state.0.len());
+        for (slot, byte) in buf.iter_mut().zip(state.0.drain(..n)) {
+            *slot = byte;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for &End {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.outgoing.state.lock().unwrap();
+        if state.1 {
+            return Err(ErrorKind::BrokenPipe.into());
+        }
+        state.0.extend(buf);
+        self.outgoing.changed.notify_all();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for End {
+    fn shutdown(&self) {
+        self.incoming.close();
+        self.outgoing.close();
+    }
+}
+
+impl Drop for End {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Serves the other end of a new in-memory connection with `server`, without sending
+/// anything. The connection closes when the server is done with it.
+pub fn open(server: Server) -> Client<End> {
+    let (client, end) = pair();
+    thread::spawn(move || server.handle_connection(&end));
+    Client::new(client)
+}
+
+/// Opens an in-memory connection and sends the preface with SETTINGS `parameters`, leaving
+/// the server's replies unread.
+pub fn connect(server: Server, parameters: Vec<(u16, u32)>) -> Client<End> {
+    let mut client = open(server);
+    client.handshake(parameters);
+    client
+}
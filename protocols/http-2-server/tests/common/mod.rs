@@ -0,0 +1,187 @@
+//! What the integration tests share: a server on a random port, and a client connection to
+//! it, or to a server over [`memory`], that sends and reads whole frames.
+
+// Every test binary uses only some of this
+#![allow(dead_code)]
+
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use http_2_server::Server;
+use toy_http2::{
+    ErrorCode, Frame, PREFACE,
+    hpack::{Decoder, Encoder},
+};
+
+pub mod memory;
+
+/// Serves every connection to a random port with `server`, returning the port.
+pub fn start_server(server: Server) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap(); // bind to random port
+    let port = listener.local_addr().unwrap().port();
+    let server = Arc::new(server);
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let server = Arc::clone(&server);
+            thread::spawn(move || server.handle_connection(&stream));
+        }
+    });
+
+    port
+}
+
+/// A connection to a test server, over TCP unless made with [`memory`].
+pub struct Client<S = TcpStream> {
+    pub stream: S,
+    buf: Vec<u8>,
+    /// Encodes the requests sent with [`Client::request`]
+    pub encoder: Encoder,
+    /// For the header blocks of responses, all of which it must see
+    pub decoder: Decoder,
+}
+
+impl Client {
+    /// Connects to a new server without sending anything.
+    pub fn open(server: Server) -> Self {
+        Self::new(TcpStream::connect(("127.0.0.1", start_server(server))).unwrap())
+    }
+
+    /// Connects and sends the preface with SETTINGS `parameters`, leaving the server's
+    /// replies unread.
+    pub fn connect(server: Server, parameters: Vec<(u16, u32)>) -> Self {
+        let mut client = Self::open(server);
+        client.handshake(parameters);
+        client
+    }
+
+    /// Reads frames other than SETTINGS until none arrive for `wait`.
+    pub fn read_quiet(&mut self, wait: Duration) -> Vec<Frame> {
+        self.stream.set_read_timeout(Some(wait)).unwrap();
+        let mut frames = Vec::new();
+        while let Some(frame) = self.read_frame() {
+            if !matches!(frame, Frame::Settings { .. }) {
+                frames.push(frame);
+            }
+        }
+        self.stream.set_read_timeout(None).unwrap();
+        frames
+    }
+}
+
+impl<S> Client<S>
+where
+    for<'a> &'a S: Read + Write,
+{
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            buf: Vec::new(),
+            encoder: Encoder::default(),
+            decoder: Decoder::default(),
+        }
+    }
+
+    /// Sends the preface with SETTINGS `parameters`, leaving the server's replies unread.
+    pub fn handshake(&mut self, parameters: Vec<(u16, u32)>) {
+        self.write(PREFACE);
+        self.send(Frame::Settings {
+            ack: false,
+            parameters,
+        });
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        (&self.stream).write_all(bytes).unwrap();
+    }
+
+    pub fn send(&mut self, frame: Frame) {
+        frame.write_to(&mut &self.stream).unwrap();
+    }
+
+    /// Sends a request for `path` in one HEADERS frame, ending the stream unless a body
+    /// is to follow.
+    pub fn request(&mut self, stream_id: u32, method: &str, path: &str, end_stream: bool) {
+        let block = self.encoder.encode([
+            (":method", method),
+            (":scheme", "http"),
+            (":authority", "localhost"),
+            (":path", path),
+        ]);
+        self.send(Frame::Headers {
+            stream_id,
+            block,
+            priority: None,
+            end_stream,
+            end_headers: true,
+            padding: None,
+        });
+    }
+
+    /// Reads whole frames as they arrive, or None once the server has closed or a read
+    /// timeout has passed.
+    pub fn read_frame(&mut self) -> Option<Frame> {
+        loop {
+            if let Some((frame, len)) = Frame::parse(&self.buf, 1 << 24).unwrap() {
+                self.buf.drain(..len);
+                return Some(frame);
+            }
+            let mut chunk = [0u8; 4096];
+            match (&self.stream).read(&mut chunk) {
+                Ok(0) | Err(_) => return None,
+                Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+    }
+
+    /// Skips frames until one matching `wanted` arrives, failing on close or on a GOAWAY or
+    /// RST_STREAM that was not wanted.
+    pub fn expect(&mut self, wanted: impl Fn(&Frame) -> bool) -> Frame {
+        loop {
+            let frame = self.read_frame().expect("closed or silent");
+            if wanted(&frame) {
+                return frame;
+            }
+            assert!(
+                !matches!(frame, Frame::GoAway { .. } | Frame::RstStream { .. }),
+                "unexpected {frame:?}"
+            );
+        }
+    }
+
+    /// Skips to the GOAWAY, checks the connection then closes, and returns its error code.
+    pub fn expect_goaway(&mut self) -> ErrorCode {
+        let Frame::GoAway { code, .. } = self.expect(|f| matches!(f, Frame::GoAway { .. })) else {
+            unreachable!()
+        };
+        self.expect_closed();
+        code
+    }
+
+    /// Checks the server has closed the connection, rather than merely gone quiet.
+    pub fn expect_closed(&mut self) {
+        let mut rest = [0u8; 1];
+        let read = (&self.stream).read(&mut rest);
+        let closed = match &read {
+            Ok(0) => self.buf.is_empty(),
+            Err(e) => e.kind() == ErrorKind::ConnectionReset,
+            Ok(_) => false,
+        };
+        assert!(closed, "connection left open: {read:?}");
+    }
+
+    /// Skips to the next RST_STREAM, returning its stream and error code.
+    pub fn expect_reset(&mut self) -> (u32, ErrorCode) {
+        let Frame::RstStream { stream_id, code } =
+            self.expect(|f| matches!(f, Frame::RstStream { .. }))
+        else {
+            unreachable!()
+        };
+        (stream_id, code)
+    }
+}
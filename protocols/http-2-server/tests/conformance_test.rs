@@ -0,0 +1,511 @@
+//! Protocol conformance cases after h2spec, grouped by RFC 9113 section, run against
+//! `handle_connection` over an in-memory connection.
+
+use http_2_server::Server;
+use toy_http2::{
+    ErrorCode, Frame, PREFACE,
+    frame::{CONTINUATION, DATA, END_HEADERS, PING, PRIORITY, SETTINGS},
+    settings::{ENABLE_PUSH, INITIAL_WINDOW_SIZE, MAX_FRAME_SIZE},
+};
+
+mod common;
+use common::{
+    Client,
+    memory::{self, End},
+};
+
+// Serves the other end of a new in-memory connection, which closes when the server is done
+fn start() -> Client<End> {
+    memory::open(Server::default())
+}
+
+// Sends the preface and empty SETTINGS, leaving the server's replies unread
+fn connect() -> Client<End> {
+    memory::connect(Server::default(), Vec::new())
+}
+
+impl Client<End> {
+    // For frames `Frame` cannot express, such as ones on the wrong stream
+    fn send_raw(&mut self, frame_type: u8, flags: u8, stream_id: u32, payload: &[u8]) {
+        let len = (payload.len() as u32).to_be_bytes();
+        let mut bytes = vec![len[1], len[2], len[3], frame_type, flags];
+        bytes.extend_from_slice(&stream_id.to_be_bytes());
+        bytes.extend_from_slice(payload);
+        self.write(&bytes);
+    }
+
+    fn request_block(&mut self, path: &str) -> Vec<u8> {
+        self.encoder
+            .encode([(":method", "GET"), (":scheme", "http"), (":path", path)])
+    }
+
+    fn headers(&mut self, stream_id: u32, block: Vec<u8>, end_stream: bool, end_headers: bool) {
+        self.send(Frame::Headers {
+            stream_id,
+            block,
+            priority: None,
+            end_stream,
+            end_headers,
+            padding: None,
+        });
+    }
+
+    fn get(&mut self, stream_id: u32, path: &str) {
+        self.request(stream_id, "GET", path, true);
+    }
+
+    // Opens a stream whose request body is still to come
+    fn open_stream(&mut self, stream_id: u32) {
+        self.request(stream_id, "GET", "/", false);
+    }
+
+    fn expect_connection_error(&mut self, expected: ErrorCode) {
+        assert_eq!(self.expect_goaway(), expected);
+    }
+
+    fn expect_stream_error(&mut self, expected_id: u32, expected: ErrorCode) {
+        assert_eq!(self.expect_reset(), (expected_id, expected));
+        self.expect_alive();
+    }
+
+    // The connection still answers a PING
+    fn expect_alive(&mut self) {
+        let data = *b"conform!";
+        self.send(Frame::Ping { ack: false, data });
+        let ack = self.expect(|frame| matches!(frame, Frame::Ping { ack: true, .. }));
+        assert!(matches!(ack, Frame::Ping { data: d, .. } if d == data));
+    }
+
+    // Reads a whole response on `wanted`, returning its status and body
+    fn expect_response(&mut self, wanted: u32) -> (String, Vec<u8>) {
+        let mut status = None;
+        let mut body = Vec::new();
+        loop {
+            match self.expect(|frame| matches!(frame, Frame::Headers { .. } | Frame::Data { .. })) {
+                Frame::Headers {
+                    stream_id,
+                    block,
+                    end_stream,
+                    ..
+                } => {
+                    let fields = self.decoder.decode(&block).unwrap();
+                    if stream_id == wanted {
+                        status = fields
+                            .into_iter()
+                            .find(|(name, _)| name == ":status")
+                            .map(|(_, value)| value);
+                        if end_stream {
+                            break;
+                        }
+                    }
+                }
+                Frame::Data {
+                    stream_id,
+                    data,
+                    end_stream,
+                    ..
+                } if stream_id == wanted => {
+                    body.extend_from_slice(&data);
+                    if end_stream {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        (status.expect("no :status"), body)
+    }
+}
+
+#[test]
+fn requests_are_answered_over_memory() {
+    let mut client = connect();
+    client.get(1, "/");
+    let (status, body) = client.expect_response(1);
+
+    assert_eq!(status, "200");
+    assert_eq!(body, b"Hello from toy HTTP/2 server!");
+}
+
+// 3.4: HTTP/2 connection preface
+
+#[test]
+fn invalid_preface_is_a_protocol_error() {
+    let mut client = start();
+    client.write(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+    client.expect_connection_error(ErrorCode::ProtocolError);
+}
+
+#[test]
+fn preface_must_be_followed_by_settings() {
+    let mut client = start();
+    client.write(PREFACE);
+    client.send(Frame::Ping {
+        ack: false,
+        data: [0; 8],
+    });
+    client.expect_connection_error(ErrorCode::ProtocolError);
+}
+
+// 4.2: Frame size
+
+#[test]
+fn data_beyond_max_frame_size_is_a_frame_size_error() {
+    let mut client = connect();
+    client.open_stream(1);
+    client.send(Frame::Data {
+        stream_id: 1,
+        data: vec![0; (1 << 14) + 1],
+        end_stream: true,
+        padding: None,
+    });
+    client.expect_connection_error(ErrorCode::FrameSizeError);
+}
+
+#[test]
+fn headers_beyond_max_frame_size_is_a_frame_size_error() {
+    let mut client = connect();
+    let mut block = client.request_block("/");
+    block.resize((1 << 14) + 1, 0);
+    client.headers(1, block, true, true);
+    client.expect_connection_error(ErrorCode::FrameSizeError);
+}
+
+// 4.3: Header compression and decompression
+
+#[test]
+fn undecodable_header_block_is_a_compression_error() {
+    let mut client = connect();
+    // An index far beyond both tables
+    client.headers(1, vec![0xff, 0xff, 0xff, 0x0f], true, true);
+    client.expect_connection_error(ErrorCode::CompressionError);
+}
+
+#[test]
+fn priority_inside_a_header_block_is_a_protocol_error() {
+    let mut client = connect();
+    let block = client.request_block("/");
+    client.headers(1, block, true, false);
+    client.send_raw(PRIORITY, 0, 1, &[0, 0, 0, 0, 16]);
+    client.expect_connection_error(ErrorCode::ProtocolError);
+}
+
+#[test]
+fn headers_on_another_stream_inside_a_header_block_is_a_protocol_error() {
+    let mut client = connect();
+    let block = client.request_block("/");
+    client.headers(1, block, true, false);
+    let block = client.request_block("/");
+    client.headers(3, block, true, true);
+    client.expect_connection_error(ErrorCode::ProtocolError);
+}
+
+// 5.1: Stream states
+
+#[test]
+fn data_on_an_idle_stream_is_a_protocol_error() {
+    let mut client = connect();
+    client.send(Frame::Data {
+        stream_id: 1,
+        data: b"hi".to_vec(),
+        end_stream: true,
+        padding: None,
+    });
+    client.expect_connection_error(ErrorCode::ProtocolError);
+}
+
+#[test]
+fn rst_stream_on_an_idle_stream_is_a_protocol_error() {
+    let mut client = connect();
+    client.send(Frame::RstStream {
+        stream_id: 1,
+        code: ErrorCode::Cancel,
+    });
+    client.expect_connection_error(ErrorCode::ProtocolError);
+}
+
+#[test]
+fn window_update_on_an_idle_stream_is_a_protocol_error() {
+    let mut client = connect();
+    client.send(Frame::WindowUpdate {
+        stream_id: 1,
+        increment: 100,
+    });
+    client.expect_connection_error(ErrorCode::ProtocolError);
+}
+
+#[test]
+fn data_on_a_half_closed_stream_is_stream_closed() {
+    let mut client = connect();
+    client.get(1, "/");
+    client.send(Frame::Data {
+        stream_id: 1,
+        data: b"late".to_vec(),
+        end_stream: true,
+        padding: None,
+    });
+    client.expect_stream_error(1, ErrorCode::StreamClosed);
+}
+
+#[test]
+fn headers_on_a_closed_stream_is_stream_closed() {
+    let mut client = connect();
+    client.get(1, "/");
+    client.expect_response(1);
+    client.get(1, "/");
+    client.expect_connection_error(ErrorCode::StreamClosed);
+}
+
+// 5.1.1: Stream identifiers
+
+#[test]
+fn even_stream_ids_are_a_protocol_error() {
+    let mut client = connect();
+    client.get(2, "/");
+    client.expect_connection_error(ErrorCode::ProtocolError);
+}
+
+#[test]
+fn stream_frames_on_stream_zero_are_a_protocol_error() {
+    let mut client = connect();
+    client.send_raw(DATA, 0, 0, b"hi");
+    client.expect_connection_error(ErrorCode::ProtocolError);
+}
+
+// 6.5: SETTINGS
+
+#[test]
+fn settings_on_a_stream_is_a_protocol_error() {
+    let mut client = connect();
+    client.send_raw(SETTINGS, 0, 1, &[]);
+    client.expect_connection_error(ErrorCode::ProtocolError);
+}
+
+#[test]
+fn settings_of_a_partial_parameter_is_a_frame_size_error() {
+    let mut client = connect();
+    client.send_raw(SETTINGS, 0, 0, &[0, 3, 0, 0, 0]);
+    client.expect_connection_error(ErrorCode::FrameSizeError);
+}
+
+#[test]
+fn settings_ack_with_a_payload_is_a_frame_size_error() {
+    let mut client = connect();
+    client.send_raw(SETTINGS, 0x1, 0, &[0, 3, 0, 0, 0, 1]);
+    client.expect_connection_error(ErrorCode::FrameSizeError);
+}
+
+#[test]
+fn invalid_settings_values_are_rejected() {
+    for (parameter, code) in [
+        ((ENABLE_PUSH, 2), ErrorCode::ProtocolError),
+        ((MAX_FRAME_SIZE, (1 << 14) - 1), ErrorCode::ProtocolError),
+        ((MAX_FRAME_SIZE, 1 << 24), ErrorCode::ProtocolError),
+        ((INITIAL_WINDOW_SIZE, 1 << 31), ErrorCode::FlowControlError),
+    ] {
+        let mut client = connect();
+        client.send(Frame::Settings {
+            ack: false,
+            parameters: vec![parameter],
+        });
+        client.expect_connection_error(code);
+    }
+}
+
+// 6.7: PING
+
+#[test]
+fn pings_are_echoed() {
+    let mut client = connect();
+    client.expect_alive();
+}
+
+#[test]
+fn ping_on_a_stream_is_a_protocol_error() {
+    let mut client = connect();
+    client.send_raw(PING, 0, 1, &[0; 8]);
+    client.expect_connection_error(ErrorCode::ProtocolError);
+}
+
+#[test]
+fn ping_of_the_wrong_length_is_a_frame_size_error() {
+    let mut client = connect();
+    client.send_raw(PING, 0, 0, &[0; 6]);
+    client.expect_connection_error(ErrorCode::FrameSizeError);
+}
+
+// 6.9: Flow control
+
+#[test]
+fn zero_window_increment_on_the_connection_is_a_protocol_error() {
+    let mut client = connect();
+    client.send(Frame::WindowUpdate {
+        stream_id: 0,
+        increment: 0,
+    });
+    client.expect_connection_error(ErrorCode::ProtocolError);
+}
+
+#[test]
+fn zero_window_increment_on_a_stream_is_a_stream_error() {
+    let mut client = connect();
+    client.open_stream(1);
+    client.send(Frame::WindowUpdate {
+        stream_id: 1,
+        increment: 0,
+    });
+    client.expect_stream_error(1, ErrorCode::ProtocolError);
+}
+
+#[test]
+fn connection_window_overflow_is_a_flow_control_error() {
+    let mut client = connect();
+    client.send(Frame::WindowUpdate {
+        stream_id: 0,
+        increment: (1 << 31) - 1,
+    });
+    client.expect_connection_error(ErrorCode::FlowControlError);
+}
+
+#[test]
+fn stream_window_overflow_is_a_stream_error() {
+    let mut client = connect();
+    client.open_stream(1);
+    client.send(Frame::WindowUpdate {
+        stream_id: 1,
+        increment: (1 << 31) - 1,
+    });
+    client.expect_stream_error(1, ErrorCode::FlowControlError);
+}
+
+#[test]
+fn data_beyond_the_stream_window_is_a_stream_error() {
+    let mut client = memory::connect(Server::default().initial_window_size(100), Vec::new());
+    client.open_stream(1);
+    let data = |len| Frame::Data {
+        stream_id: 1,
+        data: vec![0; len],
+        end_stream: false,
+        padding: None,
+    };
+    // All of the window may be used, after which the server opens it again
+    client.send(data(100));
+    client.expect_alive();
+    client.send(data(101));
+    client.expect_stream_error(1, ErrorCode::FlowControlError);
+}
+
+#[test]
+fn data_beyond_the_connection_window_is_a_flow_control_error() {
+    // Frames and stream windows large enough to overrun the connection's 65,535 bytes at once
+    let server = Server::default()
+        .initial_window_size(1 << 20)
+        .max_frame_size(1 << 17);
+    let mut client = memory::connect(server, Vec::new());
+    client.open_stream(1);
+    client.send(Frame::Data {
+        stream_id: 1,
+        data: vec![0; 65_536],
+        end_stream: false,
+        padding: None,
+    });
+    client.expect_connection_error(ErrorCode::FlowControlError);
+}
+
+#[test]
+fn initial_window_size_overflowing_a_stream_is_a_flow_control_error() {
+    let mut client = connect();
+    client.open_stream(1);
+    client.send(Frame::WindowUpdate {
+        stream_id: 1,
+        increment: (1 << 30) + 1,
+    });
+    // 65,535 + 2^30 + 1 on the stream, then 2^31 - 1 - 65,535 more from the new size
+    client.send(Frame::Settings {
+        ack: false,
+        parameters: vec![(INITIAL_WINDOW_SIZE, (1 << 31) - 1)],
+    });
+    client.expect_connection_error(ErrorCode::FlowControlError);
+}
+
+// 6.10: CONTINUATION
+
+#[test]
+fn header_blocks_may_span_continuations() {
+    let mut client = connect();
+    let block = client.request_block("/split");
+    let (first, rest) = block.split_at(2);
+    let (second, third) = rest.split_at(1);
+    client.headers(1, first.to_vec(), true, false);
+    client.send_raw(CONTINUATION, 0, 1, second);
+    client.send_raw(CONTINUATION, END_HEADERS, 1, third);
+
+    assert_eq!(client.expect_response(1).0, "200");
+}
+
+#[test]
+fn continuation_after_end_headers_is_a_protocol_error() {
+    let mut client = connect();
+    client.open_stream(1);
+    client.send_raw(CONTINUATION, END_HEADERS, 1, &[0x82]);
+    client.expect_connection_error(ErrorCode::ProtocolError);
+}
+
+#[test]
+fn continuation_on_another_stream_is_a_protocol_error() {
+    let mut client = connect();
+    let block = client.request_block("/");
+    client.headers(1, block, true, false);
+    client.send_raw(CONTINUATION, END_HEADERS, 3, &[0x82]);
+    client.expect_connection_error(ErrorCode::ProtocolError);
+}
+
+#[test]
+fn continuation_on_stream_zero_is_a_protocol_error() {
+    let mut client = connect();
+    let block = client.request_block("/");
+    client.headers(1, block, true, false);
+    client.send_raw(CONTINUATION, END_HEADERS, 0, &[0x82]);
+    client.expect_connection_error(ErrorCode::ProtocolError);
+}
+
+// 8.2, 8.3: HTTP header fields and pseudo-header fields
+
+#[test]
+fn malformed_requests_are_stream_errors() {
+    let malformed: [&[(&str, &str)]; 5] = [
+        &[(":method", "GET"), (":scheme", "http")],
+        &[
+            (":method", "GET"),
+            (":scheme", "http"),
+            (":path", "/"),
+            ("Upper", "x"),
+        ],
+        &[
+            (":method", "GET"),
+            (":scheme", "http"),
+            (":path", "/"),
+            ("connection", "close"),
+        ],
+        &[
+            (":method", "GET"),
+            ("x", "y"),
+            (":scheme", "http"),
+            (":path", "/"),
+        ],
+        &[
+            (":method", "GET"),
+            (":scheme", "http"),
+            (":path", "/"),
+            (":unknown", "x"),
+        ],
+    ];
+    let mut client = connect();
+    let mut stream_id = 1;
+    for fields in malformed {
+        let block = client.encoder.encode(fields.iter().copied());
+        client.headers(stream_id, block, true, true);
+        client.expect_stream_error(stream_id, ErrorCode::ProtocolError);
+        stream_id += 2;
+    }
+}
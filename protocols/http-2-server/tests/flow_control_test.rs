@@ -0,0 +1,231 @@
+use std::{
+    io::{self, Read},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use http_2_server::{DEFAULT_MAX_CONCURRENT_STREAMS, Response, Server};
+use toy_http2::{
+    ErrorCode, Frame,
+    settings::{INITIAL_WINDOW_SIZE, MAX_CONCURRENT_STREAMS},
+};
+
+mod common;
+use common::Client;
+
+fn connect(parameters: Vec<(u16, u32)>) -> Client {
+    Client::connect(Server::default(), parameters)
+}
+
+fn window_update(client: &mut Client, stream_id: u32, increment: u32) {
+    client.send(Frame::WindowUpdate {
+        stream_id,
+        increment,
+    });
+}
+
+// Reads DATA until `len` bytes have come, skipping other frames; returns the bytes and
+// whether the last frame ended the stream
+fn read_data(client: &mut Client, len: usize) -> (Vec<u8>, bool) {
+    let mut data = Vec::new();
+    let mut ended = false;
+    while data.len() < len {
+        if let Frame::Data {
+            data: payload,
+            end_stream,
+            ..
+        } = client.read_frame().expect("closed")
+        {
+            data.extend(payload);
+            ended = end_stream;
+        }
+    }
+    assert_eq!(data.len(), len, "more DATA than the window allows");
+    (data, ended)
+}
+
+// Checks that no DATA arrives for a while
+fn assert_no_data(client: &mut Client) {
+    let frames = client.read_quiet(Duration::from_millis(200));
+    assert!(
+        !frames.iter().any(|f| matches!(f, Frame::Data { .. })),
+        "DATA sent without window"
+    );
+}
+
+#[test]
+fn large_bodies_follow_both_windows() {
+    let mut client = connect(Vec::new());
+    let body: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+
+    // Upload within the server's windows, which it reopens as it reads
+    client.request(1, "POST", "/", false);
+    let (mut connection_window, mut stream_window) = (65_535usize, 65_535usize);
+    let mut sent = 0;
+    while sent < body.len() {
+        let room = connection_window.min(stream_window).min(16_384);
+        if room == 0 {
+            if let Frame::WindowUpdate {
+                stream_id,
+                increment,
+            } = client.read_frame().unwrap()
+            {
+                match stream_id {
+                    0 => connection_window += increment as usize,
+                    _ => stream_window += increment as usize,
+                }
+            }
+            continue;
+        }
+        let chunk = &body[sent..(sent + room).min(body.len())];
+        sent += chunk.len();
+        client.send(Frame::Data {
+            stream_id: 1,
+            data: chunk.to_vec(),
+            end_stream: sent == body.len(),
+            padding: None,
+        });
+        connection_window -= chunk.len();
+        stream_window -= chunk.len();
+    }
+
+    // The echo stops once our default windows are used up
+    let (mut echoed, ended) = read_data(&mut client, 65_535);
+    assert!(!ended);
+    assert_no_data(&mut client);
+
+    window_update(&mut client, 0, 50_000);
+    window_update(&mut client, 1, 50_000);
+    let (rest, ended) = read_data(&mut client, body.len() - 65_535);
+    echoed.extend(rest);
+
+    assert!(ended);
+    assert!(echoed == body, "echoed body differs");
+}
+
+#[test]
+fn streamed_bodies_are_read_only_as_windows_allow() {
+    // Counts what the server has taken from the body so far
+    struct Counted(io::Cursor<Vec<u8>>, Arc<AtomicUsize>);
+    impl Read for Counted {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.0.read(buf)?;
+            self.1.fetch_add(n, Ordering::SeqCst);
+            Ok(n)
+        }
+    }
+    let taken = Arc::new(AtomicUsize::new(0));
+    let body: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let server = {
+        let (body, taken) = (body.clone(), Arc::clone(&taken));
+        Server::new(move |_| {
+            let reader = Counted(io::Cursor::new(body.clone()), Arc::clone(&taken));
+            Response::new(200).stream(reader)
+        })
+    };
+    let mut client = Client::connect(server, Vec::new());
+
+    client.request(1, "GET", "/", true);
+    let (mut received, ended) = read_data(&mut client, 65_535);
+    assert!(!ended);
+    assert_no_data(&mut client);
+    assert_eq!(taken.load(Ordering::SeqCst), 65_535);
+
+    window_update(&mut client, 0, 50_000);
+    window_update(&mut client, 1, 50_000);
+    let (rest, ended) = read_data(&mut client, body.len() - 65_535);
+    received.extend(rest);
+
+    assert!(ended);
+    assert!(received == body, "streamed body differs");
+}
+
+#[test]
+fn initial_window_size_applies_to_open_streams() {
+    let mut client = connect(vec![(INITIAL_WINDOW_SIZE, 10)]);
+
+    client.request(1, "POST", "/", true);
+    let (first, ended) = read_data(&mut client, 10);
+    assert_eq!(first, b"Hello from");
+    assert!(!ended);
+    assert_no_data(&mut client);
+
+    // Raising the setting grows the open stream's window by the difference
+    client.send(Frame::Settings {
+        ack: false,
+        parameters: vec![(INITIAL_WINDOW_SIZE, 100)],
+    });
+    let (rest, ended) = read_data(&mut client, 19);
+    assert_eq!(rest, b" toy HTTP/2 server!");
+    assert!(ended);
+}
+
+#[test]
+fn servers_can_set_their_own_stream_window() {
+    let mut client = Client::connect(Server::default().initial_window_size(100), Vec::new());
+    assert_eq!(
+        client.read_frame(),
+        Some(Frame::Settings {
+            ack: false,
+            parameters: vec![
+                (MAX_CONCURRENT_STREAMS, DEFAULT_MAX_CONCURRENT_STREAMS),
+                (INITIAL_WINDOW_SIZE, 100),
+            ],
+        })
+    );
+    client.request(1, "POST", "/", false);
+    let data = |len| Frame::Data {
+        stream_id: 1,
+        data: vec![b'a'; len],
+        end_stream: false,
+        padding: None,
+    };
+
+    // All of the window may be used, after which the server opens it again
+    client.send(data(100));
+    let update = client.expect(|f| matches!(f, Frame::WindowUpdate { stream_id: 1, .. }));
+    assert_eq!(
+        update,
+        Frame::WindowUpdate {
+            stream_id: 1,
+            increment: 100
+        }
+    );
+    client.send(data(101));
+    assert_eq!(client.expect_reset(), (1, ErrorCode::FlowControlError));
+}
+
+#[test]
+fn bad_stream_window_updates_reset_only_the_stream() {
+    let mut client = connect(Vec::new());
+
+    // Streams left open by requests that have not finished
+    client.request(1, "POST", "/", false);
+    client.request(3, "POST", "/", false);
+    window_update(&mut client, 1, (1 << 31) - 1);
+    assert_eq!(client.expect_reset(), (1, ErrorCode::FlowControlError));
+    window_update(&mut client, 3, 0);
+    assert_eq!(client.expect_reset(), (3, ErrorCode::ProtocolError));
+
+    // The connection carries on
+    client.request(5, "POST", "/", true);
+    let (body, ended) = read_data(&mut client, 29);
+    assert_eq!(body, b"Hello from toy HTTP/2 server!");
+    assert!(ended);
+}
+
+#[test]
+fn bad_connection_window_updates_close_the_connection() {
+    for (increment, code) in [
+        ((1 << 31) - 1, ErrorCode::FlowControlError),
+        (0, ErrorCode::ProtocolError),
+    ] {
+        let mut client = connect(Vec::new());
+        window_update(&mut client, 0, increment);
+
+        assert_eq!(client.expect_goaway(), code);
+    }
+}
@@ -0,0 +1,143 @@
+use http_2_server::Server;
+use toy_http2::{
+    ErrorCode, Frame,
+    frame::Priority,
+    hpack::{Decoder, Encoder},
+};
+
+mod common;
+use common::Client;
+
+fn connect() -> Client {
+    Client::connect(Server::default(), Vec::new())
+}
+
+fn request_block() -> Vec<u8> {
+    Encoder::default().encode([
+        (":method", "POST"),
+        (":scheme", "http"),
+        (":path", "/"),
+        ("x-long", &"a".repeat(100)),
+    ])
+}
+
+#[test]
+fn pings_are_answered() {
+    let mut client = connect();
+
+    client.send(Frame::Ping {
+        ack: false,
+        data: *b"toyping!",
+    });
+    let pong = client.expect(|f| matches!(f, Frame::Ping { .. }));
+    assert_eq!(
+        pong,
+        Frame::Ping {
+            ack: true,
+            data: *b"toyping!"
+        }
+    );
+}
+
+#[test]
+fn header_blocks_continue_and_padding_is_dropped() {
+    let mut client = connect();
+
+    // The block is split three ways, with a PRIORITY frame on another stream first
+    let block = request_block();
+    client.send(Frame::Priority {
+        stream_id: 5,
+        priority: Priority {
+            dependency: 0,
+            exclusive: false,
+            weight: 15,
+        },
+    });
+    client.send(Frame::Headers {
+        stream_id: 1,
+        block: block[..10].to_vec(),
+        priority: None,
+        end_stream: false,
+        end_headers: false,
+        padding: Some(20),
+    });
+    client.send(Frame::Continuation {
+        stream_id: 1,
+        block: block[10..50].to_vec(),
+        end_headers: false,
+    });
+    client.send(Frame::Continuation {
+        stream_id: 1,
+        block: block[50..].to_vec(),
+        end_headers: true,
+    });
+    client.send(Frame::Data {
+        stream_id: 1,
+        data: b"padded body".to_vec(),
+        end_stream: true,
+        padding: Some(100),
+    });
+
+    let head = client.expect(|f| matches!(f, Frame::Headers { .. }));
+    let Frame::Headers { block, .. } = head else {
+        unreachable!()
+    };
+    let headers = Decoder::default().decode(&block).unwrap();
+    assert_eq!(headers[0], (":status".to_string(), "200".to_string()));
+
+    let body = client.expect(|f| matches!(f, Frame::Data { .. }));
+    assert_eq!(
+        body,
+        Frame::Data {
+            stream_id: 1,
+            data: b"padded body".to_vec(),
+            end_stream: true,
+            padding: None,
+        }
+    );
+}
+
+#[test]
+fn interrupted_header_block_is_a_protocol_error() {
+    let mut client = connect();
+    client.send(Frame::Headers {
+        stream_id: 1,
+        block: request_block()[..10].to_vec(),
+        priority: None,
+        end_stream: true,
+        end_headers: false,
+        padding: None,
+    });
+    client.send(Frame::Settings {
+        ack: false,
+        parameters: Vec::new(),
+    });
+
+    assert_eq!(client.expect_goaway(), ErrorCode::ProtocolError);
+}
+
+#[test]
+fn continuation_without_headers_is_a_protocol_error() {
+    let mut client = connect();
+    client.send(Frame::Continuation {
+        stream_id: 1,
+        block: request_block(),
+        end_headers: true,
+    });
+
+    assert_eq!(client.expect_goaway(), ErrorCode::ProtocolError);
+}
+
+#[test]
+fn clients_cannot_push() {
+    let mut client = connect();
+    client.send(Frame::PushPromise {
+        stream_id: 1,
+        promised_id: 2,
+        block: request_block(),
+        end_headers: true,
+        padding: None,
+    });
+
+    assert_eq!(client.expect_goaway(), ErrorCode::ProtocolError);
+}
@@ -1,66 +1,91 @@
-use std::{
-    io::{Read, Write},
-    net::{TcpListener, TcpStream},
-    thread,
-    time::Duration,
-};
+use http_2_server::Server;
+use toy_http2::{Frame, hpack::Decoder};
 
-use toy_http2::{handle_connection, send_frame, HEADERS, DATA}; // make functions pub in main.rs
+mod common;
+use common::Client;
 
-fn start_server() -> u16 {
-    let listener = TcpListener::bind("127.0.0.1:0").unwrap(); // bind to random port
-    let port = listener.local_addr().unwrap().port();
+// Send the preface and empty SETTINGS, then take the server's SETTINGS and the ACK of ours
+fn handshake() -> Client {
+    let mut client = Client::connect(Server::default(), Vec::new());
 
-    thread::spawn(move || {
-        for stream in listener.incoming() {
-            if let Ok(mut stream) = stream {
-                handle_connection(&mut stream);
-            }
+    let mut acks = Vec::new();
+    for _ in 0..2 {
+        match client.read_frame() {
+            Some(Frame::Settings { ack, .. }) => acks.push(ack),
+            other => panic!("expected SETTINGS, got {other:?}"),
         }
-    });
-
-    port
+    }
+    acks.sort();
+    assert_eq!(acks, [false, true]);
+    client
 }
 
-fn send_test_headers(stream: &mut TcpStream, stream_id: u32) {
-    // Very basic payload for fake HPACK-encoded headers
-    let payload = b":method:GET\0:path:/\0";
-    send_frame(stream, HEADERS, 0x4, stream_id, payload);
+// Read frames until one ends the stream
+fn read_frames(client: &mut Client) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    loop {
+        let frame = client.read_frame().expect("closed");
+        let ended = matches!(
+            frame,
+            Frame::Headers {
+                end_stream: true,
+                ..
+            } | Frame::Data {
+                end_stream: true,
+                ..
+            }
+        );
+        frames.push(frame);
+        if ended {
+            return frames;
+        }
+    }
 }
 
 #[test]
 fn test_http2_response_headers_and_data() {
-    let port = start_server();
-    thread::sleep(Duration::from_millis(100)); // wait for server
-
-    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
-
-    send_test_headers(&mut stream, 1);
-
-    let mut buf = vec![0u8; 1024];
-    let n = stream.read(&mut buf).unwrap();
-    assert!(n > 0);
+    let mut client = handshake();
+    let mut decoder = Decoder::default();
 
-    let mut pos = 0;
-    while pos + 9 <= n {
-        let len = ((buf[pos] as usize) << 16) | ((buf[pos + 1] as usize) << 8) | (buf[pos + 2] as usize);
-        let frame_type = buf[pos + 3];
-        let flags = buf[pos + 4];
-        let stream_id = u32::from_be_bytes([buf[pos + 5], buf[pos + 6], buf[pos + 7], buf[pos + 8]]) & 0x7FFFFFFF;
+    client.request(1, "GET", "/", true);
 
-        let payload = &buf[pos + 9..pos + 9 + len];
-
-        match frame_type {
-            HEADERS => {
-                let headers = String::from_utf8_lossy(payload);
-                assert!(headers.contains(":status:200"));
+    for frame in read_frames(&mut client) {
+        match frame {
+            Frame::Headers { block, .. } => {
+                let headers = decoder.decode(&block).unwrap();
+                assert!(headers.contains(&(":status".to_string(), "200".to_string())));
             }
-            DATA => {
-                let body = String::from_utf8_lossy(payload);
+            Frame::Data { data, .. } => {
+                let body = String::from_utf8_lossy(&data);
                 assert!(body.contains("Hello from toy HTTP/2 server!"));
             }
-            _ => panic!("Unexpected frame type: {}", frame_type),
+            _ => panic!("Unexpected frame: {frame:?}"),
         }
+    }
+}
+
+#[test]
+fn test_http2_header_table_carries_across_requests() {
+    let mut client = handshake();
+    let mut decoder = Decoder::default();
 
-        pos +=
+    for stream_id in [1, 3] {
+        client.request(stream_id, "GET", "/", true);
+        let Frame::Headers { block, .. } = read_frames(&mut client).remove(0) else {
+            panic!("response did not start with HEADERS");
+        };
+        let headers = decoder.decode(&block).unwrap();
 
+        assert_eq!(
+            headers,
+            [
+                (":status".to_string(), "200".to_string()),
+                ("content-type".to_string(), "text/plain".to_string()),
+            ]
+        );
+        if stream_id == 3 {
+            // content-type is now in the dynamic table, so the block is two indexes
+            assert_eq!(block.len(), 2);
+        }
+    }
+}
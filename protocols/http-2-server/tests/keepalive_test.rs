@@ -0,0 +1,120 @@
+use std::{
+    io::{ErrorKind, Read},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use http_2_server::{ConnectionOptions, Response, Server};
+use toy_http2::{
+    Frame,
+    settings::{INITIAL_WINDOW_SIZE, MAX_WINDOW_SIZE},
+};
+
+mod common;
+use common::Client;
+
+const INTERVAL: Duration = Duration::from_millis(50);
+const TIMEOUT: Duration = Duration::from_millis(300);
+
+// Connect and send the preface with empty SETTINGS, leaving the server's replies unread
+fn connect(server: Server) -> Client {
+    connect_with(server, Vec::new())
+}
+
+fn connect_with(server: Server, parameters: Vec<(u16, u32)>) -> Client {
+    let client = Client::connect(server, parameters);
+    client
+        .stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    client
+}
+
+// Skips frames until the server's next PING, returning its payload
+fn next_ping(client: &mut Client) -> Option<[u8; 8]> {
+    loop {
+        if let Frame::Ping { ack: false, data } = client.read_frame()? {
+            return Some(data);
+        }
+    }
+}
+
+#[test]
+fn acknowledged_pings_report_round_trips() {
+    let (tx, rx) = mpsc::channel();
+    let options = ConnectionOptions::new()
+        .keepalive(INTERVAL, TIMEOUT)
+        .on_rtt(move |rtt| tx.send(rtt).unwrap());
+    let mut client = connect(Server::default().options(options));
+
+    let mut payloads = Vec::new();
+    for _ in 0..3 {
+        let data = next_ping(&mut client).expect("closed");
+        thread::sleep(Duration::from_millis(20));
+        client.send(Frame::Ping { ack: true, data });
+        payloads.push(data);
+
+        let rtt = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(rtt >= Duration::from_millis(20), "{rtt:?}");
+        assert!(rtt < TIMEOUT, "{rtt:?}");
+    }
+
+    // Each PING carries a payload of its own, so a stale answer is not mistaken for a new one
+    payloads.dedup();
+    assert_eq!(payloads.len(), 3);
+}
+
+#[test]
+fn unanswered_pings_close_the_connection() {
+    let options = ConnectionOptions::new().keepalive(INTERVAL, TIMEOUT);
+    let mut client = connect(Server::default().options(options));
+
+    let start = Instant::now();
+    next_ping(&mut client).expect("closed");
+    // Only one PING is outstanding, and then the server gives up on us
+    assert_eq!(next_ping(&mut client), None);
+
+    let elapsed = start.elapsed();
+    assert!(elapsed >= TIMEOUT, "{elapsed:?}");
+    assert!(elapsed < Duration::from_secs(4), "{elapsed:?}");
+}
+
+#[test]
+fn clients_that_stop_reading_are_cut_off() {
+    const BODY: usize = 64 << 20;
+    let options = ConnectionOptions::new().keepalive(INTERVAL, TIMEOUT);
+    let server = Server::new(|_| Response::new(200).body(vec![0; BODY])).options(options);
+    // Window enough for the whole body, so only the socket holds it back
+    let mut client = connect_with(server, vec![(INITIAL_WINDOW_SIZE, MAX_WINDOW_SIZE)]);
+    client.send(Frame::WindowUpdate {
+        stream_id: 0,
+        increment: MAX_WINDOW_SIZE - 65_535,
+    });
+    client.request(1, "GET", "/", true);
+
+    // Stalled long enough, the server gives up rather than finishing the body
+    thread::sleep(Duration::from_secs(1));
+    let mut received = 0;
+    let mut chunk = [0; 1 << 16];
+    loop {
+        match client.stream.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => received += n,
+            Err(e) if e.kind() == ErrorKind::ConnectionReset => break,
+            Err(e) => panic!("connection left open: {e}"),
+        }
+    }
+    assert!(received < BODY, "the whole body was sent");
+}
+
+#[test]
+fn pings_are_off_by_default() {
+    let mut client = connect(Server::default());
+    client
+        .stream
+        .set_read_timeout(Some(Duration::from_millis(300)))
+        .unwrap();
+
+    assert_eq!(next_ping(&mut client), None);
+}
@@ -0,0 +1,233 @@
+use std::{thread, time::Duration};
+
+use http_2_server::{Request, Response, Server};
+use toy_http2::{ErrorCode, Frame, hpack::Decoder, settings::INITIAL_WINDOW_SIZE};
+
+mod common;
+use common::Client;
+
+const SLOW: Duration = Duration::from_millis(300);
+
+// "/slow" takes a while, "/big/N" answers with N bytes, and anything else with its path
+fn handler(request: &Request) -> Response {
+    if request.path == "/slow" {
+        thread::sleep(SLOW);
+    }
+    let body = match request.path.strip_prefix("/big/") {
+        Some(len) => vec![b'x'; len.parse().unwrap()],
+        None => request.path.clone().into_bytes(),
+    };
+    Response::new(200).body(body)
+}
+
+fn get(client: &mut Client, stream_id: u32, path: &str) {
+    client.request(stream_id, "GET", path, true);
+}
+
+// Reads one whole response, skipping frames for other streams
+fn read_response(client: &mut Client, decoder: &mut Decoder, wanted: u32) -> Vec<u8> {
+    let mut body = Vec::new();
+    loop {
+        match client.read_frame().expect("closed") {
+            // Every block must go through the decoder to keep its table in step
+            Frame::Headers {
+                stream_id,
+                block,
+                end_stream,
+                ..
+            } => {
+                let headers = decoder.decode(&block).unwrap();
+                if stream_id == wanted {
+                    assert!(headers.contains(&(":status".to_string(), "200".to_string())));
+                    if end_stream {
+                        return body;
+                    }
+                }
+            }
+            Frame::Data {
+                stream_id,
+                data,
+                end_stream,
+                ..
+            } if stream_id == wanted => {
+                body.extend(data);
+                if end_stream {
+                    return body;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn stream_id(frame: &Frame) -> u32 {
+    match frame {
+        Frame::Headers { stream_id, .. } | Frame::Data { stream_id, .. } => *stream_id,
+        _ => 0,
+    }
+}
+
+#[test]
+fn slow_handlers_do_not_hold_up_other_streams() {
+    let mut client = Client::connect(Server::new(handler), Vec::new());
+    let mut decoder = Decoder::default();
+
+    get(&mut client, 1, "/slow");
+    get(&mut client, 3, "/fast");
+
+    // The second request is answered first, then the first one still completes
+    let first = match client.expect(|f| matches!(f, Frame::Headers { .. })) {
+        Frame::Headers {
+            stream_id, block, ..
+        } => {
+            decoder.decode(&block).unwrap();
+            stream_id
+        }
+        _ => unreachable!(),
+    };
+    assert_eq!(first, 3);
+    assert_eq!(read_response(&mut client, &mut decoder, 1), b"/slow");
+}
+
+#[test]
+fn responses_share_the_connection() {
+    // With no stream window to start with, only the heads are sent
+    let mut client = Client::connect(Server::new(handler), vec![(INITIAL_WINDOW_SIZE, 0)]);
+    get(&mut client, 1, "/big/50000");
+    get(&mut client, 3, "/big/50000");
+    let heads = client.read_quiet(Duration::from_millis(200));
+    assert_eq!(heads.len(), 2);
+    assert!(heads.iter().all(|f| matches!(f, Frame::Headers { .. })));
+
+    // Opening both windows at once lets the bodies take turns until the connection's runs out
+    client.send(Frame::Settings {
+        ack: false,
+        parameters: vec![(INITIAL_WINDOW_SIZE, 65_535)],
+    });
+    let data = client.read_quiet(Duration::from_millis(200));
+    let order: Vec<u32> = data.iter().map(stream_id).collect();
+    assert_eq!(order, [1, 3, 1, 3]);
+    let sent: usize = data
+        .iter()
+        .map(|frame| match frame {
+            Frame::Data { data, .. } => data.len(),
+            _ => 0,
+        })
+        .sum();
+    assert_eq!(sent, 65_535);
+}
+
+#[test]
+fn streams_beyond_the_limit_are_refused() {
+    let mut client = Client::connect(Server::new(handler).max_concurrent_streams(1), Vec::new());
+    let mut decoder = Decoder::default();
+
+    get(&mut client, 1, "/slow");
+    get(&mut client, 3, "/fast");
+    assert_eq!(client.expect_reset(), (3, ErrorCode::RefusedStream));
+    assert_eq!(read_response(&mut client, &mut decoder, 1), b"/slow");
+
+    // Once the first stream has closed there is room again
+    get(&mut client, 5, "/again");
+    assert_eq!(read_response(&mut client, &mut decoder, 5), b"/again");
+}
+
+#[test]
+fn pushed_streams_do_not_count_against_the_limit() {
+    // "/" pushes two more responses, none of which can finish without window
+    let server = Server::new(|request| {
+        let response = handler(request);
+        if request.path == "/" {
+            response.push("/a").push("/b")
+        } else {
+            response
+        }
+    });
+    let mut client = Client::connect(
+        server.max_concurrent_streams(2),
+        vec![(INITIAL_WINDOW_SIZE, 0)],
+    );
+
+    get(&mut client, 1, "/");
+    client.expect(|f| matches!(f, Frame::Headers { stream_id: 1, .. }));
+    // Stream 1 and the two pushes are open, but only one of them is the client's
+    get(&mut client, 3, "/next");
+    client.expect(|f| matches!(f, Frame::Headers { stream_id: 3, .. }));
+}
+
+#[test]
+fn bodies_past_the_limit_get_413_and_a_reset() {
+    let mut client = Client::connect(Server::new(handler).max_body_size(10), Vec::new());
+    let mut decoder = Decoder::default();
+    let data = |end_stream| Frame::Data {
+        stream_id: 1,
+        data: vec![b'x'; 8],
+        end_stream,
+        padding: None,
+    };
+
+    client.request(1, "POST", "/upload", false);
+    client.send(data(false));
+    client.send(data(true));
+    let Frame::Headers {
+        block, end_stream, ..
+    } = client.expect(|f| matches!(f, Frame::Headers { stream_id: 1, .. }))
+    else {
+        unreachable!()
+    };
+    assert!(end_stream);
+    let headers = decoder.decode(&block).unwrap();
+    assert!(headers.contains(&(":status".to_string(), "413".to_string())));
+    assert_eq!(client.expect_reset(), (1, ErrorCode::NoError));
+
+    // Other streams carry on
+    get(&mut client, 3, "/next");
+    assert_eq!(read_response(&mut client, &mut decoder, 3), b"/next");
+}
+
+#[test]
+fn client_reset_cancels_the_response() {
+    let mut client = Client::connect(Server::new(handler), Vec::new());
+
+    get(&mut client, 1, "/slow");
+    client.send(Frame::RstStream {
+        stream_id: 1,
+        code: ErrorCode::Cancel,
+    });
+    get(&mut client, 3, "/fast");
+
+    // Nothing comes for the cancelled stream, even after its handler has finished
+    let frames = client.read_quiet(SLOW * 2);
+    assert!(frames.iter().all(|f| stream_id(f) == 3), "{frames:?}");
+    assert_eq!(frames.len(), 2);
+}
+
+#[test]
+fn frames_after_end_stream_close_the_stream() {
+    let mut client = Client::connect(Server::new(handler), Vec::new());
+    let mut decoder = Decoder::default();
+
+    get(&mut client, 1, "/slow");
+    client.send(Frame::Data {
+        stream_id: 1,
+        data: b"late".to_vec(),
+        end_stream: false,
+        padding: None,
+    });
+    assert_eq!(client.expect_reset(), (1, ErrorCode::StreamClosed));
+
+    // Only that stream is gone
+    get(&mut client, 3, "/fast");
+    assert_eq!(read_response(&mut client, &mut decoder, 3), b"/fast");
+}
+
+#[test]
+fn reset_of_an_idle_stream_is_a_connection_error() {
+    let mut client = Client::connect(Server::new(handler), Vec::new());
+
+    client.send(Frame::RstStream {
+        stream_id: 7,
+        code: ErrorCode::Cancel,
+    });
+    assert_eq!(client.expect_goaway(), ErrorCode::ProtocolError);
+}
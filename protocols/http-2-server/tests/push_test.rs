@@ -0,0 +1,150 @@
+use std::time::Duration;
+
+use http_2_server::{ConnectionOptions, Request, Response, Server};
+use toy_http2::{ErrorCode, Frame, hpack::Decoder, settings::ENABLE_PUSH};
+
+mod common;
+use common::Client;
+
+// "/" pushes its stylesheet and script; everything answers with its path
+fn handler(request: &Request) -> Response {
+    let response = Response::new(200).body(format!("body of {}", request.path));
+    if request.path == "/" {
+        response.push("/style.css").push("/app.js")
+    } else {
+        response
+    }
+}
+
+// Connect and send the preface with SETTINGS `parameters`, then GET `/` on stream 1
+fn connect(options: ConnectionOptions, parameters: Vec<(u16, u32)>) -> Client {
+    let mut client = Client::connect(Server::new(handler).options(options), parameters);
+    client.request(1, "GET", "/", true);
+    client
+}
+
+// Reads frames other than SETTINGS until none arrive for a while
+fn read_quiet(client: &mut Client) -> Vec<Frame> {
+    client.read_quiet(Duration::from_millis(300))
+}
+
+fn body(frames: &[Frame], wanted: u32) -> String {
+    let mut body = Vec::new();
+    for frame in frames {
+        if let Frame::Data {
+            stream_id, data, ..
+        } = frame
+            && *stream_id == wanted
+        {
+            body.extend_from_slice(data);
+        }
+    }
+    String::from_utf8(body).unwrap()
+}
+
+fn assert_no_push(frames: &[Frame]) {
+    assert!(
+        !frames
+            .iter()
+            .any(|frame| matches!(frame, Frame::PushPromise { .. })),
+        "{frames:?}"
+    );
+    assert_eq!(body(frames, 1), "body of /");
+}
+
+#[test]
+fn pushes_are_promised_before_the_response_then_answered() {
+    let mut client = connect(ConnectionOptions::new(), Vec::new());
+    let frames = read_quiet(&mut client);
+
+    // Header blocks share one dynamic table, so they are decoded in arrival order
+    let mut decoder = Decoder::default();
+    let mut promises = Vec::new();
+    let mut response_at = None;
+    for (i, frame) in frames.iter().enumerate() {
+        match frame {
+            Frame::PushPromise {
+                stream_id,
+                promised_id,
+                block,
+                ..
+            } => {
+                assert_eq!(*stream_id, 1);
+                assert!(response_at.is_none(), "promise after the response");
+                promises.push((*promised_id, decoder.decode(block).unwrap()));
+            }
+            Frame::Headers {
+                stream_id, block, ..
+            } => {
+                decoder.decode(block).unwrap();
+                if *stream_id == 1 {
+                    response_at = Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    assert!(response_at.is_some());
+    let promised_ids: Vec<u32> = promises.iter().map(|(id, _)| *id).collect();
+    assert_eq!(promised_ids, [2, 4]);
+    let (_, fields) = &promises[0];
+    for field in [
+        (":method", "GET"),
+        (":path", "/style.css"),
+        (":authority", "localhost"),
+    ] {
+        assert!(
+            fields.contains(&(field.0.to_string(), field.1.to_string())),
+            "{fields:?}"
+        );
+    }
+
+    assert_eq!(body(&frames, 1), "body of /");
+    assert_eq!(body(&frames, 2), "body of /style.css");
+    assert_eq!(body(&frames, 4), "body of /app.js");
+}
+
+#[test]
+fn clients_can_refuse_pushes() {
+    let mut client = connect(ConnectionOptions::new(), vec![(ENABLE_PUSH, 0)]);
+    assert_no_push(&read_quiet(&mut client));
+}
+
+#[test]
+fn pushes_can_be_turned_off() {
+    let mut client = connect(ConnectionOptions::new().push(false), Vec::new());
+    assert_no_push(&read_quiet(&mut client));
+}
+
+#[test]
+fn pushed_streams_can_be_cancelled() {
+    let mut client = connect(ConnectionOptions::new(), Vec::new());
+    read_quiet(&mut client);
+
+    // Pushed streams are closed once answered, so a late cancel is harmless
+    client.send(Frame::RstStream {
+        stream_id: 2,
+        code: ErrorCode::Cancel,
+    });
+    client.request(3, "GET", "/other", true);
+    let frames = read_quiet(&mut client);
+    assert_eq!(body(&frames, 3), "body of /other");
+
+    // Even ids beyond those promised are idle
+    client.send(Frame::RstStream {
+        stream_id: 10,
+        code: ErrorCode::Cancel,
+    });
+    let frames = read_quiet(&mut client);
+    assert!(
+        matches!(
+            frames.last(),
+            Some(Frame::GoAway {
+                code: ErrorCode::ProtocolError,
+                ..
+            })
+        ),
+        "{frames:?}"
+    );
+}
@@ -0,0 +1,151 @@
+use http_2_server::{ACK, DEFAULT_MAX_CONCURRENT_STREAMS, SETTINGS, Server, send_frame};
+use toy_http2::{
+    ErrorCode, Frame, PREFACE,
+    hpack::Decoder,
+    settings::{HEADER_TABLE_SIZE, INITIAL_WINDOW_SIZE, MAX_CONCURRENT_STREAMS, MAX_FRAME_SIZE},
+};
+
+mod common;
+use common::Client;
+
+fn settings(parameters: Vec<(u16, u32)>) -> Frame {
+    Frame::Settings {
+        ack: false,
+        parameters,
+    }
+}
+
+#[test]
+fn settings_are_exchanged_and_acknowledged() {
+    let mut client = Client::connect(Server::default(), vec![(INITIAL_WINDOW_SIZE, 1 << 20)]);
+
+    // The server's own SETTINGS come first, then the acknowledgement of ours
+    assert_eq!(
+        client.read_frame(),
+        Some(settings(vec![(
+            MAX_CONCURRENT_STREAMS,
+            DEFAULT_MAX_CONCURRENT_STREAMS
+        )]))
+    );
+    let ack = Frame::Settings {
+        ack: true,
+        parameters: Vec::new(),
+    };
+    assert_eq!(client.read_frame().as_ref(), Some(&ack));
+
+    // Our acknowledgement of theirs needs no answer, and a later change is acknowledged again
+    client.send(ack.clone());
+    client.send(settings(vec![(MAX_FRAME_SIZE, 1 << 20)]));
+    assert_eq!(client.read_frame(), Some(ack));
+}
+
+#[test]
+fn bad_preface_gets_protocol_error() {
+    let mut client = Client::open(Server::default());
+    client.write(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+
+    assert_eq!(client.expect_goaway(), ErrorCode::ProtocolError);
+}
+
+#[test]
+fn first_frame_must_be_settings() {
+    let mut client = Client::open(Server::default());
+    client.write(PREFACE);
+    client.request(1, "GET", "/", true);
+
+    assert_eq!(client.expect_goaway(), ErrorCode::ProtocolError);
+}
+
+#[test]
+fn invalid_settings_close_the_connection() {
+    let parameter = |id: u16, value: u32| [&id.to_be_bytes()[..], &value.to_be_bytes()].concat();
+    for (payload, stream_id, flags, code) in [
+        (
+            parameter(MAX_FRAME_SIZE, 100),
+            0,
+            0,
+            ErrorCode::ProtocolError,
+        ),
+        (
+            parameter(INITIAL_WINDOW_SIZE, 1 << 31),
+            0,
+            0,
+            ErrorCode::FlowControlError,
+        ),
+        (vec![0; 4], 0, 0, ErrorCode::FrameSizeError),
+        (vec![], 1, 0, ErrorCode::ProtocolError),
+        (vec![], 0, ACK, ErrorCode::ProtocolError),
+    ] {
+        // Sent raw, as most of these cannot be expressed as a `Frame`
+        let mut client = Client::open(Server::default());
+        client.write(PREFACE);
+        send_frame(&mut client.stream, SETTINGS, flags, stream_id, &payload).unwrap();
+
+        assert_eq!(client.expect_goaway(), code, "{payload:?}");
+    }
+}
+
+#[test]
+fn oversized_frames_get_frame_size_error() {
+    let mut client = Client::connect(Server::default(), Vec::new());
+    // One byte over the default SETTINGS_MAX_FRAME_SIZE
+    client.send(Frame::Unknown {
+        frame_type: 0xfa,
+        flags: 0,
+        stream_id: 0,
+        payload: vec![0; (1 << 14) + 1],
+    });
+
+    assert_eq!(client.expect_goaway(), ErrorCode::FrameSizeError);
+}
+
+#[test]
+fn servers_can_accept_larger_frames() {
+    let server = Server::default().max_frame_size(1 << 15);
+    let mut client = Client::connect(server, Vec::new());
+    assert_eq!(
+        client.read_frame(),
+        Some(settings(vec![
+            (MAX_CONCURRENT_STREAMS, DEFAULT_MAX_CONCURRENT_STREAMS),
+            (MAX_FRAME_SIZE, 1 << 15),
+        ]))
+    );
+    let unknown = |len| Frame::Unknown {
+        frame_type: 0xfa,
+        flags: 0,
+        stream_id: 0,
+        payload: vec![0; len],
+    };
+
+    // Up to the advertised size is ignored like any unknown frame, and one byte more is not
+    client.send(unknown(1 << 15));
+    client.request(1, "GET", "/", true);
+    client.expect(|f| matches!(f, Frame::Headers { stream_id: 1, .. }));
+    client.send(unknown((1 << 15) + 1));
+    assert_eq!(client.expect_goaway(), ErrorCode::FrameSizeError);
+}
+
+#[test]
+fn header_table_size_is_applied_to_responses() {
+    let mut client = Client::connect(Server::default(), vec![(HEADER_TABLE_SIZE, 0)]);
+    let mut decoder = Decoder::new(0);
+
+    let mut blocks = Vec::new();
+    for stream_id in [1, 3] {
+        client.request(stream_id, "GET", "/", true);
+        match client.expect(|f| matches!(f, Frame::Headers { .. })) {
+            Frame::Headers { block, .. } => blocks.push(block),
+            _ => unreachable!(),
+        }
+    }
+
+    // The first block announces the empty table, and nothing is indexed after that
+    assert_eq!(blocks[0][0], 0x20);
+    assert_eq!(blocks[1].len(), blocks[0].len() - 1);
+    for block in &blocks {
+        assert_eq!(
+            decoder.decode(block).unwrap()[1],
+            ("content-type".to_string(), "text/plain".to_string())
+        );
+    }
+}
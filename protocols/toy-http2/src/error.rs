@@ -0,0 +1,61 @@
+//! Error codes carried by RST_STREAM and GOAWAY frames (RFC 9113 section 7).
+
+use std::{error::Error, fmt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    NoError,
+    ProtocolError,
+    InternalError,
+    FlowControlError,
+    SettingsTimeout,
+    StreamClosed,
+    FrameSizeError,
+    RefusedStream,
+    Cancel,
+    CompressionError,
+    ConnectError,
+    EnhanceYourCalm,
+    InadequateSecurity,
+    Http11Required,
+}
+
+const CODES: [(ErrorCode, &str); 14] = [
+    (ErrorCode::NoError, "NO_ERROR"),
+    (ErrorCode::ProtocolError, "PROTOCOL_ERROR"),
+    (ErrorCode::InternalError, "INTERNAL_ERROR"),
+    (ErrorCode::FlowControlError, "FLOW_CONTROL_ERROR"),
+    (ErrorCode::SettingsTimeout, "SETTINGS_TIMEOUT"),
+    (ErrorCode::StreamClosed, "STREAM_CLOSED"),
+    (ErrorCode::FrameSizeError, "FRAME_SIZE_ERROR"),
+    (ErrorCode::RefusedStream, "REFUSED_STREAM"),
+    (ErrorCode::Cancel, "CANCEL"),
+    (ErrorCode::CompressionError, "COMPRESSION_ERROR"),
+    (ErrorCode::ConnectError, "CONNECT_ERROR"),
+    (ErrorCode::EnhanceYourCalm, "ENHANCE_YOUR_CALM"),
+    (ErrorCode::InadequateSecurity, "INADEQUATE_SECURITY"),
+    (ErrorCode::Http11Required, "HTTP_1_1_REQUIRED"),
+];
+
+impl ErrorCode {
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+}
+
+impl From<u32> for ErrorCode {
+    /// Codes this side does not know are treated as INTERNAL_ERROR.
+    fn from(code: u32) -> Self {
+        CODES
+            .get(code as usize)
+            .map_or(ErrorCode::InternalError, |&(code, _)| code)
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(CODES[*self as usize].1)
+    }
+}
+
+impl Error for ErrorCode {}
@@ -0,0 +1,124 @@
+//! Flow-control windows (RFC 9113 section 5.2).
+
+use crate::{ErrorCode, settings::MAX_WINDOW_SIZE};
+
+/// How many bytes of DATA payload may still be sent on a stream or connection.
+///
+/// A window can drop below zero when SETTINGS_INITIAL_WINDOW_SIZE shrinks, leaving
+/// nothing available until enough WINDOW_UPDATE increments arrive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window(i64);
+
+impl Window {
+    pub fn new(size: u32) -> Self {
+        Self(size as i64)
+    }
+
+    /// Bytes that may be sent right now.
+    pub fn available(self) -> usize {
+        self.0.max(0) as usize
+    }
+
+    /// Takes `len` bytes out, failing if the window does not hold them, i.e. if the peer
+    /// sent more than it was allowed to.
+    pub fn consume(&mut self, len: usize) -> Result<(), ErrorCode> {
+        if len as i64 > self.0 {
+            return Err(ErrorCode::FlowControlError);
+        }
+        self.0 -= len as i64;
+        Ok(())
+    }
+
+    /// Applies a WINDOW_UPDATE increment or a change of SETTINGS_INITIAL_WINDOW_SIZE,
+    /// failing if the window would grow past its largest size.
+    pub fn grow(&mut self, delta: i64) -> Result<(), ErrorCode> {
+        let grown = self.0 + delta;
+        if grown > MAX_WINDOW_SIZE as i64 {
+            return Err(ErrorCode::FlowControlError);
+        }
+        self.0 = grown;
+        Ok(())
+    }
+
+    /// For a window we grant: once half of `target` has been used, tops the window back
+    /// up and returns the increment to send in a WINDOW_UPDATE.
+    pub fn replenish(&mut self, target: u32) -> Option<u32> {
+        if self.0 > target as i64 / 2 {
+            return None;
+        }
+        let increment = target as i64 - self.0;
+        self.0 = target as i64;
+        Some(increment as u32)
+    }
+}
+
+/// Reads the increment out of a WINDOW_UPDATE payload.
+///
+/// A wrong length is a connection error; a zero increment is an error on whichever stream
+/// or connection the frame was sent for.
+pub fn decode_increment(payload: &[u8]) -> Result<u32, ErrorCode> {
+    let bytes: [u8; 4] = payload.try_into().map_err(|_| ErrorCode::FrameSizeError)?;
+    match u32::from_be_bytes(bytes) & MAX_WINDOW_SIZE {
+        0 => Err(ErrorCode::ProtocolError),
+        increment => Ok(increment),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sending_stops_at_the_window() {
+        let mut window = Window::new(100);
+
+        window.consume(60).unwrap();
+        assert_eq!(window.available(), 40);
+        assert_eq!(window.consume(41), Err(ErrorCode::FlowControlError));
+        window.consume(40).unwrap();
+        assert_eq!(window.available(), 0);
+    }
+
+    #[test]
+    fn windows_can_go_negative_but_not_overflow() {
+        let mut window = Window::new(100);
+        window.consume(80).unwrap();
+
+        // SETTINGS_INITIAL_WINDOW_SIZE cut from 100 to 50
+        window.grow(-50).unwrap();
+        assert_eq!(window, Window(-30));
+        assert_eq!(window.available(), 0);
+
+        window.grow(40).unwrap();
+        assert_eq!(window.available(), 10);
+
+        assert_eq!(
+            window.grow(MAX_WINDOW_SIZE as i64),
+            Err(ErrorCode::FlowControlError)
+        );
+        assert_eq!(window.available(), 10);
+    }
+
+    #[test]
+    fn replenish_waits_for_half_the_window() {
+        let mut window = Window::new(100);
+
+        window.consume(49).unwrap();
+        assert_eq!(window.replenish(100), None);
+        window.consume(1).unwrap();
+        assert_eq!(window.replenish(100), Some(50));
+        assert_eq!(window.available(), 100);
+    }
+
+    #[test]
+    fn increments_are_checked() {
+        assert_eq!(decode_increment(&[0, 0, 1, 0]), Ok(256));
+        // The reserved bit is ignored
+        assert_eq!(decode_increment(&[0x80, 0, 0, 1]), Ok(1));
+        assert_eq!(
+            decode_increment(&[0, 0, 0, 0]),
+            Err(ErrorCode::ProtocolError)
+        );
+        assert_eq!(decode_increment(&[0, 1]), Err(ErrorCode::FrameSizeError));
+    }
+}
@@ -0,0 +1,750 @@
+//! Frames (RFC 9113 section 6): a typed payload for each frame type, read from and
+//! written to the wire with its 9-byte header.
+
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Write},
+};
+
+use crate::{ErrorCode, flow::decode_increment};
+
+pub const FRAME_HEADER_LEN: usize = 9;
+
+// Frame types
+pub const DATA: u8 = 0x0;
+pub const HEADERS: u8 = 0x1;
+pub const PRIORITY: u8 = 0x2;
+pub const RST_STREAM: u8 = 0x3;
+pub const SETTINGS: u8 = 0x4;
+pub const PUSH_PROMISE: u8 = 0x5;
+pub const PING: u8 = 0x6;
+pub const GOAWAY: u8 = 0x7;
+pub const WINDOW_UPDATE: u8 = 0x8;
+pub const CONTINUATION: u8 = 0x9;
+
+// Flags
+pub const END_STREAM: u8 = 0x1;
+pub const ACK: u8 = 0x1;
+pub const END_HEADERS: u8 = 0x4;
+pub const PADDED: u8 = 0x8;
+pub const PRIORITY_FLAG: u8 = 0x20;
+
+/// Why a frame is invalid, and how much of the connection that breaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    /// The connection has to be closed with a GOAWAY carrying the code.
+    Connection(ErrorCode, &'static str),
+    /// Only the stream has to be reset with a RST_STREAM carrying the code.
+    Stream(u32, ErrorCode),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::Connection(code, reason) => write!(f, "{code}: {reason}"),
+            FrameError::Stream(stream_id, code) => write!(f, "{code} on stream {stream_id}"),
+        }
+    }
+}
+
+impl Error for FrameError {}
+
+/// Where a stream sits in the priority tree. Carried on the wire, but never acted on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Priority {
+    pub dependency: u32,
+    pub exclusive: bool,
+    /// One less than the weight, as sent.
+    pub weight: u8,
+}
+
+/// One frame. `padding` holds the pad length of frames sent with the PADDED flag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    Data {
+        stream_id: u32,
+        data: Vec<u8>,
+        end_stream: bool,
+        padding: Option<u8>,
+    },
+    Headers {
+        stream_id: u32,
+        /// The header block fragment; CONTINUATION frames follow unless `end_headers`.
+        block: Vec<u8>,
+        priority: Option<Priority>,
+        end_stream: bool,
+        end_headers: bool,
+        padding: Option<u8>,
+    },
+    Priority {
+        stream_id: u32,
+        priority: Priority,
+    },
+    RstStream {
+        stream_id: u32,
+        code: ErrorCode,
+    },
+    Settings {
+        ack: bool,
+        /// Parameter ids and values, in the order sent.
+        parameters: Vec<(u16, u32)>,
+    },
+    PushPromise {
+        stream_id: u32,
+        promised_id: u32,
+        block: Vec<u8>,
+        end_headers: bool,
+        padding: Option<u8>,
+    },
+    Ping {
+        ack: bool,
+        data: [u8; 8],
+    },
+    GoAway {
+        last_stream_id: u32,
+        code: ErrorCode,
+        debug_data: Vec<u8>,
+    },
+    WindowUpdate {
+        stream_id: u32,
+        increment: u32,
+    },
+    Continuation {
+        stream_id: u32,
+        block: Vec<u8>,
+        end_headers: bool,
+    },
+    /// A frame of a type this crate does not know, which receivers must ignore.
+    Unknown {
+        frame_type: u8,
+        flags: u8,
+        stream_id: u32,
+        payload: Vec<u8>,
+    },
+}
+
+impl Frame {
+    /// Decodes the frame at the start of `buf`, returning it and how many bytes it took up,
+    /// or `None` until `buf` holds all of it.
+    ///
+    /// A frame longer than `max_frame_size` fails as soon as its header is in.
+    pub fn parse(buf: &[u8], max_frame_size: u32) -> Result<Option<(Frame, usize)>, FrameError> {
+        let Some(header) = buf.get(..FRAME_HEADER_LEN) else {
+            return Ok(None);
+        };
+        let len = ((header[0] as usize) << 16) | ((header[1] as usize) << 8) | header[2] as usize;
+        if len > max_frame_size as usize {
+            return Err(FrameError::Connection(
+                ErrorCode::FrameSizeError,
+                "frame larger than SETTINGS_MAX_FRAME_SIZE",
+            ));
+        }
+        let Some(payload) = buf.get(FRAME_HEADER_LEN..FRAME_HEADER_LEN + len) else {
+            return Ok(None);
+        };
+
+        let stream_id = stream_id(&header[5..]);
+        let frame = Frame::decode(header[3], header[4], stream_id, payload)?;
+        Ok(Some((frame, FRAME_HEADER_LEN + len)))
+    }
+
+    /// Decodes a frame from the fields of its header and its payload.
+    pub fn decode(
+        frame_type: u8,
+        flags: u8,
+        stream_id: u32,
+        payload: &[u8],
+    ) -> Result<Frame, FrameError> {
+        let on_stream = matches!(
+            frame_type,
+            DATA | HEADERS | PRIORITY | RST_STREAM | PUSH_PROMISE | CONTINUATION
+        );
+        let on_connection = matches!(frame_type, SETTINGS | PING | GOAWAY);
+        if on_stream && stream_id == 0 {
+            return Err(FrameError::Connection(
+                ErrorCode::ProtocolError,
+                "stream frame on stream 0",
+            ));
+        }
+        if on_connection && stream_id != 0 {
+            return Err(FrameError::Connection(
+                ErrorCode::ProtocolError,
+                "connection frame on a stream",
+            ));
+        }
+
+        let frame = match frame_type {
+            DATA => {
+                let (data, padding) = strip_padding(flags, payload)?;
+                Frame::Data {
+                    stream_id,
+                    data: data.to_vec(),
+                    end_stream: flags & END_STREAM != 0,
+                    padding,
+                }
+            }
+            HEADERS => {
+                let (mut block, padding) = strip_padding(flags, payload)?;
+                let mut priority = None;
+                if flags & PRIORITY_FLAG != 0 {
+                    let Some((fields, rest)) = block.split_first_chunk::<5>() else {
+                        return Err(FrameError::Connection(
+                            ErrorCode::FrameSizeError,
+                            "HEADERS too short for its priority",
+                        ));
+                    };
+                    priority = Some(decode_priority(stream_id, fields)?);
+                    block = rest;
+                }
+                Frame::Headers {
+                    stream_id,
+                    block: block.to_vec(),
+                    priority,
+                    end_stream: flags & END_STREAM != 0,
+                    end_headers: flags & END_HEADERS != 0,
+                    padding,
+                }
+            }
+            PRIORITY => {
+                let fields = payload
+                    .try_into()
+                    .map_err(|_| FrameError::Stream(stream_id, ErrorCode::FrameSizeError))?;
+                Frame::Priority {
+                    stream_id,
+                    priority: decode_priority(stream_id, fields)?,
+                }
+            }
+            RST_STREAM => {
+                let code: [u8; 4] = payload.try_into().map_err(|_| {
+                    FrameError::Connection(
+                        ErrorCode::FrameSizeError,
+                        "RST_STREAM payload is not 4 bytes",
+                    )
+                })?;
+                Frame::RstStream {
+                    stream_id,
+                    code: u32::from_be_bytes(code).into(),
+                }
+            }
+            SETTINGS => {
+                let ack = flags & ACK != 0;
+                if ack && !payload.is_empty() {
+                    return Err(FrameError::Connection(
+                        ErrorCode::FrameSizeError,
+                        "SETTINGS acknowledgement with a payload",
+                    ));
+                }
+                if !payload.len().is_multiple_of(6) {
+                    return Err(FrameError::Connection(
+                        ErrorCode::FrameSizeError,
+                        "SETTINGS payload is not a whole number of parameters",
+                    ));
+                }
+                let parameters = payload
+                    .chunks(6)
+                    .map(|p| {
+                        let id = u16::from_be_bytes([p[0], p[1]]);
+                        (id, u32::from_be_bytes([p[2], p[3], p[4], p[5]]))
+                    })
+                    .collect();
+                Frame::Settings { ack, parameters }
+            }
+            PUSH_PROMISE => {
+                let (rest, padding) = strip_padding(flags, payload)?;
+                let Some((promised, block)) = rest.split_first_chunk::<4>() else {
+                    return Err(FrameError::Connection(
+                        ErrorCode::FrameSizeError,
+                        "PUSH_PROMISE too short for the promised stream id",
+                    ));
+                };
+                Frame::PushPromise {
+                    stream_id,
+                    promised_id: self::stream_id(promised),
+                    block: block.to_vec(),
+                    end_headers: flags & END_HEADERS != 0,
+                    padding,
+                }
+            }
+            PING => Frame::Ping {
+                ack: flags & ACK != 0,
+                data: payload.try_into().map_err(|_| {
+                    FrameError::Connection(ErrorCode::FrameSizeError, "PING payload is not 8 bytes")
+                })?,
+            },
+            GOAWAY => {
+                let Some((fields, debug_data)) = payload.split_first_chunk::<8>() else {
+                    return Err(FrameError::Connection(
+                        ErrorCode::FrameSizeError,
+                        "GOAWAY shorter than 8 bytes",
+                    ));
+                };
+                Frame::GoAway {
+                    last_stream_id: self::stream_id(&fields[..4]),
+                    code: u32::from_be_bytes([fields[4], fields[5], fields[6], fields[7]]).into(),
+                    debug_data: debug_data.to_vec(),
+                }
+            }
+            WINDOW_UPDATE => Frame::WindowUpdate {
+                stream_id,
+                increment: decode_increment(payload).map_err(|code| match code {
+                    ErrorCode::ProtocolError if stream_id != 0 => {
+                        FrameError::Stream(stream_id, code)
+                    }
+                    _ => FrameError::Connection(code, "invalid WINDOW_UPDATE"),
+                })?,
+            },
+            CONTINUATION => Frame::Continuation {
+                stream_id,
+                block: payload.to_vec(),
+                end_headers: flags & END_HEADERS != 0,
+            },
+            _ => Frame::Unknown {
+                frame_type,
+                flags,
+                stream_id,
+                payload: payload.to_vec(),
+            },
+        };
+        Ok(frame)
+    }
+
+    /// The stream the frame is sent on, 0 for the connection itself.
+    pub fn stream_id(&self) -> u32 {
+        match *self {
+            Frame::Data { stream_id, .. }
+            | Frame::Headers { stream_id, .. }
+            | Frame::Priority { stream_id, .. }
+            | Frame::RstStream { stream_id, .. }
+            | Frame::PushPromise { stream_id, .. }
+            | Frame::WindowUpdate { stream_id, .. }
+            | Frame::Continuation { stream_id, .. }
+            | Frame::Unknown { stream_id, .. } => stream_id,
+            Frame::Settings { .. } | Frame::Ping { .. } | Frame::GoAway { .. } => 0,
+        }
+    }
+
+    pub fn frame_type(&self) -> u8 {
+        match self {
+            Frame::Data { .. } => DATA,
+            Frame::Headers { .. } => HEADERS,
+            Frame::Priority { .. } => PRIORITY,
+            Frame::RstStream { .. } => RST_STREAM,
+            Frame::Settings { .. } => SETTINGS,
+            Frame::PushPromise { .. } => PUSH_PROMISE,
+            Frame::Ping { .. } => PING,
+            Frame::GoAway { .. } => GOAWAY,
+            Frame::WindowUpdate { .. } => WINDOW_UPDATE,
+            Frame::Continuation { .. } => CONTINUATION,
+            Frame::Unknown { frame_type, .. } => *frame_type,
+        }
+    }
+
+    /// The frame as sent, header included.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut flags = 0;
+        let mut payload = Vec::new();
+        let mut flag = |set: bool, bit: u8| {
+            if set {
+                flags |= bit;
+            }
+        };
+
+        match self {
+            Frame::Data {
+                data,
+                end_stream,
+                padding,
+                ..
+            } => {
+                flag(*end_stream, END_STREAM);
+                flag(padding.is_some(), PADDED);
+                pad(&mut payload, *padding, |payload| payload.extend(data));
+            }
+            Frame::Headers {
+                block,
+                priority,
+                end_stream,
+                end_headers,
+                padding,
+                ..
+            } => {
+                flag(*end_stream, END_STREAM);
+                flag(*end_headers, END_HEADERS);
+                flag(padding.is_some(), PADDED);
+                flag(priority.is_some(), PRIORITY_FLAG);
+                pad(&mut payload, *padding, |payload| {
+                    if let Some(priority) = priority {
+                        encode_priority(payload, priority);
+                    }
+                    payload.extend(block);
+                });
+            }
+            Frame::Priority { priority, .. } => encode_priority(&mut payload, priority),
+            Frame::RstStream { code, .. } => payload.extend(code.code().to_be_bytes()),
+            Frame::Settings { ack, parameters } => {
+                flag(*ack, ACK);
+                for (id, value) in parameters {
+                    payload.extend(id.to_be_bytes());
+                    payload.extend(value.to_be_bytes());
+                }
+            }
+            Frame::PushPromise {
+                promised_id,
+                block,
+                end_headers,
+                padding,
+                ..
+            } => {
+                flag(*end_headers, END_HEADERS);
+                flag(padding.is_some(), PADDED);
+                pad(&mut payload, *padding, |payload| {
+                    payload.extend((promised_id & 0x7FFFFFFF).to_be_bytes());
+                    payload.extend(block);
+                });
+            }
+            Frame::Ping { ack, data } => {
+                flag(*ack, ACK);
+                payload.extend(data);
+            }
+            Frame::GoAway {
+                last_stream_id,
+                code,
+                debug_data,
+            } => {
+                payload.extend((last_stream_id & 0x7FFFFFFF).to_be_bytes());
+                payload.extend(code.code().to_be_bytes());
+                payload.extend(debug_data);
+            }
+            Frame::WindowUpdate { increment, .. } => payload.extend(increment.to_be_bytes()),
+            Frame::Continuation {
+                block, end_headers, ..
+            } => {
+                flag(*end_headers, END_HEADERS);
+                payload.extend(block);
+            }
+            Frame::Unknown {
+                flags: unknown,
+                payload: bytes,
+                ..
+            } => {
+                flags = *unknown;
+                payload.extend(bytes);
+            }
+        }
+
+        let len = payload.len();
+        let mut out = Vec::with_capacity(FRAME_HEADER_LEN + len);
+        out.extend_from_slice(&(len as u32).to_be_bytes()[1..]);
+        out.push(self.frame_type());
+        out.push(flags);
+        out.extend_from_slice(&(self.stream_id() & 0x7FFFFFFF).to_be_bytes());
+        out.extend(payload);
+        out
+    }
+
+    /// Writes the frame to `out` and flushes it.
+    pub fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&self.encode())?;
+        out.flush()
+    }
+}
+
+/// Reads a stream id, ignoring the reserved bit.
+fn stream_id(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) & 0x7FFFFFFF
+}
+
+/// Splits the pad length and padding off a PADDED payload.
+fn strip_padding(flags: u8, payload: &[u8]) -> Result<(&[u8], Option<u8>), FrameError> {
+    if flags & PADDED == 0 {
+        return Ok((payload, None));
+    }
+    let Some((&pad_len, rest)) = payload.split_first() else {
+        return Err(FrameError::Connection(
+            ErrorCode::FrameSizeError,
+            "padded frame without a pad length",
+        ));
+    };
+    if pad_len as usize > rest.len() {
+        return Err(FrameError::Connection(
+            ErrorCode::ProtocolError,
+            "padding longer than the payload",
+        ));
+    }
+    Ok((&rest[..rest.len() - pad_len as usize], Some(pad_len)))
+}
+
+/// Writes the pad length, whatever `body` writes, then that much padding.
+fn pad(payload: &mut Vec<u8>, padding: Option<u8>, body: impl FnOnce(&mut Vec<u8>)) {
+    if let Some(pad_len) = padding {
+        payload.push(pad_len);
+    }
+    body(payload);
+    payload.resize(payload.len() + padding.unwrap_or(0) as usize, 0);
+}
+
+fn decode_priority(stream_id: u32, fields: &[u8; 5]) -> Result<Priority, FrameError> {
+    let priority = Priority {
+        dependency: self::stream_id(fields),
+        exclusive: fields[0] & 0x80 != 0,
+        weight: fields[4],
+    };
+    if priority.dependency == stream_id {
+        return Err(FrameError::Stream(stream_id, ErrorCode::ProtocolError));
+    }
+    Ok(priority)
+}
+
+fn encode_priority(payload: &mut Vec<u8>, priority: &Priority) {
+    let exclusive = if priority.exclusive { 0x80000000 } else { 0 };
+    payload.extend(((priority.dependency & 0x7FFFFFFF) | exclusive).to_be_bytes());
+    payload.push(priority.weight);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(frame: Frame) {
+        let bytes = frame.encode();
+        assert_eq!(
+            Frame::parse(&bytes, 1 << 14),
+            Ok(Some((frame.clone(), bytes.len()))),
+            "{frame:?}"
+        );
+    }
+
+    // Builds a frame by hand, so it can be anything the codec would refuse to write
+    fn raw(frame_type: u8, flags: u8, stream_id: u32, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = (payload.len() as u32).to_be_bytes()[1..].to_vec();
+        bytes.extend([frame_type, flags]);
+        bytes.extend(stream_id.to_be_bytes());
+        bytes.extend(payload);
+        bytes
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Frame, FrameError> {
+        Frame::parse(bytes, 1 << 14).map(|parsed| parsed.unwrap().0)
+    }
+
+    const PRIORITY_5: Priority = Priority {
+        dependency: 5,
+        exclusive: true,
+        weight: 15,
+    };
+
+    #[test]
+    fn every_frame_type_round_trips() {
+        for frame in [
+            Frame::Data {
+                stream_id: 1,
+                data: b"hello".to_vec(),
+                end_stream: true,
+                padding: None,
+            },
+            Frame::Data {
+                stream_id: 3,
+                data: Vec::new(),
+                end_stream: false,
+                padding: Some(7),
+            },
+            Frame::Headers {
+                stream_id: 1,
+                block: vec![0x82, 0x86, 0x84],
+                priority: Some(PRIORITY_5),
+                end_stream: false,
+                end_headers: true,
+                padding: Some(3),
+            },
+            Frame::Headers {
+                stream_id: 7,
+                block: vec![0x88],
+                priority: None,
+                end_stream: true,
+                end_headers: false,
+                padding: None,
+            },
+            Frame::Priority {
+                stream_id: 3,
+                priority: PRIORITY_5,
+            },
+            Frame::RstStream {
+                stream_id: 5,
+                code: ErrorCode::Cancel,
+            },
+            Frame::Settings {
+                ack: false,
+                parameters: vec![(0x3, 100), (0x4, 1 << 20)],
+            },
+            Frame::Settings {
+                ack: true,
+                parameters: Vec::new(),
+            },
+            Frame::PushPromise {
+                stream_id: 1,
+                promised_id: 2,
+                block: vec![0x82],
+                end_headers: true,
+                padding: Some(0),
+            },
+            Frame::Ping {
+                ack: true,
+                data: *b"12345678",
+            },
+            Frame::GoAway {
+                last_stream_id: 9,
+                code: ErrorCode::EnhanceYourCalm,
+                debug_data: b"slow down".to_vec(),
+            },
+            Frame::WindowUpdate {
+                stream_id: 0,
+                increment: (1 << 31) - 1,
+            },
+            Frame::Continuation {
+                stream_id: 7,
+                block: vec![0x84],
+                end_headers: true,
+            },
+            Frame::Unknown {
+                frame_type: 0xfa,
+                flags: 0xff,
+                stream_id: 11,
+                payload: b"ignored".to_vec(),
+            },
+        ] {
+            round_trip(frame);
+        }
+    }
+
+    #[test]
+    fn padding_is_stripped() {
+        // DATA with 2 bytes of padding: pad length, "hi", then the padding itself
+        let bytes = raw(DATA, PADDED | END_STREAM, 1, &[2, b'h', b'i', 0, 0]);
+        assert_eq!(
+            parse(&bytes),
+            Ok(Frame::Data {
+                stream_id: 1,
+                data: b"hi".to_vec(),
+                end_stream: true,
+                padding: Some(2),
+            })
+        );
+
+        let too_long = raw(HEADERS, PADDED | END_HEADERS, 1, &[4, 0x82, 0, 0]);
+        assert!(matches!(
+            parse(&too_long),
+            Err(FrameError::Connection(ErrorCode::ProtocolError, _))
+        ));
+        let no_pad_length = raw(DATA, PADDED, 1, &[]);
+        assert!(matches!(
+            parse(&no_pad_length),
+            Err(FrameError::Connection(ErrorCode::FrameSizeError, _))
+        ));
+    }
+
+    #[test]
+    fn partial_frames_wait_for_more() {
+        let bytes = Frame::Ping {
+            ack: false,
+            data: [1; 8],
+        }
+        .encode();
+
+        for len in [0, 5, FRAME_HEADER_LEN, bytes.len() - 1] {
+            assert_eq!(Frame::parse(&bytes[..len], 1 << 14), Ok(None));
+        }
+        // Only the first frame is taken from a buffer holding more
+        let mut two = bytes.clone();
+        two.extend(&bytes);
+        assert_eq!(Frame::parse(&two, 1 << 14).unwrap().unwrap().1, bytes.len());
+    }
+
+    #[test]
+    fn oversized_frames_fail_on_their_header() {
+        let bytes = raw(DATA, 0, 1, &[0; 100]);
+        assert!(matches!(
+            Frame::parse(&bytes[..FRAME_HEADER_LEN], 99),
+            Err(FrameError::Connection(ErrorCode::FrameSizeError, _))
+        ));
+    }
+
+    #[test]
+    fn frames_on_the_wrong_stream_are_connection_errors() {
+        for bytes in [
+            raw(DATA, 0, 0, b"x"),
+            raw(HEADERS, END_HEADERS, 0, &[0x82]),
+            raw(RST_STREAM, 0, 0, &[0; 4]),
+            raw(CONTINUATION, END_HEADERS, 0, &[]),
+            raw(SETTINGS, 0, 1, &[]),
+            raw(PING, 0, 1, &[0; 8]),
+            raw(GOAWAY, 0, 3, &[0; 8]),
+        ] {
+            assert!(
+                matches!(
+                    parse(&bytes),
+                    Err(FrameError::Connection(ErrorCode::ProtocolError, _))
+                ),
+                "{bytes:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn wrong_lengths_are_frame_size_errors() {
+        for bytes in [
+            raw(RST_STREAM, 0, 1, &[0; 3]),
+            raw(SETTINGS, 0, 0, &[0; 5]),
+            raw(SETTINGS, ACK, 0, &[0; 6]),
+            raw(PING, 0, 0, &[0; 7]),
+            raw(GOAWAY, 0, 0, &[0; 7]),
+            raw(WINDOW_UPDATE, 0, 1, &[0; 5]),
+            raw(HEADERS, PRIORITY_FLAG, 1, &[0; 4]),
+            raw(PUSH_PROMISE, 0, 1, &[0; 3]),
+        ] {
+            assert!(
+                matches!(
+                    parse(&bytes),
+                    Err(FrameError::Connection(ErrorCode::FrameSizeError, _))
+                ),
+                "{bytes:?}"
+            );
+        }
+
+        // PRIORITY changes nothing beyond its own stream
+        assert_eq!(
+            parse(&raw(PRIORITY, 0, 3, &[0; 4])),
+            Err(FrameError::Stream(3, ErrorCode::FrameSizeError))
+        );
+    }
+
+    #[test]
+    fn stream_errors_stay_on_their_stream() {
+        assert_eq!(
+            parse(&raw(WINDOW_UPDATE, 0, 3, &[0; 4])),
+            Err(FrameError::Stream(3, ErrorCode::ProtocolError))
+        );
+        assert!(matches!(
+            parse(&raw(WINDOW_UPDATE, 0, 0, &[0; 4])),
+            Err(FrameError::Connection(ErrorCode::ProtocolError, _))
+        ));
+
+        // A stream cannot depend on itself
+        assert_eq!(
+            parse(&raw(PRIORITY, 0, 5, &[0, 0, 0, 5, 16])),
+            Err(FrameError::Stream(5, ErrorCode::ProtocolError))
+        );
+    }
+
+    #[test]
+    fn reserved_bits_are_ignored() {
+        let bytes = raw(WINDOW_UPDATE, 0, 0x80000001, &[0x80, 0, 0, 1]);
+        assert_eq!(
+            parse(&bytes),
+            Ok(Frame::WindowUpdate {
+                stream_id: 1,
+                increment: 1,
+            })
+        );
+    }
+}
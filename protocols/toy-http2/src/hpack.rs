@@ -0,0 +1,583 @@
+//! HPACK header compression (RFC 7541).
+//!
+//! Each side of a connection keeps one [`Encoder`] for the header blocks it sends and one
+//! [`Decoder`] for those it receives, since both carry a dynamic table that follows every
+//! block in order.
+
+mod huffman;
+mod table;
+
+use std::{error::Error, fmt};
+
+use table::{DynamicTable, entry_size};
+
+/// Dynamic table size both sides start with, before any SETTINGS_HEADER_TABLE_SIZE.
+pub const DEFAULT_TABLE_SIZE: usize = 4096;
+
+/// Why a header block could not be decoded. Any of these is a connection error of type
+/// COMPRESSION_ERROR, as the dynamic tables can no longer agree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The block ended inside a representation.
+    Truncated,
+    /// An integer did not fit in a `usize`.
+    IntegerOverflow,
+    /// An index past the end of both tables, or zero.
+    InvalidIndex(usize),
+    /// A Huffman string with bad padding or an end-of-string symbol.
+    InvalidHuffman,
+    /// A name or value that was not UTF-8.
+    InvalidUtf8,
+    /// A dynamic table size update after a field, or above the allowed maximum.
+    InvalidSizeUpdate(usize),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "truncated header block"),
+            DecodeError::IntegerOverflow => write!(f, "integer overflow in header block"),
+            DecodeError::InvalidIndex(index) => write!(f, "invalid table index {index}"),
+            DecodeError::InvalidHuffman => write!(f, "invalid Huffman-encoded string"),
+            DecodeError::InvalidUtf8 => write!(f, "header field is not UTF-8"),
+            DecodeError::InvalidSizeUpdate(size) => {
+                write!(f, "invalid dynamic table size update to {size}")
+            }
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Turns header lists into header blocks.
+#[derive(Debug)]
+pub struct Encoder {
+    table: DynamicTable,
+    huffman: bool,
+    /// Smallest size the table had since the last block, then its current size, both of
+    /// which the next block has to announce.
+    size_update: Option<(usize, usize)>,
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new(DEFAULT_TABLE_SIZE)
+    }
+}
+
+impl Encoder {
+    /// An encoder whose dynamic table starts out holding at most `max_table_size` bytes.
+    pub fn new(max_table_size: usize) -> Self {
+        Self {
+            table: DynamicTable::new(max_table_size),
+            huffman: true,
+            size_update: None,
+        }
+    }
+
+    /// Whether strings are Huffman-coded when that does not make them longer; on by default.
+    pub fn huffman(mut self, enabled: bool) -> Self {
+        self.huffman = enabled;
+        self
+    }
+
+    /// Resizes the dynamic table, e.g. after the peer's SETTINGS_HEADER_TABLE_SIZE, and
+    /// announces the change at the start of the next block.
+    pub fn set_max_table_size(&mut self, size: usize) {
+        let smallest = match self.size_update {
+            Some((smallest, _)) => smallest.min(size),
+            None => self.table.max_size().min(size),
+        };
+        self.size_update = Some((smallest, size));
+        self.table.set_max_size(size);
+    }
+
+    /// Encodes one header block. Names should already be lowercase.
+    pub fn encode<'a>(&mut self, headers: impl IntoIterator<Item = (&'a str, &'a str)>) -> Vec<u8> {
+        let mut out = Vec::new();
+        if let Some((smallest, size)) = self.size_update.take() {
+            if smallest < size {
+                encode_integer(smallest, 5, 0x20, &mut out);
+            }
+            encode_integer(size, 5, 0x20, &mut out);
+        }
+
+        for (name, value) in headers {
+            let found = self.table.find(name, value);
+            if let Some((index, true)) = found {
+                encode_integer(index, 7, 0x80, &mut out);
+                continue;
+            }
+
+            // Index the field unless it would only flush the table
+            let indexing = entry_size(name, value) <= self.table.max_size();
+            let (prefix, flags) = if indexing { (6, 0x40) } else { (4, 0x00) };
+            match found {
+                Some((index, _)) => encode_integer(index, prefix, flags, &mut out),
+                None => {
+                    out.push(flags);
+                    self.encode_string(name, &mut out);
+                }
+            }
+            self.encode_string(value, &mut out);
+
+            if indexing {
+                self.table.insert(name.to_string(), value.to_string());
+            }
+        }
+        out
+    }
+
+    fn encode_string(&self, s: &str, out: &mut Vec<u8>) {
+        let encoded_len = huffman::encoded_len(s.as_bytes());
+        if self.huffman && encoded_len <= s.len() {
+            encode_integer(encoded_len, 7, 0x80, out);
+            huffman::encode(s.as_bytes(), out);
+        } else {
+            encode_integer(s.len(), 7, 0x00, out);
+            out.extend_from_slice(s.as_bytes());
+        }
+    }
+}
+
+/// Turns header blocks back into header lists.
+#[derive(Debug)]
+pub struct Decoder {
+    table: DynamicTable,
+    /// Most the peer may grow the table to, i.e. our SETTINGS_HEADER_TABLE_SIZE.
+    max_table_size: usize,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new(DEFAULT_TABLE_SIZE)
+    }
+}
+
+impl Decoder {
+    /// A decoder for a peer whose dynamic table holds at most `max_table_size` bytes.
+    pub fn new(max_table_size: usize) -> Self {
+        Self {
+            table: DynamicTable::new(max_table_size),
+            max_table_size,
+        }
+    }
+
+    /// Changes the limit the peer's size updates must respect, once our SETTINGS carrying
+    /// it are acknowledged.
+    pub fn set_max_table_size(&mut self, size: usize) {
+        self.max_table_size = size;
+        if self.table.max_size() > size {
+            self.table.set_max_size(size);
+        }
+    }
+
+    /// Decodes one complete header block, with CONTINUATION payloads already joined on.
+    pub fn decode(&mut self, mut block: &[u8]) -> Result<Vec<(String, String)>, DecodeError> {
+        let input = &mut block;
+        let mut headers = Vec::new();
+
+        while let Some(&first) = input.first() {
+            if first & 0x80 != 0 {
+                // Indexed field
+                let index = decode_integer(input, 7)?;
+                let (name, value) = self.lookup(index)?;
+                headers.push((name.to_string(), value.to_string()));
+            } else if first & 0xe0 == 0x20 {
+                let size = decode_integer(input, 5)?;
+                if !headers.is_empty() || size > self.max_table_size {
+                    return Err(DecodeError::InvalidSizeUpdate(size));
+                }
+                self.table.set_max_size(size);
+            } else {
+                // Literal with incremental indexing, without indexing or never indexed
+                let indexing = first & 0x40 != 0;
+                let index = decode_integer(input, if indexing { 6 } else { 4 })?;
+                let name = match index {
+                    0 => decode_string(input)?,
+                    _ => self.lookup(index)?.0.to_string(),
+                };
+                let value = decode_string(input)?;
+                if indexing {
+                    self.table.insert(name.clone(), value.clone());
+                }
+                headers.push((name, value));
+            }
+        }
+        Ok(headers)
+    }
+
+    fn lookup(&self, index: usize) -> Result<(&str, &str), DecodeError> {
+        self.table
+            .get(index)
+            .ok_or(DecodeError::InvalidIndex(index))
+    }
+}
+
+/// Appends `value` with an N-bit prefix, the rest of the first byte holding `flags`.
+fn encode_integer(value: usize, prefix: u8, flags: u8, out: &mut Vec<u8>) {
+    let max = (1 << prefix) - 1;
+    if value < max {
+        out.push(flags | value as u8);
+        return;
+    }
+    out.push(flags | max as u8);
+    let mut rest = value - max;
+    while rest >= 0x80 {
+        out.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    out.push(rest as u8);
+}
+
+/// Reads an integer with an N-bit prefix off the front of `input`.
+fn decode_integer(input: &mut &[u8], prefix: u8) -> Result<usize, DecodeError> {
+    let (&first, rest) = input.split_first().ok_or(DecodeError::Truncated)?;
+    *input = rest;
+    let max = (1 << prefix) - 1;
+    let mut value = first as usize & max;
+    if value < max {
+        return Ok(value);
+    }
+
+    let mut shift = 0;
+    loop {
+        let (&byte, rest) = input.split_first().ok_or(DecodeError::Truncated)?;
+        *input = rest;
+        let more = ((byte & 0x7f) as usize)
+            .checked_shl(shift)
+            .filter(|more| more >> shift == (byte & 0x7f) as usize)
+            .ok_or(DecodeError::IntegerOverflow)?;
+        value = value
+            .checked_add(more)
+            .ok_or(DecodeError::IntegerOverflow)?;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+/// Reads a length-prefixed, possibly Huffman-coded string off the front of `input`.
+fn decode_string(input: &mut &[u8]) -> Result<String, DecodeError> {
+    let huffman = input.first().is_some_and(|&b| b & 0x80 != 0);
+    let len = decode_integer(input, 7)?;
+    if input.len() < len {
+        return Err(DecodeError::Truncated);
+    }
+    let (raw, rest) = input.split_at(len);
+    *input = rest;
+
+    let bytes = if huffman {
+        huffman::decode(raw)?
+    } else {
+        raw.to_vec()
+    };
+    String::from_utf8(bytes).map_err(|_| DecodeError::InvalidUtf8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        let digits: Vec<u8> = s.bytes().filter(u8::is_ascii_hexdigit).collect();
+        digits
+            .chunks(2)
+            .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+            .collect()
+    }
+
+    type Headers = &'static [(&'static str, &'static str)];
+
+    fn owned(headers: &[(&str, &str)]) -> Vec<(String, String)> {
+        headers
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    // Runs the blocks of one RFC example through a fresh encoder and decoder in turn
+    fn check_sequence(
+        mut encoder: Encoder,
+        mut decoder: Decoder,
+        blocks: &[(&str, Headers, usize)],
+    ) {
+        for (i, &(wire, headers, table_size)) in blocks.iter().enumerate() {
+            assert_eq!(
+                encoder.encode(headers.iter().copied()),
+                hex(wire),
+                "block {i}"
+            );
+            assert_eq!(encoder.table.size(), table_size, "block {i}");
+            assert_eq!(
+                decoder.decode(&hex(wire)).unwrap(),
+                owned(headers),
+                "block {i}"
+            );
+            assert_eq!(decoder.table.size(), table_size, "block {i}");
+        }
+    }
+
+    #[test]
+    fn integer_examples() {
+        // RFC 7541 C.1
+        for (value, prefix, wire) in [(10, 5, "0a"), (1337, 5, "1f9a0a"), (42, 8, "2a")] {
+            let mut out = Vec::new();
+            encode_integer(value, prefix, 0, &mut out);
+            assert_eq!(out, hex(wire));
+            assert_eq!(decode_integer(&mut &out[..], prefix), Ok(value));
+        }
+
+        assert_eq!(
+            decode_integer(&mut &hex("1f9a")[..], 5),
+            Err(DecodeError::Truncated)
+        );
+        assert_eq!(
+            decode_integer(&mut &hex("1fffffffffffffffffffff7f")[..], 5),
+            Err(DecodeError::IntegerOverflow)
+        );
+    }
+
+    #[test]
+    fn single_representations() {
+        // RFC 7541 C.2
+        let mut decoder = Decoder::default();
+
+        assert_eq!(
+            decoder
+                .decode(&hex(
+                    "400a 6375 7374 6f6d 2d6b 6579 0d63 7573 746f 6d2d 6865 6164 6572"
+                ))
+                .unwrap(),
+            owned(&[("custom-key", "custom-header")])
+        );
+        assert_eq!(decoder.table.size(), 55);
+
+        let mut decoder = Decoder::default();
+        assert_eq!(
+            decoder
+                .decode(&hex("040c 2f73 616d 706c 652f 7061 7468"))
+                .unwrap(),
+            owned(&[(":path", "/sample/path")])
+        );
+        assert_eq!(
+            decoder
+                .decode(&hex("1008 7061 7373 776f 7264 0673 6563 7265 74"))
+                .unwrap(),
+            owned(&[("password", "secret")])
+        );
+        assert_eq!(
+            decoder.decode(&hex("82")).unwrap(),
+            owned(&[(":method", "GET")])
+        );
+        assert_eq!(decoder.table.size(), 0);
+    }
+
+    const REQUEST_1: Headers = &[
+        (":method", "GET"),
+        (":scheme", "http"),
+        (":path", "/"),
+        (":authority", "www.example.com"),
+    ];
+    const REQUEST_2: Headers = &[
+        (":method", "GET"),
+        (":scheme", "http"),
+        (":path", "/"),
+        (":authority", "www.example.com"),
+        ("cache-control", "no-cache"),
+    ];
+    const REQUEST_3: Headers = &[
+        (":method", "GET"),
+        (":scheme", "https"),
+        (":path", "/index.html"),
+        (":authority", "www.example.com"),
+        ("custom-key", "custom-value"),
+    ];
+
+    #[test]
+    fn requests_without_huffman() {
+        // RFC 7541 C.3
+        check_sequence(
+            Encoder::default().huffman(false),
+            Decoder::default(),
+            &[
+                (
+                    "8286 8441 0f77 7777 2e65 7861 6d70 6c65 2e63 6f6d",
+                    REQUEST_1,
+                    57,
+                ),
+                ("8286 84be 5808 6e6f 2d63 6163 6865", REQUEST_2, 110),
+                (
+                    "8287 85bf 400a 6375 7374 6f6d 2d6b 6579 0c63 7573 746f 6d2d 7661 6c75 65",
+                    REQUEST_3,
+                    164,
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn requests_with_huffman() {
+        // RFC 7541 C.4
+        check_sequence(
+            Encoder::default(),
+            Decoder::default(),
+            &[
+                ("8286 8441 8cf1 e3c2 e5f2 3a6b a0ab 90f4 ff", REQUEST_1, 57),
+                ("8286 84be 5886 a8eb 1064 9cbf", REQUEST_2, 110),
+                (
+                    "8287 85bf 4088 25a8 49e9 5ba9 7d7f 8925 a849 e95b b8e8 b4bf",
+                    REQUEST_3,
+                    164,
+                ),
+            ],
+        );
+    }
+
+    const RESPONSE_1: Headers = &[
+        (":status", "302"),
+        ("cache-control", "private"),
+        ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+        ("location", "https://www.example.com"),
+    ];
+    const RESPONSE_2: Headers = &[
+        (":status", "307"),
+        ("cache-control", "private"),
+        ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+        ("location", "https://www.example.com"),
+    ];
+    const RESPONSE_3: Headers = &[
+        (":status", "200"),
+        ("cache-control", "private"),
+        ("date", "Mon, 21 Oct 2013 20:13:22 GMT"),
+        ("location", "https://www.example.com"),
+        ("content-encoding", "gzip"),
+        (
+            "set-cookie",
+            "foo=ASDJKHQKBZXOQWEOPIUAXQWEOIU; max-age=3600; version=1",
+        ),
+    ];
+
+    #[test]
+    fn responses_without_huffman_evict() {
+        // RFC 7541 C.5, with a 256-byte table
+        check_sequence(
+            Encoder::new(256).huffman(false),
+            Decoder::new(256),
+            &[
+                (
+                    "4803 3330 3258 0770 7269 7661 7465 611d 4d6f 6e2c 2032 3120 4f63 7420 \
+                     3230 3133 2032 303a 3133 3a32 3120 474d 546e 1768 7474 7073 3a2f 2f77 \
+                     7777 2e65 7861 6d70 6c65 2e63 6f6d",
+                    RESPONSE_1,
+                    222,
+                ),
+                ("4803 3330 37c1 c0bf", RESPONSE_2, 222),
+                (
+                    "88c1 611d 4d6f 6e2c 2032 3120 4f63 7420 3230 3133 2032 303a 3133 3a32 \
+                     3220 474d 54c0 5a04 677a 6970 7738 666f 6f3d 4153 444a 4b48 514b 425a \
+                     584f 5157 454f 5049 5541 5851 5745 4f49 553b 206d 6178 2d61 6765 3d33 \
+                     3630 303b 2076 6572 7369 6f6e 3d31",
+                    RESPONSE_3,
+                    215,
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn responses_with_huffman_evict() {
+        // RFC 7541 C.6, with a 256-byte table
+        check_sequence(
+            Encoder::new(256),
+            Decoder::new(256),
+            &[
+                (
+                    "4882 6402 5885 aec3 771a 4b61 96d0 7abe 9410 54d4 44a8 2005 9504 0b81 \
+                     66e0 82a6 2d1b ff6e 919d 29ad 1718 63c7 8f0b 97c8 e9ae 82ae 43d3",
+                    RESPONSE_1,
+                    222,
+                ),
+                ("4883 640e ffc1 c0bf", RESPONSE_2, 222),
+                (
+                    "88c1 6196 d07a be94 1054 d444 a820 0595 040b 8166 e084 a62d 1bff c05a \
+                     839b d9ab 77ad 94e7 821d d7f2 e6c7 b335 dfdf cd5b 3960 d5af 2708 7f36 \
+                     72c1 ab27 0fb5 291f 9587 3160 65c0 03ed 4ee5 b106 3d50 07",
+                    RESPONSE_3,
+                    215,
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn size_updates_are_announced_and_enforced() {
+        let mut encoder = Encoder::default();
+        let mut decoder = Decoder::default();
+        decoder
+            .decode(&encoder.encode([("x-first", "1"), ("x-second", "2")]))
+            .unwrap();
+
+        // Shrinking to 0 and back clears both tables, so both sizes are sent
+        encoder.set_max_table_size(0);
+        encoder.set_max_table_size(100);
+        let block = encoder.encode([("x-third", "3")]);
+        assert_eq!(&block[..2], [0x20, 0x3f]);
+        assert_eq!(decoder.decode(&block).unwrap(), owned(&[("x-third", "3")]));
+        assert_eq!(decoder.table.size(), entry_size("x-third", "3"));
+        assert_eq!(decoder.table.get(63), None);
+
+        // Above our limit, or after a field
+        decoder.set_max_table_size(64);
+        assert_eq!(
+            decoder.decode(&hex("3f 22")),
+            Err(DecodeError::InvalidSizeUpdate(65))
+        );
+        assert_eq!(
+            decoder.decode(&hex("82 20")),
+            Err(DecodeError::InvalidSizeUpdate(0))
+        );
+    }
+
+    #[test]
+    fn malformed_blocks_are_errors() {
+        let mut decoder = Decoder::default();
+
+        assert_eq!(
+            decoder.decode(&hex("80")),
+            Err(DecodeError::InvalidIndex(0))
+        );
+        assert_eq!(
+            decoder.decode(&hex("be")),
+            Err(DecodeError::InvalidIndex(62))
+        );
+        assert_eq!(
+            decoder.decode(&hex("4005 6162")),
+            Err(DecodeError::Truncated)
+        );
+        assert_eq!(
+            decoder.decode(&hex("0001 ff00")),
+            Err(DecodeError::InvalidUtf8)
+        );
+        assert_eq!(
+            decoder.decode(&hex("0081 00 00")),
+            Err(DecodeError::InvalidHuffman)
+        );
+    }
+
+    #[test]
+    fn large_fields_are_not_indexed() {
+        let mut encoder = Encoder::new(64);
+        let value = "v".repeat(100);
+
+        let block = encoder.encode([("x-big", value.as_str())]);
+
+        assert_eq!(block[0], 0x00);
+        assert_eq!(encoder.table.size(), 0);
+        assert_eq!(
+            Decoder::new(64).decode(&block).unwrap(),
+            owned(&[("x-big", &value)])
+        );
+    }
+}
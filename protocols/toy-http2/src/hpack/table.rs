@@ -0,0 +1,202 @@
+//! The static table of RFC 7541 Appendix A and the per-connection dynamic table.
+
+use std::collections::VecDeque;
+
+/// Entries 1 to 61; index 0 is unused.
+pub(crate) const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// What an entry counts against the table size: its name and value plus 32 bytes.
+pub(crate) fn entry_size(name: &str, value: &str) -> usize {
+    name.len() + value.len() + 32
+}
+
+/// Recently added header fields, newest first, evicted oldest first to stay within
+/// `max_size`.
+#[derive(Debug)]
+pub(crate) struct DynamicTable {
+    entries: VecDeque<(String, String)>,
+    size: usize,
+    max_size: usize,
+}
+
+impl DynamicTable {
+    pub(crate) fn new(max_size: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            size: 0,
+            max_size,
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    pub(crate) fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Shrinks or grows the table, evicting entries that no longer fit.
+    pub(crate) fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+        self.evict(0);
+    }
+
+    /// Adds an entry, which empties the table if the entry alone is too big.
+    pub(crate) fn insert(&mut self, name: String, value: String) {
+        let size = entry_size(&name, &value);
+        self.evict(size);
+        if size <= self.max_size {
+            self.size += size;
+            self.entries.push_front((name, value));
+        }
+    }
+
+    fn evict(&mut self, room: usize) {
+        while self.size + room > self.max_size
+            && let Some((name, value)) = self.entries.pop_back()
+        {
+            self.size -= entry_size(&name, &value);
+        }
+    }
+
+    /// The entry at `index` in the combined address space, where 62 is the newest entry.
+    pub(crate) fn get(&self, index: usize) -> Option<(&str, &str)> {
+        match index {
+            0 => None,
+            1..=61 => Some(STATIC_TABLE[index - 1]),
+            _ => self
+                .entries
+                .get(index - 62)
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        }
+    }
+
+    /// The lowest index holding `name` and `value`, or failing that just `name`, as
+    /// `(index, value matched)`.
+    pub(crate) fn find(&self, name: &str, value: &str) -> Option<(usize, bool)> {
+        let all = STATIC_TABLE.iter().copied().chain(
+            self.entries
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        );
+        let mut by_name = None;
+        for (i, (n, v)) in all.enumerate() {
+            if n == name {
+                if v == value {
+                    return Some((i + 1, true));
+                }
+                by_name.get_or_insert(i + 1);
+            }
+        }
+        by_name.map(|index| (index, false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexes_span_both_tables() {
+        let mut table = DynamicTable::new(4096);
+        table.insert("a".into(), "1".into());
+        table.insert("b".into(), "2".into());
+
+        assert_eq!(table.get(2), Some((":method", "GET")));
+        assert_eq!(table.get(61), Some(("www-authenticate", "")));
+        assert_eq!(table.get(62), Some(("b", "2")));
+        assert_eq!(table.get(63), Some(("a", "1")));
+        assert_eq!(table.get(64), None);
+        assert_eq!(table.get(0), None);
+        assert_eq!(table.find("a", "1"), Some((63, true)));
+        assert_eq!(table.find(":status", "302"), Some((8, false)));
+        assert_eq!(table.find("x-none", ""), None);
+    }
+
+    #[test]
+    fn oldest_entries_are_evicted() {
+        // Room for two 34-byte entries
+        let mut table = DynamicTable::new(70);
+        table.insert("a".into(), "1".into());
+        table.insert("b".into(), "2".into());
+        table.insert("c".into(), "3".into());
+
+        assert_eq!(table.size(), 68);
+        assert_eq!(table.get(62), Some(("c", "3")));
+        assert_eq!(table.get(63), Some(("b", "2")));
+        assert_eq!(table.get(64), None);
+
+        table.set_max_size(40);
+        assert_eq!(table.size(), 34);
+        assert_eq!(table.get(63), None);
+
+        // Too big to fit at all, so it only clears the table
+        table.insert("long".into(), "x".repeat(10));
+        assert_eq!(table.size(), 0);
+        assert_eq!(table.get(62), None);
+    }
+}
@@ -0,0 +1,15 @@
+//! Pieces shared by the toy HTTP/2 server and client.
+
+pub mod error;
+pub mod flow;
+pub mod frame;
+pub mod hpack;
+pub mod settings;
+
+pub use error::ErrorCode;
+pub use flow::Window;
+pub use frame::{Frame, FrameError};
+pub use settings::Settings;
+
+/// What a client sends first on every HTTP/2 connection, ahead of its SETTINGS frame.
+pub const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
@@ -0,0 +1,249 @@
+//! SETTINGS parameters (RFC 9113 section 6.5.2).
+
+use crate::ErrorCode;
+
+pub const HEADER_TABLE_SIZE: u16 = 0x1;
+pub const ENABLE_PUSH: u16 = 0x2;
+pub const MAX_CONCURRENT_STREAMS: u16 = 0x3;
+pub const INITIAL_WINDOW_SIZE: u16 = 0x4;
+pub const MAX_FRAME_SIZE: u16 = 0x5;
+pub const MAX_HEADER_LIST_SIZE: u16 = 0x6;
+
+/// Smallest and largest values SETTINGS_MAX_FRAME_SIZE may take.
+pub const MIN_MAX_FRAME_SIZE: u32 = 1 << 14;
+pub const MAX_MAX_FRAME_SIZE: u32 = (1 << 24) - 1;
+
+/// Largest flow-control window, and so largest SETTINGS_INITIAL_WINDOW_SIZE.
+pub const MAX_WINDOW_SIZE: u32 = (1 << 31) - 1;
+
+/// The parameters one endpoint has announced, starting from the protocol defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    pub header_table_size: u32,
+    pub enable_push: bool,
+    /// `None` while unlimited.
+    pub max_concurrent_streams: Option<u32>,
+    pub initial_window_size: u32,
+    pub max_frame_size: u32,
+    /// `None` while unlimited.
+    pub max_header_list_size: Option<u32>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            header_table_size: 4096,
+            enable_push: true,
+            max_concurrent_streams: None,
+            initial_window_size: 65_535,
+            max_frame_size: MIN_MAX_FRAME_SIZE,
+            max_header_list_size: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Applies the parameters of a SETTINGS frame in order, ignoring unknown ones.
+    ///
+    /// Fails with the error code the whole connection has to be closed with.
+    pub fn apply(&mut self, parameters: &[(u16, u32)]) -> Result<(), ErrorCode> {
+        for &(id, value) in parameters {
+            match id {
+                HEADER_TABLE_SIZE => self.header_table_size = value,
+                ENABLE_PUSH => {
+                    self.enable_push = match value {
+                        0 => false,
+                        1 => true,
+                        _ => return Err(ErrorCode::ProtocolError),
+                    }
+                }
+                MAX_CONCURRENT_STREAMS => self.max_concurrent_streams = Some(value),
+                INITIAL_WINDOW_SIZE => {
+                    if value > MAX_WINDOW_SIZE {
+                        return Err(ErrorCode::FlowControlError);
+                    }
+                    self.initial_window_size = value;
+                }
+                MAX_FRAME_SIZE => {
+                    if !(MIN_MAX_FRAME_SIZE..=MAX_MAX_FRAME_SIZE).contains(&value) {
+                        return Err(ErrorCode::ProtocolError);
+                    }
+                    self.max_frame_size = value;
+                }
+                MAX_HEADER_LIST_SIZE => self.max_header_list_size = Some(value),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The parameters that differ from the defaults, for a SETTINGS frame.
+    pub fn parameters(&self) -> Vec<(u16, u32)> {
+        let defaults = Settings::default();
+        let parameters = [
+            (
+                HEADER_TABLE_SIZE,
+                Some(self.header_table_size).filter(|&v| v != defaults.header_table_size),
+            ),
+            (ENABLE_PUSH, (!self.enable_push).then_some(0)),
+            (MAX_CONCURRENT_STREAMS, self.max_concurrent_streams),
+            (
+                INITIAL_WINDOW_SIZE,
+                Some(self.initial_window_size).filter(|&v| v != defaults.initial_window_size),
+            ),
+            (
+                MAX_FRAME_SIZE,
+                Some(self.max_frame_size).filter(|&v| v != defaults.max_frame_size),
+            ),
+            (MAX_HEADER_LIST_SIZE, self.max_header_list_size),
+        ];
+
+        parameters
+            .into_iter()
+            .filter_map(|(id, value)| Some((id, value?)))
+            .collect()
+    }
+}
+
+/// The base64url alphabet the HTTP2-Settings header is written in.
+const BASE64URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Writes SETTINGS parameters as the value of an h2c upgrade's HTTP2-Settings header: the
+/// frame payload in base64url without padding (RFC 7540 section 3.2.1).
+pub fn to_header(parameters: &[(u16, u32)]) -> String {
+    let payload: Vec<u8> = parameters
+        .iter()
+        .flat_map(|(id, value)| [&id.to_be_bytes()[..], &value.to_be_bytes()].concat())
+        .collect();
+
+    let mut header = String::new();
+    for chunk in payload.chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |bits, (i, &b)| bits | (b as u32) << (16 - 8 * i));
+        // Three bytes make four characters, and a shorter tail one more than its length
+        for i in 0..=chunk.len() {
+            header.push(BASE64URL[(bits >> (18 - 6 * i)) as usize & 0x3f] as char);
+        }
+    }
+    header
+}
+
+/// Reads the parameters out of an HTTP2-Settings header, or `None` if it is not valid
+/// base64url of a whole number of parameters.
+pub fn from_header(value: &str) -> Option<Vec<(u16, u32)>> {
+    // Some clients pad anyway
+    let value = value.trim().trim_end_matches('=');
+    if value.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut payload = Vec::with_capacity(value.len() * 3 / 4);
+    for chunk in value.as_bytes().chunks(4) {
+        let mut bits = 0u32;
+        for (i, &c) in chunk.iter().enumerate() {
+            let digit = BASE64URL.iter().position(|&d| d == c)? as u32;
+            bits |= digit << (18 - 6 * i);
+        }
+        payload.extend_from_slice(&bits.to_be_bytes()[1..chunk.len()]);
+    }
+
+    if !payload.len().is_multiple_of(6) {
+        return None;
+    }
+    let parameters = payload
+        .chunks(6)
+        .map(|p| {
+            let id = u16::from_be_bytes([p[0], p[1]]);
+            (id, u32::from_be_bytes([p[2], p[3], p[4], p[5]]))
+        })
+        .collect();
+    Some(parameters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_not_sent() {
+        assert_eq!(Settings::default().parameters(), []);
+    }
+
+    #[test]
+    fn sent_settings_apply_back() {
+        let settings = Settings {
+            header_table_size: 0,
+            enable_push: false,
+            max_concurrent_streams: Some(100),
+            initial_window_size: 1 << 20,
+            max_frame_size: 1 << 20,
+            max_header_list_size: Some(8192),
+        };
+
+        let mut applied = Settings::default();
+        applied.apply(&settings.parameters()).unwrap();
+
+        assert_eq!(applied, settings);
+    }
+
+    #[test]
+    fn later_values_win_and_unknown_ones_are_ignored() {
+        let mut settings = Settings::default();
+        settings
+            .apply(&[
+                (HEADER_TABLE_SIZE, 100),
+                (0xff, 1),
+                (HEADER_TABLE_SIZE, 200),
+            ])
+            .unwrap();
+
+        assert_eq!(settings.header_table_size, 200);
+    }
+
+    #[test]
+    fn invalid_values_are_connection_errors() {
+        for (parameter, code) in [
+            ((ENABLE_PUSH, 2), ErrorCode::ProtocolError),
+            ((INITIAL_WINDOW_SIZE, 1 << 31), ErrorCode::FlowControlError),
+            ((MAX_FRAME_SIZE, 16_383), ErrorCode::ProtocolError),
+            ((MAX_FRAME_SIZE, 1 << 24), ErrorCode::ProtocolError),
+        ] {
+            assert_eq!(
+                Settings::default().apply(&[parameter]),
+                Err(code),
+                "{parameter:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn http2_settings_header_round_trips() {
+        // As curl sends it
+        assert_eq!(
+            from_header("AAMAAABkAAQCAAAAAAIAAAAA"),
+            Some(vec![
+                (MAX_CONCURRENT_STREAMS, 100),
+                (INITIAL_WINDOW_SIZE, 1 << 25),
+                (ENABLE_PUSH, 0)
+            ])
+        );
+        assert_eq!(from_header(""), Some(vec![]));
+
+        for parameters in [
+            vec![],
+            vec![(HEADER_TABLE_SIZE, 0)],
+            vec![(ENABLE_PUSH, 0), (MAX_FRAME_SIZE, 1 << 20)],
+        ] {
+            assert_eq!(from_header(&to_header(&parameters)), Some(parameters));
+        }
+    }
+
+    #[test]
+    fn bad_http2_settings_headers_are_refused() {
+        for value in ["AAMAAABk!AAQ", "AAMAAABkAA", "A", "AAMA+ABk"] {
+            assert_eq!(from_header(value), None, "{value}");
+        }
+    }
+}